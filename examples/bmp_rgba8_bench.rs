@@ -0,0 +1,51 @@
+use imagine::{
+  bmp::{bmp_try_bitmap_rgba, bmp_try_bitmap_rgba8},
+  Bitmap,
+};
+use pixel_formats::{r8g8b8a8_Srgb, r8g8b8a8_Unorm};
+use std::time::{Duration, Instant};
+
+/// Times `bmp_try_bitmap_rgba8` against the generic float path.
+///
+/// Run this with a folder of BMP files (`tests/bmp/valid/` by default). Only
+/// the 24-bit files are used, since that's the common case for the fast path.
+fn main() {
+  let args: Vec<String> = std::env::args().collect();
+  let dir = args.get(1).map(String::as_str).unwrap_or("tests/bmp/valid/");
+  let rounds: u32 = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(20);
+  let files: Vec<Vec<u8>> = match std::fs::read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|e| std::fs::read(e.ok()?.path()).ok())
+      // the bits per pixel field of the info header.
+      .filter(|bytes| bytes.starts_with(b"BM") && bytes.get(28..30) == Some(&[24, 0]))
+      .collect(),
+    Err(e) => {
+      println!("{e:?}");
+      return;
+    }
+  };
+  if files.is_empty() {
+    println!("no 24-bit BMP files found in `{dir}`.");
+    return;
+  }
+  println!("{} files, {rounds} rounds each", files.len());
+
+  let time = |f: &dyn Fn(&[u8])| {
+    let start = Instant::now();
+    for _ in 0..rounds {
+      files.iter().for_each(|bytes| f(bytes));
+    }
+    start.elapsed()
+  };
+  let float = time(&|bytes| {
+    let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(bytes, true).unwrap();
+    std::hint::black_box(bitmap);
+  });
+  let fast = time(&|bytes| {
+    let bitmap: Bitmap<r8g8b8a8_Unorm> = bmp_try_bitmap_rgba8(bytes, true).unwrap();
+    std::hint::black_box(bitmap);
+  });
+  let per_file = |d: Duration| d / (rounds * files.len() as u32);
+  println!("bmp_try_bitmap_rgba:  {float:?} ({:?} per file)", per_file(float));
+  println!("bmp_try_bitmap_rgba8: {fast:?} ({:?} per file)", per_file(fast));
+}
@@ -1,5 +1,8 @@
-use crate::borrowed_bitmap::*;
-use pixel_formats::r8g8b8a8_Srgb;
+use crate::{
+  borrowed_bitmap::*,
+  util::{linear_to_srgb, srgb_to_linear},
+};
+use pixel_formats::{r32g32b32_Sfloat, r32g32b32a32_Sfloat, r8_Unorm, r8g8b8a8_Srgb};
 
 /// An owned direct-color image.
 ///
@@ -37,6 +40,1863 @@ impl<P> Bitmap<P> {
     BorrowedBitmap { width: self.width, height: self.height, pixels: &mut self.pixels }
       .vertical_flip()
   }
+  /// Flips the image left to right.
+  #[inline]
+  pub fn horizontal_flip(&mut self) {
+    BorrowedBitmap { width: self.width, height: self.height, pixels: &mut self.pixels }
+      .horizontal_flip()
+  }
+  /// Flips the image across the given axis.
+  #[inline]
+  pub fn flip(&mut self, axis: Axis) {
+    BorrowedBitmap { width: self.width, height: self.height, pixels: &mut self.pixels }.flip(axis)
+  }
+  /// Flips the image across the diagonal from the top left to the bottom
+  /// right, which swaps the width and height.
+  #[inline]
+  pub fn transpose(&mut self)
+  where
+    P: Copy,
+  {
+    let w = self.width as usize;
+    let h = self.height as usize;
+    if w * h == 0 || self.pixels.len() < w * h {
+      core::mem::swap(&mut self.width, &mut self.height);
+      return;
+    }
+    let pixels =
+      (0..w).flat_map(|x| (0..h).map(move |y| y * w + x)).map(|i| self.pixels[i]).collect();
+    self.pixels = pixels;
+    core::mem::swap(&mut self.width, &mut self.height);
+  }
+  /// Applies an EXIF orientation value to the image, so that it displays the
+  /// right way up.
+  ///
+  /// The values are the standard EXIF ones:
+  /// * 1: Already upright, nothing is done.
+  /// * 2: Flipped left to right.
+  /// * 3: Rotated 180 degrees.
+  /// * 4: Flipped top to bottom.
+  /// * 5: Transposed (see [transpose](Self::transpose)).
+  /// * 6: Needs to be rotated 90 degrees clockwise.
+  /// * 7: Transposed and then rotated 180 degrees.
+  /// * 8: Needs to be rotated 90 degrees counter-clockwise.
+  ///
+  /// Values 5 through 8 swap the width and height. Any other value is ignored.
+  #[inline]
+  pub fn apply_exif_orientation(&mut self, orientation: u8)
+  where
+    P: Copy,
+  {
+    match orientation {
+      2 => self.horizontal_flip(),
+      3 => {
+        self.horizontal_flip();
+        self.vertical_flip();
+      }
+      4 => self.vertical_flip(),
+      5 => self.transpose(),
+      6 => {
+        self.transpose();
+        self.horizontal_flip();
+      }
+      7 => {
+        self.transpose();
+        self.horizontal_flip();
+        self.vertical_flip();
+      }
+      8 => {
+        self.transpose();
+        self.vertical_flip();
+      }
+      _ => (),
+    }
+  }
+  /// Iterates the rows of the image, starting from the bottom row.
+  #[inline]
+  pub fn rows_rev(&self) -> impl Iterator<Item = &[P]> {
+    self.pixels.chunks_exact((self.width as usize).max(1)).rev()
+  }
+  /// Iterates horizontal bands of `rows_per_band` rows each, top to bottom.
+  ///
+  /// Each item is `(start_row, pixels)`, where `pixels` covers every row of
+  /// the band. The last band is shorter if the height isn't a multiple of
+  /// `rows_per_band`. A `rows_per_band` of 0 is treated as 1.
+  #[inline]
+  pub fn row_bands(&self, rows_per_band: u32) -> impl Iterator<Item = (u32, &[P])> {
+    let rows_per_band = rows_per_band.max(1);
+    let band_len = (self.width as usize).max(1).saturating_mul(rows_per_band as usize);
+    self
+      .pixels
+      .chunks(band_len)
+      .enumerate()
+      .map(move |(i, band)| ((i as u32).saturating_mul(rows_per_band), band))
+  }
+  /// Like [row_bands](Self::row_bands), but the bands can be mutated.
+  ///
+  /// The bands don't overlap, so they can be handed out to different threads.
+  #[inline]
+  pub fn row_bands_mut(&mut self, rows_per_band: u32) -> impl Iterator<Item = (u32, &mut [P])> {
+    let rows_per_band = rows_per_band.max(1);
+    let band_len = (self.width as usize).max(1).saturating_mul(rows_per_band as usize);
+    self
+      .pixels
+      .chunks_mut(band_len)
+      .enumerate()
+      .map(move |(i, band)| ((i as u32).saturating_mul(rows_per_band), band))
+  }
+  /// Borrows the image as two images, above and below `row`.
+  ///
+  /// See [BorrowedBitmap::split_at_row]. This is the way to process an image
+  /// in parallel (with `std::thread::scope` or similar): split it into
+  /// separate bands and hand each band to a different thread.
+  #[inline]
+  #[must_use]
+  pub fn split_at_row(&mut self, row: u32) -> (BorrowedBitmap<'_, P>, BorrowedBitmap<'_, P>) {
+    BorrowedBitmap { width: self.width, height: self.height, pixels: &mut self.pixels }
+      .split_at_row(row)
+  }
+  /// Iterates the pixels of column `x`, top to bottom, or `None` if the column
+  /// is out of bounds.
+  #[inline]
+  pub fn column(&self, x: u32) -> Option<impl Iterator<Item = &P>> {
+    if x < self.width {
+      Some(
+        self.pixels.iter().skip(x as usize).step_by(self.width as usize).take(self.height as usize),
+      )
+    } else {
+      None
+    }
+  }
+  /// Copies out a rectangle of the image as a new image.
+  ///
+  /// The rectangle's top left is at `(x, y)`, and it's clipped to the bounds
+  /// of the image, so the output can be smaller than `width` by `height` (or
+  /// even empty).
+  #[inline]
+  #[must_use]
+  pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Bitmap<P>
+  where
+    P: Copy,
+  {
+    let x = x.min(self.width);
+    let y = y.min(self.height);
+    let width = width.min(self.width - x);
+    let height = height.min(self.height - y);
+    let mut pixels = alloc::vec::Vec::with_capacity((width as usize) * (height as usize));
+    for row in
+      self.pixels.chunks_exact((self.width as usize).max(1)).skip(y as usize).take(height as usize)
+    {
+      pixels.extend_from_slice(&row[(x as usize)..((x + width) as usize)]);
+    }
+    Bitmap { width, height, pixels }
+  }
+  /// Copies out a `width` by `height` rectangle from the middle of the image.
+  ///
+  /// Each size is clamped to the image's own size, so asking for more than the
+  /// image has just gives the whole image in that direction. When the extra
+  /// space can't be split evenly, the extra pixel is cut from the right or
+  /// bottom. Cropping to a square and then using
+  /// [downsample_box](Self::downsample_box) makes a "cover" style thumbnail.
+  #[inline]
+  #[must_use]
+  pub fn crop_centered(&self, width: u32, height: u32) -> Bitmap<P>
+  where
+    P: Copy,
+  {
+    let width = width.min(self.width);
+    let height = height.min(self.height);
+    self.crop((self.width - width) / 2, (self.height - height) / 2, width, height)
+  }
+  /// Makes a copy of the image with `pad` extra pixels on every side, filled
+  /// by reflecting the image at its edges.
+  ///
+  /// This is how to give a convolution (such as a blur) sensible edges: run
+  /// it on the padded image without reading outside of it, then
+  /// [crop](Self::crop) the middle back out. The reflection works like
+  /// [WrapMode::Mirror]. An empty image (or one without enough pixels) is
+  /// just copied.
+  #[inline]
+  #[must_use]
+  pub fn with_mirror_border(&self, pad: u32) -> Bitmap<P>
+  where
+    P: Copy,
+  {
+    self.with_wrapped_border(pad, WrapMode::Mirror)
+  }
+  /// Like [with_mirror_border](Self::with_mirror_border), but the border
+  /// repeats the nearest edge pixel.
+  #[inline]
+  #[must_use]
+  pub fn with_clamp_border(&self, pad: u32) -> Bitmap<P>
+  where
+    P: Copy,
+  {
+    self.with_wrapped_border(pad, WrapMode::Clamp)
+  }
+  #[inline]
+  #[must_use]
+  fn with_wrapped_border(&self, pad: u32, wrap: WrapMode) -> Bitmap<P>
+  where
+    P: Copy,
+  {
+    let pixel_count = (self.width as usize) * (self.height as usize);
+    if pixel_count == 0 || self.pixels.len() < pixel_count {
+      return self.clone();
+    }
+    let width = self.width.saturating_add(pad.saturating_mul(2));
+    let height = self.height.saturating_add(pad.saturating_mul(2));
+    let mut pixels = alloc::vec::Vec::with_capacity((width as usize) * (height as usize));
+    for y in 0..i64::from(height) {
+      let row_start = wrap.wrap(y - i64::from(pad), self.height) * (self.width as usize);
+      let row = &self.pixels[row_start..row_start + (self.width as usize)];
+      pixels.extend((0..i64::from(width)).map(|x| row[wrap.wrap(x - i64::from(pad), self.width)]));
+    }
+    Bitmap { width, height, pixels }
+  }
+  /// Pads the image out so that the width and height are powers of two.
+  ///
+  /// The image is kept in the top left of the output, and the new space is
+  /// filled with `fill`. Along with the new image you get the `(u_scale,
+  /// v_scale)` that maps normalized texture coordinates of the original image
+  /// onto the padded image, so that sampling only sees the original area.
+  ///
+  /// An image that's already a power of two in both directions is just
+  /// copied, and an empty image gives a 1x1 image of `fill`.
+  #[inline]
+  #[must_use]
+  pub fn pad_to_pow2(&self, fill: P) -> (Bitmap<P>, f32, f32)
+  where
+    P: Copy,
+  {
+    let new_w = self.width.checked_next_power_of_two().unwrap_or(self.width);
+    let new_h = self.height.checked_next_power_of_two().unwrap_or(self.height);
+    let mut pixels = alloc::vec::Vec::with_capacity((new_w as usize) * (new_h as usize));
+    for row in self.pixels.chunks_exact((self.width as usize).max(1)).take(self.height as usize) {
+      pixels.extend_from_slice(row);
+      pixels.resize(pixels.len() + (new_w - self.width) as usize, fill);
+    }
+    pixels.resize((new_w as usize) * (new_h as usize), fill);
+    let u_scale = self.width as f32 / new_w as f32;
+    let v_scale = self.height as f32 / new_h as f32;
+    (Bitmap { width: new_w, height: new_h, pixels }, u_scale, v_scale)
+  }
+  /// Puts images side by side in a row, left to right.
+  ///
+  /// The output is as wide as all the images put together, and as tall as the
+  /// tallest image. Each image is placed along the top, and any space below a
+  /// shorter image is filled with `fill`. No images gives an empty image.
+  ///
+  /// If an image has fewer than `width * height` pixels, the pixels it's
+  /// missing are also filled with `fill`.
+  ///
+  /// ## Failure
+  /// * If the total width (or the total pixel count) doesn't fit in the
+  ///   output you get [ImagineError::CheckedMath](crate::ImagineError::CheckedMath).
+  #[inline]
+  pub fn concat_h(bitmaps: &[Bitmap<P>], fill: P) -> Result<Bitmap<P>, crate::ImagineError>
+  where
+    P: Copy,
+  {
+    let width = bitmaps
+      .iter()
+      .try_fold(0_u32, |total, b| total.checked_add(b.width))
+      .ok_or(crate::ImagineError::CheckedMath)?;
+    let height = bitmaps.iter().map(|b| b.height).max().unwrap_or(0);
+    let count: usize =
+      width.checked_mul(height).ok_or(crate::ImagineError::CheckedMath)?.try_into()?;
+    let mut pixels = alloc::vec::Vec::new();
+    pixels.try_reserve(count)?;
+    for y in 0..height {
+      for b in bitmaps.iter() {
+        let w = b.width as usize;
+        if y < b.height {
+          push_row_or_fill(&mut pixels, &b.pixels, (y as usize) * w, w, fill);
+        } else {
+          pixels.resize(pixels.len() + w, fill);
+        }
+      }
+    }
+    Ok(Bitmap { width, height, pixels })
+  }
+  /// Stacks images in a column, top to bottom.
+  ///
+  /// The output is as tall as all the images put together, and as wide as the
+  /// widest image. Each image is placed along the left, and any space to the
+  /// right of a narrower image is filled with `fill`. No images gives an empty
+  /// image.
+  ///
+  /// If an image has fewer than `width * height` pixels, the pixels it's
+  /// missing are also filled with `fill`.
+  ///
+  /// ## Failure
+  /// * If the total height (or the total pixel count) doesn't fit in the
+  ///   output you get [ImagineError::CheckedMath](crate::ImagineError::CheckedMath).
+  #[inline]
+  pub fn concat_v(bitmaps: &[Bitmap<P>], fill: P) -> Result<Bitmap<P>, crate::ImagineError>
+  where
+    P: Copy,
+  {
+    let width = bitmaps.iter().map(|b| b.width).max().unwrap_or(0);
+    let height = bitmaps
+      .iter()
+      .try_fold(0_u32, |total, b| total.checked_add(b.height))
+      .ok_or(crate::ImagineError::CheckedMath)?;
+    let count: usize =
+      width.checked_mul(height).ok_or(crate::ImagineError::CheckedMath)?.try_into()?;
+    let mut pixels = alloc::vec::Vec::new();
+    pixels.try_reserve(count)?;
+    for b in bitmaps.iter() {
+      let w = b.width as usize;
+      for y in 0..(b.height as usize) {
+        push_row_or_fill(&mut pixels, &b.pixels, y * w, w, fill);
+        pixels.resize(pixels.len() + (width - b.width) as usize, fill);
+      }
+    }
+    Ok(Bitmap { width, height, pixels })
+  }
+  /// Cuts the image into tiles, such as the frames of a spritesheet.
+  ///
+  /// Tiles are `tile_w` by `tile_h` and come out left to right, top to bottom.
+  /// If the image isn't an exact multiple of the tile size then the last
+  /// column and row of tiles are partial: when `keep_partial` is `true` those
+  /// are clipped to fit the image, otherwise they're skipped. A tile size of 0
+  /// gives no tiles.
+  #[inline]
+  pub fn tiles(
+    &self, tile_w: u32, tile_h: u32, keep_partial: bool,
+  ) -> impl Iterator<Item = Bitmap<P>> + '_
+  where
+    P: Copy,
+  {
+    let count = |full: u32, tile: u32| {
+      if tile == 0 {
+        0
+      } else if keep_partial {
+        full.div_ceil(tile)
+      } else {
+        full / tile
+      }
+    };
+    let across = count(self.width, tile_w);
+    let down = count(self.height, tile_h);
+    (0..down).flat_map(move |ty| {
+      (0..across).map(move |tx| self.crop(tx * tile_w, ty * tile_h, tile_w, tile_h))
+    })
+  }
+  /// Makes an `out_w` by `out_h` image by repeating this image.
+  ///
+  /// This image is placed with its top left at the top left of the output,
+  /// and then repeated to the right and down, wrapping around at the edges.
+  /// The output can be smaller than this image, in which case it's just the
+  /// top left part.
+  ///
+  /// ## Failure
+  /// * If this image has a width or height of 0 there's nothing to repeat, so
+  ///   you get [ImagineError::WidthOrHeightZero](crate::ImagineError::WidthOrHeightZero).
+  /// * If this image has fewer than `width * height` pixels you get
+  ///   [ImagineError::DimensionsMismatch](crate::ImagineError::DimensionsMismatch).
+  #[inline]
+  pub fn tiled(&self, out_w: u32, out_h: u32) -> Result<Bitmap<P>, crate::ImagineError>
+  where
+    P: Copy,
+  {
+    if self.width == 0 || self.height == 0 {
+      return Err(crate::ImagineError::WidthOrHeightZero);
+    }
+    let count: usize =
+      out_w.checked_mul(out_h).ok_or(crate::ImagineError::CheckedMath)?.try_into()?;
+    let mut pixels = alloc::vec::Vec::new();
+    pixels.try_reserve(count)?;
+    let src_w = self.width as usize;
+    for y in 0..out_h {
+      let row_start = ((y % self.height) as usize) * src_w;
+      let row = self
+        .pixels
+        .get(row_start..row_start + src_w)
+        .ok_or(crate::ImagineError::DimensionsMismatch)?;
+      pixels.extend(row.iter().cycle().take(out_w as usize).copied());
+    }
+    Ok(Bitmap { width: out_w, height: out_h, pixels })
+  }
+  /// Gets the distinct colors of the image, in the order they first appear.
+  ///
+  /// If there's more than `max` distinct colors you get `None` instead. Each
+  /// new pixel is compared against the colors found so far, so this is
+  /// intended for a small `max` (such as 256).
+  #[inline]
+  #[must_use]
+  pub fn unique_colors(&self, max: usize) -> Option<alloc::vec::Vec<P>>
+  where
+    P: Copy + PartialEq,
+  {
+    let mut colors = alloc::vec::Vec::new();
+    for p in self.pixels.iter() {
+      if !colors.contains(p) {
+        if colors.len() >= max {
+          return None;
+        }
+        colors.push(*p);
+      }
+    }
+    Some(colors)
+  }
+  /// Converts the image into a [Palmap] without losing any color info.
+  ///
+  /// The palette is the output of [unique_colors](Self::unique_colors), so if
+  /// there's more than `max` distinct colors (or more than 256, whichever is
+  /// lower) you get `None`.
+  #[inline]
+  #[must_use]
+  pub fn to_palmap_if_indexable(&self, max: usize) -> Option<Palmap<u8, P>>
+  where
+    P: Copy + PartialEq,
+  {
+    let palette = self.unique_colors(max.min(256))?;
+    let indexes = self
+      .pixels
+      .iter()
+      .map(|p| palette.iter().position(|c| c == p).unwrap_or_default() as u8)
+      .collect();
+    Some(Palmap { width: self.width, height: self.height, indexes, palette })
+  }
+  /// Removes the sRGB curve from the color channels, giving linear values.
+  ///
+  /// Each pixel is converted into [r32g32b32a32_Sfloat], the sRGB decoding
+  /// curve is applied to `r`, `g`, and `b`, and then the result is converted
+  /// into `Q`. Alpha is not changed, and the channels are used as they're
+  /// stored, so this is for straight alpha (or opaque) images.
+  ///
+  /// Be careful about which `From` impls of `pixel_formats` already do a
+  /// transfer function for you:
+  /// * `r8g8b8a8_Srgb` to/from `r32g32b32a32_Sfloat` **applies** the sRGB
+  ///   curve. Don't use this method on an `r8g8b8a8_Srgb` bitmap, the curve
+  ///   would be applied twice.
+  /// * `r8g8b8a8_Unorm` to `r32g32b32a32_Sfloat` (and the other `Unorm` and
+  ///   `Sfloat` conversions) only **rescale** the numbers.
+  ///
+  /// So the main use of this is taking a `Unorm` bitmap that actually holds
+  /// sRGB encoded bytes and getting linear color from it.
+  #[inline]
+  #[must_use]
+  pub fn linearize<Q>(&self) -> Bitmap<Q>
+  where
+    P: Copy,
+    r32g32b32a32_Sfloat: From<P>,
+    Q: From<r32g32b32a32_Sfloat>,
+  {
+    self.map_rgb_curve(srgb_to_linear)
+  }
+  /// Applies the sRGB curve to the color channels, giving encoded values.
+  ///
+  /// This is the inverse of [linearize](Self::linearize), and the same notes
+  /// about which `From` impls already do a transfer function apply. The main
+  /// use is getting sRGB encoded numbers into a type (like
+  /// [r32g32b32a32_Sfloat]) whose conversions only rescale.
+  #[inline]
+  #[must_use]
+  pub fn encode_srgb<Q>(&self) -> Bitmap<Q>
+  where
+    P: Copy,
+    r32g32b32a32_Sfloat: From<P>,
+    Q: From<r32g32b32a32_Sfloat>,
+  {
+    self.map_rgb_curve(linear_to_srgb)
+  }
+  /// Applies `curve` to the color channels of every pixel, converting into
+  /// `Q` along the way.
+  #[inline]
+  fn map_rgb_curve<Q>(&self, curve: fn(f32) -> f32) -> Bitmap<Q>
+  where
+    P: Copy,
+    r32g32b32a32_Sfloat: From<P>,
+    Q: From<r32g32b32a32_Sfloat>,
+  {
+    let pixels = self
+      .pixels
+      .iter()
+      .map(|p| {
+        let c = r32g32b32a32_Sfloat::from(*p);
+        Q::from(r32g32b32a32_Sfloat { r: curve(c.r), g: curve(c.g), b: curve(c.b), a: c.a })
+      })
+      .collect();
+    Bitmap { width: self.width, height: self.height, pixels }
+  }
+  /// Makes an edge map of the image using the Sobel operator.
+  ///
+  /// The operator runs on the luma of each pixel (Rec. 709 weights of the
+  /// linear color values), and each output pixel is the gradient magnitude,
+  /// clamped to `0.0 ..= 1.0` and then stored as a `u8`.
+  ///
+  /// * Pixels past the edge of the image are clamped to the nearest edge
+  ///   pixel, so a flat colored border doesn't show up as an edge.
+  #[inline]
+  #[must_use]
+  pub fn sobel(&self) -> Bitmap<r8_Unorm>
+  where
+    P: Copy,
+    r32g32b32a32_Sfloat: From<P>,
+  {
+    let luma: alloc::vec::Vec<f32> = self
+      .pixels
+      .iter()
+      .map(|p| {
+        let c = r32g32b32a32_Sfloat::from(*p);
+        0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+      })
+      .collect();
+    let (w, h) = (self.width as usize, self.height as usize);
+    let at = |x: usize, dx: isize, y: usize, dy: isize| -> f32 {
+      let x = x.saturating_add_signed(dx).min(w - 1);
+      let y = y.saturating_add_signed(dy).min(h - 1);
+      luma[y * w + x]
+    };
+    let mut pixels = alloc::vec::Vec::with_capacity(luma.len());
+    for y in 0..h {
+      for x in 0..w {
+        let gx = (at(x, 1, y, -1) + 2.0 * at(x, 1, y, 0) + at(x, 1, y, 1))
+          - (at(x, -1, y, -1) + 2.0 * at(x, -1, y, 0) + at(x, -1, y, 1));
+        let gy = (at(x, -1, y, 1) + 2.0 * at(x, 0, y, 1) + at(x, 1, y, 1))
+          - (at(x, -1, y, -1) + 2.0 * at(x, 0, y, -1) + at(x, 1, y, -1));
+        let magnitude = (gx * gx + gy * gy).sqrt().clamp(0.0, 1.0);
+        pixels.push(r8_Unorm { r: (magnitude * 255.0).round() as u8 });
+      }
+    }
+    Bitmap { width: self.width, height: self.height, pixels }
+  }
+  /// Draws a line from `(x0, y0)` to `(x1, y1)`, including both ends.
+  ///
+  /// This uses Bresenham's line algorithm. The positions can be outside of the
+  /// image, and any points of the line that fall outside are skipped.
+  #[inline]
+  pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: P)
+  where
+    P: Copy,
+  {
+    let (mut x, mut y) = (i64::from(x0), i64::from(y0));
+    let (x1, y1) = (i64::from(x1), i64::from(y1));
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let step_x = if x < x1 { 1 } else { -1 };
+    let step_y = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+      if let (Ok(px), Ok(py)) = (u32::try_from(x), u32::try_from(y)) {
+        if let Some(p) = self.get_mut(px, py) {
+          *p = color;
+        }
+      }
+      if x == x1 && y == y1 {
+        break;
+      }
+      let e2 = 2 * err;
+      if e2 >= dy {
+        err += dy;
+        x += step_x;
+      }
+      if e2 <= dx {
+        err += dx;
+        y += step_y;
+      }
+    }
+  }
+  /// Draws the outline of a rectangle, one pixel thick.
+  ///
+  /// The rectangle's top left is at `(x, y)`, and the outline covers exactly
+  /// `width` by `height` pixels. Parts outside of the image are skipped, and a
+  /// `width` or `height` of 0 draws nothing.
+  #[inline]
+  pub fn draw_rect_outline(&mut self, x: i32, y: i32, width: u32, height: u32, color: P)
+  where
+    P: Copy,
+  {
+    if width == 0 || height == 0 {
+      return;
+    }
+    let right = i32::try_from(i64::from(x) + i64::from(width) - 1).unwrap_or(i32::MAX);
+    let bottom = i32::try_from(i64::from(y) + i64::from(height) - 1).unwrap_or(i32::MAX);
+    self.draw_line(x, y, right, y, color);
+    self.draw_line(x, bottom, right, bottom, color);
+    self.draw_line(x, y, x, bottom, color);
+    self.draw_line(right, y, right, bottom, color);
+  }
+  /// Draws orientation markers into three corners of the image.
+  ///
+  /// This is a debugging aid for checking that an image is the right way up.
+  ///
+  /// * Top left: a 3x3 filled square.
+  /// * Top right: a 3 pixel line along the top edge.
+  /// * Bottom left: a 2x2 filled square.
+  /// * Bottom right: nothing.
+  ///
+  /// Every corner gets a different shape, and the only mark that isn't
+  /// symmetric (the line) tells apart the two orientations that put the big
+  /// square in the same corner. So any flip, rotation, or transpose of the
+  /// image can be identified at a glance. Marker pixels that would fall
+  /// outside of a very small image are skipped, and in an image smaller than
+  /// 6x5 the marks overlap.
+  #[inline]
+  pub fn draw_corner_markers(&mut self, color: P)
+  where
+    P: Copy,
+  {
+    const MARK: u32 = 3;
+    let right = self.width.saturating_sub(1);
+    let bottom = self.height.saturating_sub(1);
+    for y in 0..MARK {
+      for x in 0..MARK {
+        if let Some(p) = self.get_mut(x, y) {
+          *p = color;
+        }
+      }
+    }
+    for i in 0..MARK {
+      if let Some(p) = self.get_mut(right.wrapping_sub(i), 0) {
+        *p = color;
+      }
+    }
+    for y in 0..(MARK - 1) {
+      for x in 0..(MARK - 1) {
+        if let Some(p) = self.get_mut(x, bottom.wrapping_sub(y)) {
+          *p = color;
+        }
+      }
+    }
+  }
+  /// Samples the nearest pixel to a position given in normalized units.
+  ///
+  /// `(0.0, 0.0)` is the top left corner of the image, and `(1.0, 1.0)` is the
+  /// bottom right corner. Positions outside of that range are handled with
+  /// the [WrapMode] given. An empty image gives `None`.
+  #[inline]
+  #[must_use]
+  pub fn sample_nearest(&self, u: f32, v: f32, wrap: WrapMode) -> Option<P>
+  where
+    P: Copy,
+  {
+    if self.width == 0 || self.height == 0 {
+      return None;
+    }
+    let x = wrap.wrap((u * self.width as f32).floor() as i64, self.width);
+    let y = wrap.wrap((v * self.height as f32).floor() as i64, self.height);
+    self.pixels.get(y * (self.width as usize) + x).copied()
+  }
+  /// Packs every pixel into a 16-bit RGB565 value, such as for a small
+  /// display's framebuffer.
+  ///
+  /// Each pixel is converted to `r8g8b8a8_Srgb`, and then red and blue are
+  /// rounded to the nearest 5-bit value (`(c * 31 + 127) / 255`) and green to
+  /// the nearest 6-bit value (`(c * 63 + 127) / 255`). Red is in the top bits.
+  /// Alpha is dropped, so with pre-multiplied pixels any transparency ends up
+  /// shown over black.
+  ///
+  /// The values are native `u16`s. Use `to_le_bytes` or `to_be_bytes` on each
+  /// one to get the byte order that your display wants.
+  #[inline]
+  #[must_use]
+  pub fn to_rgb565(&self) -> alloc::vec::Vec<u16>
+  where
+    P: Copy,
+    r8g8b8a8_Srgb: From<P>,
+  {
+    let pack = |c: u8, max: u16| (u16::from(c) * max + 127) / 255;
+    self
+      .pixels
+      .iter()
+      .map(|p| {
+        let r8g8b8a8_Srgb { r, g, b, a: _ } = r8g8b8a8_Srgb::from(*p);
+        (pack(r, 31) << 11) | (pack(g, 63) << 5) | pack(b, 31)
+      })
+      .collect()
+  }
+  /// Unpacks RGB565 values, as made by [to_rgb565](Self::to_rgb565), into a
+  /// new image.
+  ///
+  /// Each channel is scaled back up to the nearest 8-bit value, so packing the
+  /// output again gives the same values. All pixels are opaque.
+  ///
+  /// ## Failure
+  /// * If `data` isn't exactly `width * height` values you get
+  ///   [ImagineError::DimensionsMismatch](crate::ImagineError::DimensionsMismatch).
+  #[inline]
+  pub fn from_rgb565(
+    data: &[u16], width: u32, height: u32,
+  ) -> Result<Bitmap<P>, crate::ImagineError>
+  where
+    P: From<r8g8b8a8_Srgb>,
+  {
+    let pixel_count = usize::try_from(width)?
+      .checked_mul(usize::try_from(height)?)
+      .ok_or(crate::ImagineError::CheckedMath)?;
+    if data.len() != pixel_count {
+      return Err(crate::ImagineError::DimensionsMismatch);
+    }
+    let unpack = |v: u16, max: u16| ((v * 255 + max / 2) / max) as u8;
+    let mut pixels = alloc::vec::Vec::new();
+    pixels.try_reserve(pixel_count)?;
+    pixels.extend(data.iter().map(|&v| {
+      P::from(r8g8b8a8_Srgb {
+        r: unpack(v >> 11, 31),
+        g: unpack((v >> 5) & 0b111111, 63),
+        b: unpack(v & 0b11111, 31),
+        a: u8::MAX,
+      })
+    }));
+    Ok(Bitmap { width, height, pixels })
+  }
+  /// Writes the bitmap out as a raw dump.
+  ///
+  /// This isn't a standard image format, it's just a fast way to cache
+  /// decoded images. The layout is:
+  /// * The magic bytes `imagine1` (the final byte is the format version).
+  /// * The width, height, and `size_of::<P>()`, each as a little-endian `u32`.
+  /// * The bytes of the pixels, exactly as they are in memory.
+  ///
+  /// Since the pixels are written in native form, a dump should only be read
+  /// back on a machine of the same endianness. Use
+  /// [from_raw_dump](Self::from_raw_dump) to read it back.
+  #[inline]
+  #[must_use]
+  pub fn to_raw_dump(&self) -> alloc::vec::Vec<u8>
+  where
+    P: bytemuck::Pod,
+  {
+    let pixel_bytes: &[u8] = bytemuck::cast_slice(&self.pixels);
+    let mut out = alloc::vec::Vec::with_capacity(RAW_DUMP_HEADER_LEN + pixel_bytes.len());
+    out.extend_from_slice(RAW_DUMP_MAGIC);
+    out.extend_from_slice(&self.width.to_le_bytes());
+    out.extend_from_slice(&self.height.to_le_bytes());
+    out.extend_from_slice(&(core::mem::size_of::<P>() as u32).to_le_bytes());
+    out.extend_from_slice(pixel_bytes);
+    out
+  }
+  /// Reads a bitmap back from a raw dump made with
+  /// [to_raw_dump](Self::to_raw_dump).
+  ///
+  /// ## Failure
+  /// * If the magic bytes are wrong, the stored pixel size doesn't match
+  ///   `size_of::<P>()`, or the data isn't exactly the length that the header
+  ///   says, you get [ImagineError::Parse](crate::ImagineError::Parse).
+  /// * If the size math overflows you get
+  ///   [ImagineError::CheckedMath](crate::ImagineError::CheckedMath).
+  #[inline]
+  pub fn from_raw_dump(bytes: &[u8]) -> Result<Self, crate::ImagineError>
+  where
+    P: bytemuck::Pod,
+  {
+    use crate::ImagineError;
+    if bytes.len() < RAW_DUMP_HEADER_LEN {
+      return Err(ImagineError::Parse);
+    }
+    let (header, pixel_bytes) = bytes.split_at(RAW_DUMP_HEADER_LEN);
+    let (magic, fields) = header.split_at(RAW_DUMP_MAGIC.len());
+    if magic != RAW_DUMP_MAGIC {
+      return Err(ImagineError::Parse);
+    }
+    let field = |i: usize| u32::from_le_bytes(fields[i * 4..(i + 1) * 4].try_into().unwrap());
+    let (width, height, pixel_size) = (field(0), field(1), field(2));
+    if usize::try_from(pixel_size)? != core::mem::size_of::<P>() {
+      return Err(ImagineError::Parse);
+    }
+    let pixel_count = usize::try_from(width)?
+      .checked_mul(usize::try_from(height)?)
+      .ok_or(ImagineError::CheckedMath)?;
+    let byte_count =
+      pixel_count.checked_mul(core::mem::size_of::<P>()).ok_or(ImagineError::CheckedMath)?;
+    if pixel_bytes.len() != byte_count {
+      return Err(ImagineError::Parse);
+    }
+    let mut pixels = alloc::vec::Vec::new();
+    pixels.try_reserve(pixel_count)?;
+    if core::mem::size_of::<P>() == 0 {
+      pixels.resize(pixel_count, P::zeroed());
+    } else {
+      pixels.extend(
+        pixel_bytes.chunks_exact(core::mem::size_of::<P>()).map(bytemuck::pod_read_unaligned::<P>),
+      );
+    }
+    Ok(Bitmap { width, height, pixels })
+  }
+}
+
+/// The magic bytes at the start of a raw bitmap dump.
+const RAW_DUMP_MAGIC: &[u8; 8] = b"imagine1";
+/// The magic bytes, then the width, height, and pixel size.
+const RAW_DUMP_HEADER_LEN: usize = RAW_DUMP_MAGIC.len() + 3 * 4;
+
+/// How positions outside of an image are handled when sampling.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub enum WrapMode {
+  /// Positions are clamped to the nearest edge pixel.
+  #[default]
+  Clamp,
+  /// The image repeats forever in every direction.
+  Repeat,
+  /// The image is reflected at each edge, without repeating the edge pixel.
+  ///
+  /// So the pixel at `-1` is a copy of the pixel at `1`.
+  Mirror,
+}
+impl WrapMode {
+  /// Wraps a pixel coordinate into `0 .. len`.
+  ///
+  /// `len` must not be 0.
+  #[inline]
+  #[must_use]
+  fn wrap(self, i: i64, len: u32) -> usize {
+    let len = i64::from(len);
+    (match self {
+      WrapMode::Clamp => i.clamp(0, len - 1),
+      WrapMode::Repeat => i.rem_euclid(len),
+      WrapMode::Mirror if len == 1 => 0,
+      WrapMode::Mirror => {
+        let period = 2 * (len - 1);
+        let m = i.rem_euclid(period);
+        if m < len {
+          m
+        } else {
+          period - m
+        }
+      }
+    }) as usize
+  }
+}
+
+/// How positions outside of an image are handled by a convolution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub enum BorderMode {
+  /// Positions are clamped to the nearest edge pixel.
+  #[default]
+  Clamp,
+  /// The image is reflected at each edge, like with [WrapMode::Mirror].
+  Mirror,
+  /// Positions outside the image count as transparent black.
+  Zero,
+}
+
+/// How the alpha channel of some image data should be understood.
+///
+/// The decoders output pre-multiplied alpha, so that's the default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub enum AlphaHandling {
+  /// The color channels have already been multiplied by the alpha channel.
+  #[default]
+  Premultiplied,
+  /// The color channels are separate from the alpha channel.
+  Straight,
+}
+impl AlphaHandling {
+  /// Converts a color of this kind into pre-multiplied form.
+  #[inline]
+  #[must_use]
+  fn to_premultiplied(self, c: r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat {
+    match self {
+      AlphaHandling::Premultiplied => c,
+      AlphaHandling::Straight => premultiply(c),
+    }
+  }
+
+  /// Converts a pre-multiplied color back into this kind of color.
+  #[inline]
+  #[must_use]
+  fn undo_premultiplied(self, c: r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat {
+    match self {
+      AlphaHandling::Premultiplied => c,
+      AlphaHandling::Straight => unpremultiply(c),
+    }
+  }
+}
+
+/// One of the channels of an RGBA color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub enum Channel {
+  /// Red
+  R,
+  /// Green
+  G,
+  /// Blue
+  B,
+  /// Alpha
+  A,
+}
+
+/// An order for the four channels of a 4 byte color.
+///
+/// See [Bitmap::swizzle].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub enum ChannelOrder {
+  /// Red, green, blue, alpha. This is the normal order, so it's a no-op.
+  #[default]
+  RGBA,
+  /// Blue, green, red, alpha.
+  BGRA,
+  /// Alpha, red, green, blue.
+  ARGB,
+  /// Alpha, blue, green, red.
+  ABGR,
+}
+impl ChannelOrder {
+  /// Moves RGBA ordered bytes into this order.
+  #[inline]
+  #[must_use]
+  const fn apply(self, [r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    match self {
+      ChannelOrder::RGBA => [r, g, b, a],
+      ChannelOrder::BGRA => [b, g, r, a],
+      ChannelOrder::ARGB => [a, r, g, b],
+      ChannelOrder::ABGR => [a, b, g, r],
+    }
+  }
+
+  /// Moves bytes in this order back into RGBA order.
+  #[inline]
+  #[must_use]
+  const fn undo(self, bytes: [u8; 4]) -> [u8; 4] {
+    match self {
+      ChannelOrder::ARGB => {
+        let [a, r, g, b] = bytes;
+        [r, g, b, a]
+      }
+      // the other orders are each their own inverse
+      _ => self.apply(bytes),
+    }
+  }
+}
+
+/// Pushes the `len` pixels of `src` starting at `start` onto `dst`.
+///
+/// Any of those pixels that `src` doesn't have are pushed as `fill` instead.
+#[inline]
+fn push_row_or_fill<P: Copy>(
+  dst: &mut alloc::vec::Vec<P>, src: &[P], start: usize, len: usize, fill: P,
+) {
+  let row = src.get(start..).unwrap_or(&[]);
+  let row = &row[..len.min(row.len())];
+  dst.extend_from_slice(row);
+  dst.resize(dst.len() + (len - row.len()), fill);
+}
+
+/// Converts a pre-multiplied color into straight alpha form.
+///
+/// A fully transparent color has no meaningful RGB, so it stays as all zero.
+#[inline]
+#[must_use]
+pub(crate) fn unpremultiply(c: r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat {
+  if c.a > 0.0 {
+    r32g32b32a32_Sfloat { r: c.r / c.a, g: c.g / c.a, b: c.b / c.a, a: c.a }
+  } else {
+    r32g32b32a32_Sfloat::TRANSPARENT_BLACK
+  }
+}
+
+/// Converts a straight alpha color into pre-multiplied form.
+#[inline]
+#[must_use]
+pub(crate) fn premultiply(c: r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat {
+  r32g32b32a32_Sfloat { r: c.r * c.a, g: c.g * c.a, b: c.b * c.a, a: c.a }
+}
+
+/// Composites a pre-multiplied `src` color over a pre-multiplied `dst` color.
+#[inline]
+#[must_use]
+fn source_over(src: r32g32b32a32_Sfloat, dst: r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat {
+  let k = 1.0 - src.a;
+  r32g32b32a32_Sfloat {
+    r: src.r + dst.r * k,
+    g: src.g + dst.g * k,
+    b: src.b + dst.b * k,
+    a: src.a + dst.a * k,
+  }
+}
+
+/// Bilinearly blends the four pixels around `(x, y)`, given in pixel units
+/// with the pixel centers on whole numbers.
+///
+/// `tap` gets the color of the pixel at a position, and if it gives `None` for
+/// any of the four then so does this.
+#[inline]
+fn bilinear<F>(x: f32, y: f32, mut tap: F) -> Option<r32g32b32a32_Sfloat>
+where
+  F: FnMut(i64, i64) -> Option<r32g32b32a32_Sfloat>,
+{
+  let x0 = x.floor();
+  let y0 = y.floor();
+  let fx = x - x0;
+  let fy = y - y0;
+  let (x0, y0) = (x0 as i64, y0 as i64);
+  let lerp = |a: r32g32b32a32_Sfloat, b: r32g32b32a32_Sfloat, t: f32| r32g32b32a32_Sfloat {
+    r: a.r + (b.r - a.r) * t,
+    g: a.g + (b.g - a.g) * t,
+    b: a.b + (b.b - a.b) * t,
+    a: a.a + (b.a - a.a) * t,
+  };
+  let top = lerp(tap(x0, y0)?, tap(x0 + 1, y0)?, fx);
+  let bottom = lerp(tap(x0, y0 + 1)?, tap(x0 + 1, y0 + 1)?, fx);
+  Some(lerp(top, bottom, fy))
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+impl<P> Bitmap<P>
+where
+  P: Copy + From<r8g8b8a8_Srgb>,
+  r8g8b8a8_Srgb: From<P>,
+{
+  /// Reorders the channels of every pixel, such as for an API that wants BGRA.
+  ///
+  /// Each pixel is converted to `r8g8b8a8_Srgb`, and the four bytes are
+  /// rearranged so that they're in `order` when read as `[r, g, b, a]`. This
+  /// only makes sense when `P` converts to and from `r8g8b8a8_Srgb` without
+  /// changing the bytes, such as with `r8g8b8a8_Srgb` itself.
+  ///
+  /// To go back to RGBA, use [unswizzle](Self::unswizzle) with the same order.
+  /// BGRA and ABGR are also their own inverse, so swizzling twice with either
+  /// of them gets the original image back.
+  #[inline]
+  pub fn swizzle(&mut self, order: ChannelOrder) {
+    self.swizzle_with(|bytes| order.apply(bytes))
+  }
+
+  /// Undoes a [swizzle](Self::swizzle) with the same `order`.
+  #[inline]
+  pub fn unswizzle(&mut self, order: ChannelOrder) {
+    self.swizzle_with(|bytes| order.undo(bytes))
+  }
+
+  #[inline]
+  fn swizzle_with(&mut self, f: impl Fn([u8; 4]) -> [u8; 4]) {
+    self.pixels.iter_mut().for_each(|p| {
+      let r8g8b8a8_Srgb { r, g, b, a } = r8g8b8a8_Srgb::from(*p);
+      let [r, g, b, a] = f([r, g, b, a]);
+      *p = P::from(r8g8b8a8_Srgb { r, g, b, a });
+    });
+  }
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+impl<P> Bitmap<P>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  /// Runs `op` on the straight alpha form of every pixel.
+  ///
+  /// The image data is assumed to be pre-multiplied (which is what the
+  /// decoders output), so each pixel is un-multiplied before `op` sees it and
+  /// then re-multiplied afterward.
+  #[inline]
+  fn map_straight_alpha<F>(&mut self, mut op: F)
+  where
+    F: FnMut(r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat,
+  {
+    for p in self.pixels.iter_mut() {
+      let straight = unpremultiply(r32g32b32a32_Sfloat::from(*p));
+      *p = P::from(premultiply(op(straight)));
+    }
+  }
+
+  /// Adjusts the brightness and contrast of the image.
+  ///
+  /// Each color channel is changed as `(c - 0.5) * contrast + 0.5 +
+  /// brightness`, then clamped to `0.0 ..= 1.0`. The math is done on the
+  /// straight alpha color values, and alpha itself is not changed.
+  ///
+  /// * A `brightness` of 0.0 and a `contrast` of 1.0 leaves the image as is.
+  #[inline]
+  pub fn adjust(&mut self, brightness: f32, contrast: f32) {
+    let f = |c: f32| ((c - 0.5) * contrast + 0.5 + brightness).clamp(0.0, 1.0);
+    self.map_straight_alpha(|c| r32g32b32a32_Sfloat { r: f(c.r), g: f(c.g), b: f(c.b), a: c.a });
+  }
+
+  /// Inverts the color channels, giving a photographic negative.
+  ///
+  /// Each color channel becomes `1.0 - c`. Like with [adjust](Self::adjust),
+  /// this works on the straight alpha color values, and alpha itself is not
+  /// changed.
+  #[inline]
+  pub fn invert(&mut self) {
+    self.map_straight_alpha(|c| r32g32b32a32_Sfloat {
+      r: 1.0 - c.r,
+      g: 1.0 - c.g,
+      b: 1.0 - c.b,
+      a: c.a,
+    });
+  }
+
+  /// Inverts all four channels, including alpha.
+  ///
+  /// Each channel of the straight alpha color becomes `1.0 - c`. Note that
+  /// fully transparent pixels have no color, so they become opaque white.
+  #[inline]
+  pub fn invert_including_alpha(&mut self) {
+    self.map_straight_alpha(|c| r32g32b32a32_Sfloat {
+      r: 1.0 - c.r,
+      g: 1.0 - c.g,
+      b: 1.0 - c.b,
+      a: 1.0 - c.a,
+    });
+  }
+
+  /// Reduces each color channel to `levels` evenly spaced steps.
+  ///
+  /// Each channel is clamped to `0.0 ..= 1.0` and then rounded to the nearest
+  /// multiple of `1.0 / (levels - 1)`, so a `levels` of 2 gives just 0.0 and
+  /// 1.0. A `levels` less than 2 is treated as 2. Like with
+  /// [adjust](Self::adjust), this works on the straight alpha color values,
+  /// and alpha itself is not changed.
+  #[inline]
+  pub fn posterize(&mut self, levels: u8) {
+    let steps = f32::from(levels.max(2) - 1);
+    let f = |c: f32| (c.clamp(0.0, 1.0) * steps).round() / steps;
+    self.map_straight_alpha(|c| r32g32b32a32_Sfloat { r: f(c.r), g: f(c.g), b: f(c.b), a: c.a });
+  }
+
+  /// Applies a 1D lookup table to each color channel.
+  ///
+  /// Each channel value is clamped to `0.0 ..= 1.0` and scaled to the table's
+  /// `0 ..= 255` range, then the output is linearly interpolated between the
+  /// two nearest entries. The red channel looks up the `r` field of the
+  /// entries, and so on for green and blue. Like with
+  /// [adjust](Self::adjust), this works on the straight alpha color values,
+  /// and alpha itself is not changed.
+  #[inline]
+  pub fn apply_lut(&mut self, lut: &[r32g32b32_Sfloat; 256]) {
+    let look_up = |c: f32, channel: fn(&r32g32b32_Sfloat) -> f32| {
+      let pos = c.clamp(0.0, 1.0) * 255.0;
+      let i = (pos.floor() as usize).min(254);
+      let t = pos - i as f32;
+      channel(&lut[i]) * (1.0 - t) + channel(&lut[i + 1]) * t
+    };
+    self.map_straight_alpha(|c| r32g32b32a32_Sfloat {
+      r: look_up(c.r, |e| e.r),
+      g: look_up(c.g, |e| e.g),
+      b: look_up(c.b, |e| e.b),
+      a: c.a,
+    });
+  }
+
+  /// Replaces one channel of every pixel with the values from `src`.
+  ///
+  /// Each `src` value is scaled to `0.0 ..= 1.0` and used directly, without
+  /// any transfer function. This works on the straight alpha color values, so
+  /// setting [Channel::A] turns an opaque image into a properly
+  /// pre-multiplied image with that alpha (eg: an RGB image plus an 8-bit
+  /// mask gives an RGBA image). Note that pixels which are fully transparent
+  /// before this have no color, so any color channel set on them is lost.
+  ///
+  /// ## Failure
+  /// * If `src` has a different width or height you get
+  ///   [ImagineError::DimensionsMismatch](crate::ImagineError::DimensionsMismatch).
+  #[inline]
+  pub fn set_channel_from(
+    &mut self, channel: Channel, src: &Bitmap<r8_Unorm>,
+  ) -> Result<(), crate::ImagineError> {
+    if (self.width, self.height) != (src.width, src.height) || self.pixels.len() != src.pixels.len()
+    {
+      return Err(crate::ImagineError::DimensionsMismatch);
+    }
+    let mut values = src.pixels.iter();
+    self.map_straight_alpha(|mut c| {
+      let v = f32::from(values.next().map_or(0, |p| p.r)) / 255.0;
+      match channel {
+        Channel::R => c.r = v,
+        Channel::G => c.g = v,
+        Channel::B => c.b = v,
+        Channel::A => c.a = v,
+      }
+      c
+    });
+    Ok(())
+  }
+
+  /// Multiplies the alpha of the image by the luma of a mask image.
+  ///
+  /// The mask's luma uses the Rec. 709 weights of its linear color, clamped
+  /// to `0.0 ..= 1.0`, so white areas of the mask keep the image as is and
+  /// black (or transparent) areas cut the image out. The image is
+  /// pre-multiplied, so all four channels are scaled together.
+  ///
+  /// ## Failure
+  /// * If `mask` has a different width or height you get
+  ///   [ImagineError::DimensionsMismatch](crate::ImagineError::DimensionsMismatch).
+  #[inline]
+  pub fn apply_mask(&mut self, mask: &Bitmap<P>) -> Result<(), crate::ImagineError> {
+    if (self.width, self.height) != (mask.width, mask.height)
+      || self.pixels.len() != mask.pixels.len()
+    {
+      return Err(crate::ImagineError::DimensionsMismatch);
+    }
+    for (p, m) in self.pixels.iter_mut().zip(mask.pixels.iter()) {
+      let m = r32g32b32a32_Sfloat::from(*m);
+      let luma = (0.2126 * m.r + 0.7152 * m.g + 0.0722 * m.b).clamp(0.0, 1.0);
+      let c = r32g32b32a32_Sfloat::from(*p);
+      *p =
+        P::from(r32g32b32a32_Sfloat { r: c.r * luma, g: c.g * luma, b: c.b * luma, a: c.a * luma });
+    }
+    Ok(())
+  }
+
+  /// Composites the image over a checkerboard, like an image viewer shows
+  /// behind transparent images.
+  ///
+  /// The checkerboard squares are `size` pixels across (a `size` of 0 is
+  /// treated as 1), and the top left square uses `light`. As long as `light`
+  /// and `dark` are opaque, the output is opaque too.
+  #[inline]
+  #[must_use]
+  pub fn composite_over_checker(&self, size: u32, light: P, dark: P) -> Bitmap<P> {
+    let size = size.max(1);
+    let light = r32g32b32a32_Sfloat::from(light);
+    let dark = r32g32b32a32_Sfloat::from(dark);
+    let width = self.width.max(1);
+    let pixels = self
+      .pixels
+      .iter()
+      .enumerate()
+      .map(|(i, p)| {
+        let x = (i as u32) % width;
+        let y = (i as u32) / width;
+        let back = if ((x / size) + (y / size)) & 1 == 0 { light } else { dark };
+        P::from(source_over(r32g32b32a32_Sfloat::from(*p), back))
+      })
+      .collect();
+    Bitmap { width: self.width, height: self.height, pixels }
+  }
+
+  /// Gets the smallest area of the image that holds every visible pixel.
+  ///
+  /// The output is `(x, y, width, height)` of the box around all the pixels
+  /// that have an alpha above 0.0, or `None` if there's no such pixels.
+  #[inline]
+  #[must_use]
+  pub fn opaque_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+    let width = self.width.max(1);
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (i, p) in self.pixels.iter().enumerate() {
+      if r32g32b32a32_Sfloat::from(*p).a <= 0.0 {
+        continue;
+      }
+      let x = (i as u32) % width;
+      let y = (i as u32) / width;
+      bounds = Some(match bounds {
+        None => (x, y, x, y),
+        Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+      });
+    }
+    bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+  }
+
+  /// Finds the first pixel that's within `tolerance` of `target`.
+  ///
+  /// Pixels are checked in row-major order (left to right, then top to
+  /// bottom), and the output is the `(x, y)` of the first match. The distance
+  /// is the Euclidean distance between the linear, pre-multiplied RGBA values,
+  /// so a `tolerance` of 0.0 only matches exactly equal pixels.
+  #[inline]
+  #[must_use]
+  pub fn find_pixel(&self, target: P, tolerance: f32) -> Option<(u32, u32)> {
+    self.pixel_matches(target, tolerance).next()
+  }
+
+  /// Finds every pixel that's within `tolerance` of `target`.
+  ///
+  /// This is like [find_pixel](Self::find_pixel), but gives the `(x, y)` of
+  /// all the matches, in row-major order.
+  #[inline]
+  #[must_use]
+  pub fn find_all_pixels(&self, target: P, tolerance: f32) -> alloc::vec::Vec<(u32, u32)> {
+    self.pixel_matches(target, tolerance).collect()
+  }
+
+  /// Iterates the positions of the pixels within `tolerance` of `target`.
+  #[inline]
+  fn pixel_matches(&self, target: P, tolerance: f32) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let width = self.width.max(1);
+    let t = r32g32b32a32_Sfloat::from(target);
+    self.pixels.iter().enumerate().filter_map(move |(i, p)| {
+      let p = r32g32b32a32_Sfloat::from(*p);
+      let (dr, dg, db, da) = (p.r - t.r, p.g - t.g, p.b - t.b, p.a - t.a);
+      if (dr * dr + dg * dg + db * db + da * da).sqrt() <= tolerance {
+        Some(((i as u32) % width, (i as u32) / width))
+      } else {
+        None
+      }
+    })
+  }
+
+  /// Checks that every pixel is a valid pre-multiplied color.
+  ///
+  /// With pre-multiplied alpha no color channel can be more than the alpha, so
+  /// this checks that `r`, `g`, and `b` are all at most `a + epsilon` for
+  /// every pixel. The decoders always give valid pixels, so this is mostly for
+  /// catching bugs in code that changes the pixels.
+  #[inline]
+  #[must_use]
+  pub fn is_valid_premultiplied(&self, epsilon: f32) -> bool {
+    self.pixels.iter().all(|p| {
+      let c = r32g32b32a32_Sfloat::from(*p);
+      let max = c.a + epsilon;
+      c.r <= max && c.g <= max && c.b <= max
+    })
+  }
+
+  /// Gets the average color of the whole image.
+  ///
+  /// The average is taken of the linear, pre-multiplied pixel values, so the
+  /// output is in that form too. An empty image gives transparent black.
+  #[inline]
+  #[must_use]
+  pub fn average_color(&self) -> r32g32b32a32_Sfloat {
+    if self.pixels.is_empty() {
+      return r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+    }
+    let mut sum = [0.0_f64; 4];
+    for p in self.pixels.iter() {
+      let c = r32g32b32a32_Sfloat::from(*p);
+      sum[0] += f64::from(c.r);
+      sum[1] += f64::from(c.g);
+      sum[2] += f64::from(c.b);
+      sum[3] += f64::from(c.a);
+    }
+    let count = self.pixels.len() as f64;
+    r32g32b32a32_Sfloat {
+      r: (sum[0] / count) as f32,
+      g: (sum[1] / count) as f32,
+      b: (sum[2] / count) as f32,
+      a: (sum[3] / count) as f32,
+    }
+  }
+
+  /// Gets the most common color of the image, roughly.
+  ///
+  /// Pixels are sorted into a coarse histogram (16 levels per channel, based
+  /// on the straight alpha color), and the output is the average of all the
+  /// pixels that went into the fullest bucket, in linear pre-multiplied form.
+  /// Fully transparent pixels are skipped, so if there's no visible pixels you
+  /// get `None`.
+  #[inline]
+  #[must_use]
+  pub fn dominant_color(&self) -> Option<r32g32b32a32_Sfloat> {
+    const LEVELS: usize = 16;
+    let mut buckets = alloc::vec![(0_usize, [0.0_f64; 4]); LEVELS * LEVELS * LEVELS];
+    let level = |c: f32| ((c.clamp(0.0, 1.0) * (LEVELS - 1) as f32).round()) as usize;
+    for p in self.pixels.iter() {
+      let c = r32g32b32a32_Sfloat::from(*p);
+      if c.a <= 0.0 {
+        continue;
+      }
+      let s = unpremultiply(c);
+      let (count, sum) = &mut buckets[(level(s.r) * LEVELS + level(s.g)) * LEVELS + level(s.b)];
+      *count += 1;
+      sum[0] += f64::from(c.r);
+      sum[1] += f64::from(c.g);
+      sum[2] += f64::from(c.b);
+      sum[3] += f64::from(c.a);
+    }
+    // `max_by_key` picks the last of any ties, so reverse to favor the first.
+    let (count, sum) = buckets.iter().rev().max_by_key(|(count, _)| *count)?;
+    if *count == 0 {
+      return None;
+    }
+    let count = *count as f64;
+    Some(r32g32b32a32_Sfloat {
+      r: (sum[0] / count) as f32,
+      g: (sum[1] / count) as f32,
+      b: (sum[2] / count) as f32,
+      a: (sum[3] / count) as f32,
+    })
+  }
+
+  /// Gets the mean squared error between this image and another image.
+  ///
+  /// The error is taken over all four channels of the linear, pre-multiplied
+  /// pixel values. Two empty images have an error of 0.0, and if the images
+  /// aren't the same size you get `None`.
+  #[inline]
+  #[must_use]
+  pub fn mse(&self, other: &Bitmap<P>) -> Option<f64> {
+    if self.width != other.width || self.height != other.height {
+      return None;
+    }
+    if self.pixels.is_empty() {
+      return Some(0.0);
+    }
+    let mut sum = 0.0_f64;
+    for (a, b) in self.pixels.iter().zip(other.pixels.iter()) {
+      let a = r32g32b32a32_Sfloat::from(*a);
+      let b = r32g32b32a32_Sfloat::from(*b);
+      for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)] {
+        let d = f64::from(x) - f64::from(y);
+        sum += d * d;
+      }
+    }
+    Some(sum / (self.pixels.len() * 4) as f64)
+  }
+
+  /// Gets the peak signal-to-noise ratio between this image and another image.
+  ///
+  /// This is based on the [mse](Self::mse) with a peak value of 1.0, and it's
+  /// measured in decibels (higher is more similar). Identical images give
+  /// infinity, and if the images aren't the same size you get `None`.
+  #[inline]
+  #[must_use]
+  pub fn psnr(&self, other: &Bitmap<P>) -> Option<f64> {
+    let mse = self.mse(other)?;
+    Some(if mse == 0.0 { f64::INFINITY } else { -10.0 * mse.log10() })
+  }
+
+  /// Shrinks the image by averaging the pixels under each output pixel.
+  ///
+  /// Each output pixel covers a rectangle of the source image, and it's the
+  /// average of all source pixels in that area, weighted by how much of each
+  /// pixel is covered (so scale factors don't need to be whole numbers). This
+  /// avoids the aliasing that point sampling gives when shrinking a lot.
+  ///
+  /// Averaging only works right on pre-multiplied colors. Otherwise the color
+  /// of transparent pixels bleeds into their neighbors, which usually darkens
+  /// the edges of shapes. If the image uses straight alpha then pass
+  /// [AlphaHandling::Straight], and the colors will be pre-multiplied before
+  /// averaging and un-multiplied again in the output.
+  ///
+  /// This is intended for making images smaller, but it will also work for
+  /// making them bigger. If the source image is empty the output is filled
+  /// with transparent black.
+  #[inline]
+  #[must_use]
+  pub fn downsample_box(&self, new_w: u32, new_h: u32, alpha: AlphaHandling) -> Bitmap<P> {
+    use alloc::vec::Vec;
+    // For each output position, the source positions and their weights.
+    fn footprints(old: u32, new: u32) -> Vec<Vec<(usize, f32)>> {
+      let scale = old as f64 / new as f64;
+      (0..new)
+        .map(|i| {
+          let start = i as f64 * scale;
+          let end = (i + 1) as f64 * scale;
+          let mut out = Vec::new();
+          let mut s = start.floor();
+          while s < end {
+            let overlap = (s + 1.0).min(end) - s.max(start);
+            if overlap > 0.0 && (s as u32) < old {
+              out.push((s as usize, (overlap / scale) as f32));
+            }
+            s += 1.0;
+          }
+          out
+        })
+        .collect()
+    }
+    let transparent_black = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
+    let pixel_count = (new_w as usize) * (new_h as usize);
+    if self.width == 0 || self.height == 0 {
+      return Bitmap {
+        width: new_w,
+        height: new_h,
+        pixels: alloc::vec![transparent_black; pixel_count],
+      };
+    }
+    let xs = footprints(self.width, new_w);
+    let ys = footprints(self.height, new_h);
+    let mut pixels = Vec::with_capacity(pixel_count);
+    for y_weights in ys.iter() {
+      for x_weights in xs.iter() {
+        let mut sum = [0.0_f32; 4];
+        for &(sy, wy) in y_weights.iter() {
+          for &(sx, wx) in x_weights.iter() {
+            let i = sy * (self.width as usize) + sx;
+            let c = self.pixels.get(i).map(|p| r32g32b32a32_Sfloat::from(*p));
+            let c = alpha.to_premultiplied(c.unwrap_or(r32g32b32a32_Sfloat::TRANSPARENT_BLACK));
+            let w = wx * wy;
+            sum[0] += c.r * w;
+            sum[1] += c.g * w;
+            sum[2] += c.b * w;
+            sum[3] += c.a * w;
+          }
+        }
+        let c = r32g32b32a32_Sfloat { r: sum[0], g: sum[1], b: sum[2], a: sum[3] };
+        pixels.push(P::from(alpha.undo_premultiplied(c)));
+      }
+    }
+    Bitmap { width: new_w, height: new_h, pixels }
+  }
+
+  /// Renders the image as ASCII art, for looking at an image in a log.
+  ///
+  /// The image is box filtered down to `cols` characters across. Characters
+  /// are about twice as tall as they are wide, so each line of text covers two
+  /// lines worth of pixels to keep the aspect ratio about right. The sRGB
+  /// encoded luma of each spot picks a character from ``" .:-=+*#%@"``, from
+  /// dark to light. Transparent areas count as black.
+  ///
+  /// Each line of the output ends with `\n`. A `cols` of 0 or an empty image
+  /// gives an empty string.
+  #[inline]
+  #[must_use]
+  pub fn to_ascii(&self, cols: u32) -> alloc::string::String {
+    const RAMP: &[u8] = b" .:-=+*#%@";
+    let mut out = alloc::string::String::new();
+    if cols == 0 || self.width == 0 || self.height == 0 {
+      return out;
+    }
+    let rows = ((u64::from(self.height) * u64::from(cols)) / (u64::from(self.width) * 2)).max(1);
+    let small =
+      self.downsample_box(cols, rows.try_into().unwrap_or(u32::MAX), AlphaHandling::Premultiplied);
+    for row in small.pixels.chunks(cols as usize) {
+      for p in row.iter() {
+        let c = r32g32b32a32_Sfloat::from(*p);
+        let luma = linear_to_srgb((0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b).clamp(0.0, 1.0));
+        let i = ((luma * (RAMP.len() - 1) as f32).round() as usize).min(RAMP.len() - 1);
+        out.push(char::from(RAMP[i]));
+      }
+      out.push('\n');
+    }
+    out
+  }
+
+  /// Reduces the image to at most `max_colors` colors, using median cut.
+  ///
+  /// The colors are repeatedly split into boxes: the box with the widest
+  /// range on any channel is sorted along that channel and cut in two at the
+  /// median. Each palette entry is the average of a box, and then every pixel
+  /// is mapped to the nearest palette entry. All of this is done with the
+  /// linear, pre-multiplied RGBA values.
+  ///
+  /// `max_colors` is clamped to `1 ..= 256`, and the palette can end up
+  /// smaller if the image doesn't have that many colors.
+  #[inline]
+  #[must_use]
+  pub fn quantize_median_cut(&self, max_colors: usize) -> Palmap<u8, P> {
+    use alloc::vec::Vec;
+    let max_colors = max_colors.clamp(1, 256);
+    let to_array = |p: &P| {
+      let c = r32g32b32a32_Sfloat::from(*p);
+      [c.r, c.g, c.b, c.a]
+    };
+    let mut colors: Vec<[f32; 4]> = self.pixels.iter().map(to_array).collect();
+    // The widest channel of a box, and how wide it is.
+    let widest = |colors: &[[f32; 4]]| -> (usize, f32) {
+      (0..4)
+        .map(|ch| {
+          let (lo, hi) = colors
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), c| (lo.min(c[ch]), hi.max(c[ch])));
+          (ch, hi - lo)
+        })
+        .fold((0, 0.0), |best, this| if this.1 > best.1 { this } else { best })
+    };
+    let mut boxes: Vec<core::ops::Range<usize>> = Vec::new();
+    if !colors.is_empty() {
+      boxes.push(0..colors.len());
+    }
+    while boxes.len() < max_colors {
+      let best = boxes
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.len() > 1)
+        .map(|(i, r)| (i, widest(&colors[r.clone()])))
+        .filter(|(_, (_, width))| *width > 0.0)
+        .fold(None, |best: Option<(usize, (usize, f32))>, this| match best {
+          Some(b) if b.1 .1 >= this.1 .1 => Some(b),
+          _ => Some(this),
+        });
+      let Some((i, (ch, _))) = best else { break };
+      let range = boxes[i].clone();
+      colors[range.clone()].sort_unstable_by(|a, b| a[ch].total_cmp(&b[ch]));
+      // cut at the median, but never between two equal values, so that each
+      // color only ever ends up in one box.
+      let sorted = &colors[range.clone()];
+      let median = sorted[sorted.len() / 2][ch];
+      let mut cut = sorted.partition_point(|c| c[ch] < median);
+      if cut == 0 {
+        cut = sorted.partition_point(|c| c[ch] <= median);
+      }
+      let mid = range.start + cut;
+      boxes[i] = range.start..mid;
+      boxes.push(mid..range.end);
+    }
+    let averages: Vec<[f32; 4]> = boxes
+      .iter()
+      .map(|r| {
+        let sum = colors[r.clone()].iter().fold([0.0_f32; 4], |mut sum, c| {
+          sum.iter_mut().zip(c.iter()).for_each(|(s, c)| *s += c);
+          sum
+        });
+        sum.map(|s| s / r.len() as f32)
+      })
+      .collect();
+    let nearest = |c: [f32; 4]| -> u8 {
+      let dist = |p: &[f32; 4]| p.iter().zip(c.iter()).map(|(p, c)| (p - c) * (p - c)).sum::<f32>();
+      averages
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| dist(a).total_cmp(&dist(b)))
+        .map(|(i, _)| i as u8)
+        .unwrap_or(0)
+    };
+    let indexes = self.pixels.iter().map(|p| nearest(to_array(p))).collect();
+    let palette =
+      averages.iter().map(|&[r, g, b, a]| P::from(r32g32b32a32_Sfloat { r, g, b, a })).collect();
+    Palmap { width: self.width, height: self.height, indexes, palette }
+  }
+
+  /// Composites any number of layers over a copy of this image.
+  ///
+  /// Each layer is `(image, x, y)`, with `(x, y)` giving where the layer's top
+  /// left goes. The layers are applied in order using pre-multiplied "source
+  /// over" blending, so later layers end up on top. Any part of a layer
+  /// outside of this image is clipped away.
+  ///
+  /// Fully opaque layer pixels are just copied, without any blending math.
+  #[inline]
+  #[must_use]
+  pub fn compose_layers(&self, layers: &[(&Bitmap<P>, u32, u32)]) -> Bitmap<P> {
+    let mut out = self.clone();
+    for (layer, x, y) in layers.iter().copied() {
+      if x >= out.width || y >= out.height {
+        continue;
+      }
+      let w = layer.width.min(out.width - x) as usize;
+      let h = layer.height.min(out.height - y) as usize;
+      let src_rows = layer.pixels.chunks_exact((layer.width as usize).max(1));
+      let dst_rows = out.pixels.chunks_exact_mut((out.width as usize).max(1)).skip(y as usize);
+      for (src_row, dst_row) in src_rows.zip(dst_rows).take(h) {
+        let dst_row = &mut dst_row[(x as usize)..];
+        for (src, dst) in src_row[..w].iter().zip(dst_row.iter_mut()) {
+          let src_f = r32g32b32a32_Sfloat::from(*src);
+          if src_f.a >= 1.0 {
+            *dst = *src;
+          } else if src_f.a > 0.0 {
+            *dst = P::from(source_over(src_f, r32g32b32a32_Sfloat::from(*dst)));
+          }
+        }
+      }
+    }
+    out
+  }
+
+  /// Composites one pre-multiplied `color` over the pixel at `(x, y)`.
+  ///
+  /// This uses the same "source over" blending as
+  /// [compose_layers](Self::compose_layers), and it's handy for drawing
+  /// translucent marks one pixel at a time. Returns `false` (and doesn't
+  /// change anything) if the position is outside the image.
+  #[inline]
+  pub fn blend_pixel(&mut self, x: u32, y: u32, color: r32g32b32a32_Sfloat) -> bool {
+    match self.get_mut(x, y) {
+      Some(dst) => {
+        *dst = P::from(source_over(color, r32g32b32a32_Sfloat::from(*dst)));
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Bilinearly samples the image at a position given in pixel units.
+  ///
+  /// Pixel centers are at `x + 0.5`. Any of the four neighbor pixels that are
+  /// outside the image use `fill` instead.
+  #[inline]
+  #[must_use]
+  fn sample_bilinear_or(&self, x: f32, y: f32, fill: r32g32b32a32_Sfloat) -> r32g32b32a32_Sfloat {
+    let tap = |tx: i64, ty: i64| -> Option<r32g32b32a32_Sfloat> {
+      if tx >= 0 && ty >= 0 && tx < i64::from(self.width) && ty < i64::from(self.height) {
+        let i = (ty as usize) * (self.width as usize) + (tx as usize);
+        Some(self.pixels.get(i).map(|p| r32g32b32a32_Sfloat::from(*p)).unwrap_or(fill))
+      } else {
+        Some(fill)
+      }
+    };
+    bilinear(x - 0.5, y - 0.5, tap).unwrap_or(fill)
+  }
+
+  /// Bilinearly samples the image at a position given in normalized units.
+  ///
+  /// `(0.0, 0.0)` is the top left corner of the image, and `(1.0, 1.0)` is the
+  /// bottom right corner, with pixel centers in between (eg: in a 2 pixel wide
+  /// image the pixel centers are at `u = 0.25` and `u = 0.75`). Any of the
+  /// four neighbor pixels that are outside the image are handled with the
+  /// [WrapMode] given. An empty image gives `None`.
+  #[inline]
+  #[must_use]
+  pub fn sample_bilinear(&self, u: f32, v: f32, wrap: WrapMode) -> Option<r32g32b32a32_Sfloat> {
+    if self.width == 0 || self.height == 0 {
+      return None;
+    }
+    let tap = |tx: i64, ty: i64| -> Option<r32g32b32a32_Sfloat> {
+      let tx = wrap.wrap(tx, self.width);
+      let ty = wrap.wrap(ty, self.height);
+      self.pixels.get(ty * (self.width as usize) + tx).map(|p| r32g32b32a32_Sfloat::from(*p))
+    };
+    bilinear(u * self.width as f32 - 0.5, v * self.height as f32 - 0.5, tap)
+  }
+
+  /// Rotates the image by any angle, making a new image.
+  ///
+  /// The rotation is about the center of the image, and positive `radians`
+  /// turn the image clockwise (because the Y axis points down). The output is
+  /// made large enough to hold all of the rotated image, and any part of the
+  /// output that the source doesn't cover is `fill`.
+  ///
+  /// Pixels are sampled bilinearly, and any part of a sample that's outside
+  /// the source image uses `fill`, so the edges blend smoothly into the fill.
+  #[inline]
+  #[must_use]
+  pub fn rotate(&self, radians: f32, fill: P) -> Bitmap<P> {
+    let (sin, cos) = radians.sin_cos();
+    let w = self.width as f32;
+    let h = self.height as f32;
+    // a little slack so that float error at right angles doesn't add a line.
+    let new_size = |a: f32, b: f32| ((a * cos).abs() + (b * sin).abs() - 0.001).ceil().max(0.0);
+    let new_width = new_size(w, h) as u32;
+    let new_height = new_size(h, w) as u32;
+    let fill_f = r32g32b32a32_Sfloat::from(fill);
+    let mut pixels = alloc::vec::Vec::with_capacity((new_width as usize) * (new_height as usize));
+    for y in 0..new_height {
+      let dy = y as f32 + 0.5 - new_height as f32 / 2.0;
+      for x in 0..new_width {
+        let dx = x as f32 + 0.5 - new_width as f32 / 2.0;
+        let sx = cos * dx + sin * dy + w / 2.0;
+        let sy = -sin * dx + cos * dy + h / 2.0;
+        let p = if sx < 0.0 || sy < 0.0 || sx > w || sy > h {
+          fill
+        } else {
+          P::from(self.sample_bilinear_or(sx, sy, fill_f))
+        };
+        pixels.push(p);
+      }
+    }
+    Bitmap { width: new_width, height: new_height, pixels }
+  }
+
+  /// Blurs the image with a gaussian blur.
+  ///
+  /// The blur is done as two 1D passes, first horizontal and then vertical,
+  /// with a kernel that reaches out `ceil(3 * sigma)` pixels. Positions past
+  /// the edge of the image are clamped to the nearest edge pixel. The math is
+  /// done with the linear, pre-multiplied RGBA values, so transparent pixels
+  /// don't darken the colors next to them.
+  ///
+  /// * A `sigma` that's not positive (or not finite) leaves the image as is.
+  #[inline]
+  #[must_use]
+  pub fn gaussian_blur(&self, sigma: f32) -> Bitmap<P> {
+    use alloc::vec::Vec;
+    if !(sigma.is_finite() && sigma > 0.0) || self.pixels.is_empty() {
+      return self.clone();
+    }
+    let radius = (3.0 * sigma).ceil() as usize;
+    let mut kernel: Vec<f32> = (0..=(2 * radius))
+      .map(|i| {
+        let d = i as f32 - radius as f32;
+        (-(d * d) / (2.0 * sigma * sigma)).exp()
+      })
+      .collect();
+    let total: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|k| *k /= total);
+
+    let (w, h) = (self.width as usize, self.height as usize);
+    // Blurs `len` values spaced `stride` apart, starting at `start`.
+    let blur_line = |src: &[[f32; 4]], dst: &mut [[f32; 4]], start: usize, stride: usize, len| {
+      for i in 0..len {
+        let mut sum = [0.0_f32; 4];
+        for (k, weight) in kernel.iter().enumerate() {
+          let j = (i + k).saturating_sub(radius).min(len - 1);
+          let c = src[start + j * stride];
+          sum.iter_mut().zip(c).for_each(|(s, c)| *s += c * weight);
+        }
+        dst[start + i * stride] = sum;
+      }
+    };
+    let src: Vec<[f32; 4]> = self
+      .pixels
+      .iter()
+      .map(|p| {
+        let c = r32g32b32a32_Sfloat::from(*p);
+        [c.r, c.g, c.b, c.a]
+      })
+      .collect();
+    let mut across = alloc::vec![[0.0_f32; 4]; src.len()];
+    for y in 0..h {
+      blur_line(&src, &mut across, y * w, 1, w);
+    }
+    let mut down = alloc::vec![[0.0_f32; 4]; src.len()];
+    for x in 0..w {
+      blur_line(&across, &mut down, x, w, h);
+    }
+    let pixels =
+      down.into_iter().map(|[r, g, b, a]| P::from(r32g32b32a32_Sfloat { r, g, b, a })).collect();
+    Bitmap { width: self.width, height: self.height, pixels }
+  }
+
+  /// Convolves the image with a 3x3 kernel.
+  ///
+  /// Each color channel of the output is the sum of `kernel[dy][dx]` times
+  /// the matching neighbor, divided by `divisor`, plus `bias`, and then
+  /// clamped to `0.0 ..= 1.0`. The center of the kernel is `kernel[1][1]`, so
+  /// the identity kernel is all zero except for a 1.0 there. This covers box
+  /// blur, sharpen, emboss, edge detection, and so on.
+  ///
+  /// The math is done on the linear, straight alpha color values, and each
+  /// pixel keeps its own alpha. Positions past the edge of the image are
+  /// handled according to the [BorderMode] given.
+  ///
+  /// * A `divisor` of 0.0 is treated as 1.0.
+  /// * An empty image (or one without enough pixels) is returned as is.
+  #[inline]
+  #[must_use]
+  pub fn convolve3x3(
+    &self, kernel: [[f32; 3]; 3], divisor: f32, bias: f32, border: BorderMode,
+  ) -> Bitmap<P> {
+    use alloc::vec::Vec;
+    let (w, h) = (self.width as usize, self.height as usize);
+    if w == 0 || h == 0 || self.pixels.len() < w * h {
+      return self.clone();
+    }
+    let divisor = if divisor == 0.0 { 1.0 } else { divisor };
+    let src: Vec<r32g32b32a32_Sfloat> =
+      self.pixels[..(w * h)].iter().map(|p| unpremultiply(r32g32b32a32_Sfloat::from(*p))).collect();
+    // Finds the color at a position, which might be outside the image.
+    let get = |x: i64, y: i64| -> r32g32b32a32_Sfloat {
+      let wrap = match border {
+        BorderMode::Clamp => WrapMode::Clamp,
+        BorderMode::Mirror => WrapMode::Mirror,
+        BorderMode::Zero => {
+          if x < 0 || y < 0 || x >= w as i64 || y >= h as i64 {
+            return r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+          }
+          WrapMode::Clamp
+        }
+      };
+      src[wrap.wrap(y, self.height) * w + wrap.wrap(x, self.width)]
+    };
+    let mut pixels = Vec::with_capacity(w * h);
+    for y in 0..(h as i64) {
+      for x in 0..(w as i64) {
+        let mut sum = [0.0_f32; 3];
+        for (dy, row) in kernel.iter().enumerate() {
+          for (dx, weight) in row.iter().enumerate() {
+            let c = get(x + dx as i64 - 1, y + dy as i64 - 1);
+            sum.iter_mut().zip([c.r, c.g, c.b]).for_each(|(s, c)| *s += c * weight);
+          }
+        }
+        let f = |s: f32| (s / divisor + bias).clamp(0.0, 1.0);
+        let a = src[y as usize * w + x as usize].a;
+        let c = r32g32b32a32_Sfloat { r: f(sum[0]), g: f(sum[1]), b: f(sum[2]), a };
+        pixels.push(P::from(premultiply(c)));
+      }
+    }
+    Bitmap { width: self.width, height: self.height, pixels }
+  }
 }
 
 /// An indexed-color image.
@@ -74,6 +1934,90 @@ impl<I, P> Palmap<I, P> {
     BorrowedBitmap { width: self.width, height: self.height, pixels: &mut self.indexes }
       .vertical_flip()
   }
+  /// Gets the largest index used by any pixel, or `None` if there's no
+  /// pixels.
+  #[inline]
+  #[must_use]
+  pub fn max_index_used(&self) -> Option<I>
+  where
+    I: Copy + Ord,
+  {
+    self.indexes.iter().copied().max()
+  }
+  /// Checks that every index is within the palette.
+  ///
+  /// Converting to a [Bitmap] turns any out of range index into the default
+  /// pixel value, so this lets you catch corrupt data before that happens.
+  #[inline]
+  #[must_use]
+  pub fn is_valid(&self) -> bool
+  where
+    I: Copy,
+    usize: From<I>,
+  {
+    self.indexes.iter().all(|&i| usize::from(i) < self.palette.len())
+  }
+}
+
+/// An indexed-color image with the indexes packed into bytes.
+///
+/// This stores 1, 2, 4, or 8 bits per index, so a bilevel image takes an
+/// eighth of the memory that a [Palmap] would need.
+///
+/// * Rows are stored top to bottom, and each row starts on a new byte.
+/// * Within each byte, the leftmost pixel uses the highest bits.
+/// * Any bits after the last pixel of a row are unused.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub struct PackedPalmap<P = r8g8b8a8_Srgb> {
+  /// width in pixels.
+  pub width: u32,
+  /// height in pixels
+  pub height: u32,
+  /// bits used by each index: 1, 2, 4, or 8.
+  pub bits_per_index: u8,
+  /// the packed index bytes, see the type docs for the layout.
+  pub packed: alloc::vec::Vec<u8>,
+  /// color palette values
+  pub palette: alloc::vec::Vec<P>,
+}
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+impl<P> PackedPalmap<P> {
+  /// The number of bytes used by each row of indexes.
+  #[inline]
+  #[must_use]
+  pub fn bytes_per_row(&self) -> usize {
+    ((self.width as usize) * usize::from(self.bits_per_index)).div_ceil(8)
+  }
+  /// Gets the index at the position, or `None` if the position is out of
+  /// bounds.
+  #[inline]
+  #[must_use]
+  pub fn unpack_index(&self, x: u32, y: u32) -> Option<u8> {
+    if x >= self.width || y >= self.height {
+      return None;
+    }
+    let bits = usize::from(self.bits_per_index);
+    if !matches!(bits, 1 | 2 | 4 | 8) {
+      return None;
+    }
+    let bit = (x as usize) * bits;
+    let byte = *self.packed.get((y as usize) * self.bytes_per_row() + bit / 8)?;
+    let shift = 8 - bits - (bit % 8);
+    Some((byte >> shift) & (u8::MAX >> (8 - bits)))
+  }
+}
+
+impl<P: Clone> From<&PackedPalmap<P>> for Palmap<u8, P> {
+  #[inline]
+  fn from(packed: &PackedPalmap<P>) -> Self {
+    let indexes = (0..packed.height)
+      .flat_map(|y| (0..packed.width).map(move |x| packed.unpack_index(x, y).unwrap_or_default()))
+      .collect();
+    Palmap { width: packed.width, height: packed.height, indexes, palette: packed.palette.clone() }
+  }
 }
 
 impl<I, PxIn, PxOut> From<&Palmap<I, PxIn>> for Bitmap<PxOut>
@@ -0,0 +1,30 @@
+use crate::Bitmap;
+use core::time::Duration;
+
+/// One frame of an [Animation].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Frame<P> {
+  /// The full image of this frame.
+  pub bitmap: Bitmap<P>,
+  /// How long this frame is shown before the next frame.
+  pub delay: Duration,
+}
+
+/// A series of images that are shown one after the other.
+///
+/// Every frame is a complete image, any blending or disposal that the source
+/// format does between frames has already been applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Animation<P> {
+  /// The frames, in the order they're shown.
+  pub frames: alloc::vec::Vec<Frame<P>>,
+  /// How many times the animation plays. 0 means it loops forever.
+  pub loop_count: u32,
+}
+impl<P> From<Bitmap<P>> for Animation<P> {
+  /// Makes a single frame animation (with no delay) that plays once.
+  #[inline]
+  fn from(bitmap: Bitmap<P>) -> Self {
+    Self { frames: alloc::vec![Frame { bitmap, delay: Duration::ZERO }], loop_count: 1 }
+  }
+}
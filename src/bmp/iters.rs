@@ -4,7 +4,7 @@ use super::*;
 
 /// Iterate the palette indexes of the image bytes, based on the bit depth.
 ///
-/// Only images with `bits_per_pixel` of 1, 4, or 8 use the palette.
+/// Only images with `bits_per_pixel` of 1, 2, 4, or 8 use the palette.
 ///
 /// ## Panics
 /// * The `bits_per_pixel` must be in the range `1..=8`.
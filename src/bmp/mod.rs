@@ -6,12 +6,13 @@ use core::{mem::size_of, num::NonZeroU8};
 use pack1::U32LE;
 use pixel_formats::*;
 
+pub mod huffman;
 pub mod iters;
 pub mod nice_header;
 pub mod raw_headers;
 pub mod rle;
 
-use self::{iters::*, nice_header::*, raw_headers::*, rle::*};
+use self::{huffman::*, iters::*, nice_header::*, raw_headers::*, rle::*};
 
 /// Checks if a BMP's initial 14 bytes are correct.
 #[inline]
@@ -39,6 +40,39 @@ pub fn padded_bytes_per_line(width: u32, bits_per_pixel: u16) -> Result<usize, I
   dwords_per_line.checked_mul(4).ok_or(ImagineError::CheckedMath)
 }
 
+/// Reads a 4 byte color table entry as `[b, g, r]`.
+///
+/// Normal entries are already `[b, g, r, reserved]`. CMYK entries are stored
+/// as `[k, y, m, c]` (the little-endian form of a GDI `CMYK` value), and get
+/// converted with the usual `r = (1 - c) * (1 - k)` formula.
+#[inline]
+#[must_use]
+#[cfg(feature = "alloc")]
+fn bmp_palette_entry_bgr(chunk: &[u8], is_cmyk: bool) -> [u8; 3] {
+  if is_cmyk {
+    let k = u32::from(u8::MAX - chunk[0]);
+    let f = |ink: u8| (u32::from(u8::MAX - ink) * k / u32::from(u8::MAX)) as u8;
+    [f(chunk[1]), f(chunk[2]), f(chunk[3])]
+  } else {
+    [chunk[0], chunk[1], chunk[2]]
+  }
+}
+
+/// Gets the header of a BMP that's about to be decoded.
+///
+/// All of the decoders use this, so that they all have the same size limit:
+/// images with a width or height over 17,000 give
+/// [ImagineError::DimensionsTooLarge] before anything is allocated.
+#[inline]
+#[cfg(feature = "alloc")]
+fn bmp_get_decode_header(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError> {
+  let header = bmp_get_nice_header(bytes)?;
+  if header.width > 17_000 || header.height > 17_000 {
+    return Err(ImagineError::DimensionsTooLarge);
+  }
+  Ok(header)
+}
+
 /// Automatically allocate and fill in a [Bitmap](crate::image::Bitmap).
 ///
 /// * Paletted images will automatically get the color from the palette (illegal
@@ -60,7 +94,7 @@ where
   #[allow(unused)]
   use alloc::vec::Vec;
 
-  let header = bmp_get_nice_header(bytes)?;
+  let header = bmp_get_decode_header(bytes)?;
   let target_pixel_count: usize =
     header.width.checked_mul(header.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
   let mut bitmap: crate::Bitmap<P> = {
@@ -73,22 +107,28 @@ where
   let image_bytes = bytes.get(data_span.0..data_span.1).ok_or(ImagineError::CheckedMath)?;
   match header.data_format {
     BmpDataFormat::Indexed1 { palette_span }
+    | BmpDataFormat::Indexed2 { palette_span }
     | BmpDataFormat::Indexed4 { palette_span }
     | BmpDataFormat::Indexed4Rle { palette_span }
     | BmpDataFormat::Indexed8 { palette_span }
-    | BmpDataFormat::Indexed8Rle { palette_span } => {
+    | BmpDataFormat::Indexed8Rle { palette_span }
+    | BmpDataFormat::Indexed1Huffman { palette_span }
+    | BmpDataFormat::Indexed4RleCmyk { palette_span }
+    | BmpDataFormat::Indexed8RleCmyk { palette_span } => {
       // If we make a 256 element palette then indexing into the palette with a u8
       // will tend to optimize away the bounds check, and it usually goes much
       // faster than using `.get(i).unwrap_or_default()` or similar.
       let mut palette: [P; 256] = [r32g32b32_Sfloat::BLACK.into(); 256];
-      let pal_bytes = bytes.get(palette_span.0..palette_span.1).ok_or(ImagineError::CheckedMath)?;
+      let pal_bytes = bytes.get(palette_span.0..palette_span.1).ok_or(ImagineError::Parse)?;
+      let is_cmyk = header.data_format.is_cmyk();
       for (chunk, p) in pal_bytes.chunks_exact(4).zip(palette.iter_mut()) {
-        *p = P::from(r32g32b32_Sfloat::from(r8g8b8_Srgb { b: chunk[0], g: chunk[1], r: chunk[2] }));
+        let [b, g, r] = bmp_palette_entry_bgr(chunk, is_cmyk);
+        *p = P::from(r32g32b32_Sfloat::from(r8g8b8_Srgb { b, g, r }));
       }
       let black: P = P::from(r32g32b32_Sfloat::BLACK);
       bitmap.pixels.resize(target_pixel_count, black);
       match header.data_format {
-        BmpDataFormat::Indexed4Rle { .. } => {
+        BmpDataFormat::Indexed4Rle { .. } | BmpDataFormat::Indexed4RleCmyk { .. } => {
           let mut x: u32 = 0;
           let mut y: u32 = 0;
           'rle_for: for rle_op in bmp_iter_rle4(image_bytes) {
@@ -144,7 +184,12 @@ where
             }
           }
         }
-        BmpDataFormat::Indexed8Rle { .. } => {
+        BmpDataFormat::Indexed1Huffman { .. } => {
+          for (p, i) in bitmap.pixels.iter_mut().zip(bmp_iter_huffman1d(image_bytes, width)) {
+            *p = palette[usize::from(i)];
+          }
+        }
+        BmpDataFormat::Indexed8Rle { .. } | BmpDataFormat::Indexed8RleCmyk { .. } => {
           let mut x: u32 = 0;
           let mut y: u32 = 0;
           'rle_for: for rle_op in bmp_iter_rle8(image_bytes) {
@@ -189,6 +234,7 @@ where
         other => {
           let bits_per_pixel = match other {
             BmpDataFormat::Indexed1 { .. } => 1,
+            BmpDataFormat::Indexed2 { .. } => 2,
             BmpDataFormat::Indexed4 { .. } => 4,
             BmpDataFormat::Indexed8 { .. } => 8,
             _ => 8,
@@ -250,13 +296,74 @@ pub fn bmp_try_bitmap_rgba<P>(
 where
   P: Copy + From<r32g32b32a32_Sfloat>,
 {
+  bmp_decode_rgba(bytes, bmp_get_decode_header(bytes)?, origin_top_left, NO_TRANSFORM)
+}
+
+/// Like [bmp_try_bitmap_rgba], but 16-bit images without explicit bitmasks
+/// are read as 5-6-5 instead of 5-5-5.
+///
+/// 16-bit BMPs that use `BI_RGB` compression don't store any bitmasks, and
+/// officially they use 5 bits for each channel (with the top bit unused).
+/// However, some writers put 5-6-5 data in files like that anyway, which makes
+/// the colors come out shifted (green bleeds into red). If you know that's
+/// the case you can use this function to decode them correctly.
+///
+/// Files that declare their own bitmasks (with `BI_BITFIELDS`) always use the
+/// masks from the file, and all other formats are decoded normally.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_try_bitmap_rgba_assume_565<P>(
+  bytes: &[u8], origin_top_left: bool,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  let mut header = bmp_get_decode_header(bytes)?;
+  if header.bits_per_pixel == 16 && header.compression == BmpCompression::RgbNoCompression {
+    header.data_format =
+      BmpDataFormat::Bitmask16RGB { r_mask: 0b11111 << 11, g_mask: 0b111111 << 5, b_mask: 0b11111 };
+  }
+  bmp_decode_rgba(bytes, header, origin_top_left, NO_TRANSFORM)
+}
+
+/// The `transform` to pass to [bmp_decode_rgba] for the normal color handling.
+#[cfg(feature = "alloc")]
+const NO_TRANSFORM: Option<&dyn crate::ColorTransform> = None;
+
+/// Decodes the image data of a BMP, using the header given.
+///
+/// Normally 8-bit channels are read as sRGB and all other channel sizes are
+/// read as linear. If there's a `transform` then every pixel's stored values
+/// go through that instead. Either way, BMP alpha is straight, so the output
+/// is pre-multiplied here.
+#[inline]
+#[cfg(feature = "alloc")]
+fn bmp_decode_rgba<P, T>(
+  bytes: &[u8], header: BmpNiceHeader, origin_top_left: bool, transform: Option<&T>,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+  T: crate::ColorTransform + ?Sized,
+{
+  use crate::alloc_bitmap::premultiply;
   #[allow(unused)]
   use alloc::vec::Vec;
 
-  let header = bmp_get_nice_header(bytes)?;
-  if header.width > 17_000 || header.height > 17_000 {
-    return Err(ImagineError::DimensionsTooLarge);
-  }
+  let u8f = |u: u8| (u as f32) / (u8::MAX as f32);
+  let from_srgb8 = |r: u8, g: u8, b: u8, a: u8| -> P {
+    match transform {
+      Some(t) => P::from(premultiply(t.apply([u8f(r), u8f(g), u8f(b), u8f(a)]))),
+      None => P::from(premultiply(r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a }))),
+    }
+  };
+  let from_linear = |c: r32g32b32a32_Sfloat| -> P {
+    match transform {
+      Some(t) => P::from(premultiply(t.apply([c.r, c.g, c.b, c.a]))),
+      None => P::from(premultiply(c)),
+    }
+  };
+
   let target_pixel_count: usize =
     header.width.checked_mul(header.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
   let mut bitmap: crate::Bitmap<P> = {
@@ -269,25 +376,26 @@ where
   let image_bytes = bytes.get(data_span.0..data_span.1).ok_or(ImagineError::Parse)?;
   match header.data_format {
     BmpDataFormat::Indexed1 { palette_span }
+    | BmpDataFormat::Indexed2 { palette_span }
     | BmpDataFormat::Indexed4 { palette_span }
     | BmpDataFormat::Indexed4Rle { palette_span }
     | BmpDataFormat::Indexed8 { palette_span }
-    | BmpDataFormat::Indexed8Rle { palette_span } => {
+    | BmpDataFormat::Indexed8Rle { palette_span }
+    | BmpDataFormat::Indexed1Huffman { palette_span }
+    | BmpDataFormat::Indexed4RleCmyk { palette_span }
+    | BmpDataFormat::Indexed8RleCmyk { palette_span } => {
       // If we make a 256 element palette then indexing into the palette with a u8
       // will tend to optimize away the bounds check, and it usually goes much
       // faster than using `.get(i).unwrap_or_default()` or similar.
       let mut palette: [P; 256] = [r32g32b32a32_Sfloat::TRANSPARENT_BLACK.into(); 256];
       let pal_bytes = bytes.get(palette_span.0..palette_span.1).ok_or(ImagineError::Parse)?;
+      let is_cmyk = header.data_format.is_cmyk();
       for (chunk, p) in pal_bytes.chunks_exact(4).zip(palette.iter_mut()) {
-        *p = P::from(r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb {
-          b: chunk[0],
-          g: chunk[1],
-          r: chunk[2],
-          a: u8::MAX,
-        }));
+        let [b, g, r] = bmp_palette_entry_bgr(chunk, is_cmyk);
+        *p = from_srgb8(r, g, b, u8::MAX);
       }
       match header.data_format {
-        BmpDataFormat::Indexed4Rle { .. } => {
+        BmpDataFormat::Indexed4Rle { .. } | BmpDataFormat::Indexed4RleCmyk { .. } => {
           let black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
           bitmap.pixels.resize(target_pixel_count, black);
           let mut x: u32 = 0;
@@ -345,7 +453,14 @@ where
             }
           }
         }
-        BmpDataFormat::Indexed8Rle { .. } => {
+        BmpDataFormat::Indexed1Huffman { .. } => {
+          let black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
+          bitmap.pixels.resize(target_pixel_count, black);
+          for (p, i) in bitmap.pixels.iter_mut().zip(bmp_iter_huffman1d(image_bytes, width)) {
+            *p = palette[usize::from(i)];
+          }
+        }
+        BmpDataFormat::Indexed8Rle { .. } | BmpDataFormat::Indexed8RleCmyk { .. } => {
           let black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
           bitmap.pixels.resize(target_pixel_count, black);
           let mut x: u32 = 0;
@@ -392,6 +507,7 @@ where
         other => {
           let bits_per_pixel = match other {
             BmpDataFormat::Indexed1 { .. } => 1,
+            BmpDataFormat::Indexed2 { .. } => 2,
             BmpDataFormat::Indexed4 { .. } => 4,
             BmpDataFormat::Indexed8 { .. } => 8,
             _ => 8,
@@ -405,43 +521,42 @@ where
     }
     BmpDataFormat::BGR24 => bitmap
       .pixels
-      .extend(bmp_iter_bgr24(image_bytes, width).map(|[b, g, r]| {
-        P::from(r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a: u8::MAX }))
-      })),
+      .extend(bmp_iter_bgr24(image_bytes, width).map(|[b, g, r]| from_srgb8(r, g, b, u8::MAX))),
     BmpDataFormat::Bitmask16RGB { r_mask, g_mask, b_mask } => {
       bitmap.pixels.extend(
         bmp_iter_bitmask16_rgb(image_bytes, r_mask, g_mask, b_mask, width)
-          .map(|rgb| P::from(r32g32b32a32_Sfloat::from(rgb))),
+          .map(|rgb| from_linear(r32g32b32a32_Sfloat::from(rgb))),
       );
     }
     BmpDataFormat::Bitmask32RGB { r_mask, g_mask, b_mask } => {
       if r_mask.count_ones() == 8 && g_mask.count_ones() == 8 && b_mask.count_ones() == 8 {
         bitmap.pixels.extend(
           bmp_iter_bitmask32_srgb(image_bytes, r_mask, g_mask, b_mask, width)
-            .map(|srgb| P::from(r32g32b32a32_Sfloat::from(r32g32b32_Sfloat::from(srgb)))),
+            .map(|r8g8b8_Srgb { r, g, b }| from_srgb8(r, g, b, u8::MAX)),
         );
       } else {
         bitmap.pixels.extend(
           bmp_iter_bitmask32_linear_rgb(image_bytes, r_mask, g_mask, b_mask, width)
-            .map(|rgb| P::from(r32g32b32a32_Sfloat::from(rgb))),
+            .map(|rgb| from_linear(r32g32b32a32_Sfloat::from(rgb))),
         );
       }
     }
     BmpDataFormat::Bitmask16RGBA { r_mask, g_mask, b_mask, a_mask } => {
       bitmap.pixels.extend(
-        bmp_iter_bitmask16_rgba(image_bytes, r_mask, g_mask, b_mask, a_mask, width).map(P::from),
+        bmp_iter_bitmask16_rgba(image_bytes, r_mask, g_mask, b_mask, a_mask, width)
+          .map(from_linear),
       );
     }
     BmpDataFormat::Bitmask32RGBA { r_mask, g_mask, b_mask, a_mask } => {
       if r_mask.count_ones() == 8 && g_mask.count_ones() == 8 && b_mask.count_ones() == 8 {
         bitmap.pixels.extend(
           bmp_iter_bitmask32_srgba(image_bytes, r_mask, g_mask, b_mask, a_mask, width)
-            .map(|srgb| P::from(r32g32b32a32_Sfloat::from(srgb))),
+            .map(|r8g8b8a8_Srgb { r, g, b, a }| from_srgb8(r, g, b, a)),
         );
       } else {
         bitmap.pixels.extend(
           bmp_iter_bitmask32_linear_rgba(image_bytes, r_mask, g_mask, b_mask, a_mask, width)
-            .map(P::from),
+            .map(from_linear),
         );
       }
     }
@@ -453,3 +568,547 @@ where
   }
   Ok(bitmap)
 }
+
+/// Decodes a BMP into linear, straight alpha `r8g8b8a8_Unorm` pixels.
+///
+/// The output is the same as converting the output of [bmp_try_bitmap_rgba]
+/// (like [decode_rgba8](crate::decode_rgba8) does), but the common formats
+/// skip the per-pixel float math: 24-bit data, 32-bit data with the usual 8
+/// bit masks, and uncompressed indexed data all use a 256 entry sRGB to linear
+/// table instead. Other formats (RLE, Huffman, and unusual bitmasks) use the
+/// float path.
+///
+/// Note that 8 bits is too little precision to store linear colors well: the
+/// darkest sRGB values all round to the same few linear values, so dark
+/// gradients come out banded. If you need to keep the dark detail use
+/// [bmp_try_bitmap_rgba] with [r8g8b8a8_Srgb] or a float pixel type instead.
+///
+/// The output is automatically flipped as necessary so that the output will be
+/// oriented with the origin in the top left if `origin_top_left` is set.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_try_bitmap_rgba8(
+  bytes: &[u8], origin_top_left: bool,
+) -> Result<crate::Bitmap<r8g8b8a8_Unorm>, ImagineError> {
+  use alloc::vec::Vec;
+
+  let u8f = |c: f32| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+  let header = bmp_get_decode_header(bytes)?;
+  let is_standard_32 = |r_mask: u32, g_mask: u32, b_mask: u32| {
+    r_mask.count_ones() == 8 && g_mask.count_ones() == 8 && b_mask.count_ones() == 8
+  };
+  let is_fast = match header.data_format {
+    BmpDataFormat::BGR24
+    | BmpDataFormat::Indexed1 { .. }
+    | BmpDataFormat::Indexed2 { .. }
+    | BmpDataFormat::Indexed4 { .. }
+    | BmpDataFormat::Indexed8 { .. } => true,
+    BmpDataFormat::Bitmask32RGB { r_mask, g_mask, b_mask } => {
+      is_standard_32(r_mask, g_mask, b_mask)
+    }
+    BmpDataFormat::Bitmask32RGBA { r_mask, g_mask, b_mask, a_mask } => {
+      is_standard_32(r_mask, g_mask, b_mask) && a_mask.count_ones() == 8
+    }
+    _ => false,
+  };
+  if !is_fast {
+    let bitmap: crate::Bitmap<r32g32b32a32_Sfloat> = bmp_try_bitmap_rgba(bytes, origin_top_left)?;
+    let mut pixels = Vec::new();
+    pixels.try_reserve(bitmap.pixels.len())?;
+    pixels.extend(bitmap.pixels.iter().map(|&p| {
+      let s = crate::alloc_bitmap::unpremultiply(p);
+      r8g8b8a8_Unorm { r: u8f(s.r), g: u8f(s.g), b: u8f(s.b), a: u8f(s.a) }
+    }));
+    return Ok(crate::Bitmap { width: bitmap.width, height: bitmap.height, pixels });
+  }
+
+  let mut to_linear = [0_u8; 256];
+  for (i, out) in to_linear.iter_mut().enumerate() {
+    let srgb = r8g8b8a8_Srgb { r: i as u8, g: 0, b: 0, a: u8::MAX };
+    *out = u8f(r32g32b32a32_Sfloat::from(srgb).r);
+  }
+  let opaque = |[b, g, r]: [u8; 3]| r8g8b8a8_Unorm {
+    r: to_linear[usize::from(r)],
+    g: to_linear[usize::from(g)],
+    b: to_linear[usize::from(b)],
+    a: u8::MAX,
+  };
+
+  let target_pixel_count: usize =
+    header.width.checked_mul(header.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  let mut pixels: Vec<r8g8b8a8_Unorm> = Vec::new();
+  pixels.try_reserve(target_pixel_count)?;
+  let width = header.width;
+  let image_bytes = bytes.get(header.data_span.0..header.data_span.1).ok_or(ImagineError::Parse)?;
+  match header.data_format {
+    BmpDataFormat::Indexed1 { palette_span }
+    | BmpDataFormat::Indexed2 { palette_span }
+    | BmpDataFormat::Indexed4 { palette_span }
+    | BmpDataFormat::Indexed8 { palette_span } => {
+      let mut palette = [r8g8b8a8_Unorm::TRANSPARENT_BLACK; 256];
+      let pal_bytes = bytes.get(palette_span.0..palette_span.1).ok_or(ImagineError::Parse)?;
+      let is_cmyk = header.data_format.is_cmyk();
+      for (chunk, p) in pal_bytes.chunks_exact(4).zip(palette.iter_mut()) {
+        *p = opaque(bmp_palette_entry_bgr(chunk, is_cmyk));
+      }
+      let bits_per_pixel = match header.data_format {
+        BmpDataFormat::Indexed1 { .. } => 1,
+        BmpDataFormat::Indexed2 { .. } => 2,
+        BmpDataFormat::Indexed4 { .. } => 4,
+        _ => 8,
+      };
+      pixels.extend(
+        bmp_iter_pal_indexes_no_compression(image_bytes, width, bits_per_pixel)
+          .map(|i| palette[usize::from(i)]),
+      );
+    }
+    BmpDataFormat::BGR24 => pixels.extend(bmp_iter_bgr24(image_bytes, width).map(opaque)),
+    BmpDataFormat::Bitmask32RGB { r_mask, g_mask, b_mask } => pixels.extend(
+      bmp_iter_bitmask32_srgb(image_bytes, r_mask, g_mask, b_mask, width)
+        .map(|r8g8b8_Srgb { r, g, b }| opaque([b, g, r])),
+    ),
+    BmpDataFormat::Bitmask32RGBA { r_mask, g_mask, b_mask, a_mask } => pixels.extend(
+      bmp_iter_bitmask32_srgba(image_bytes, r_mask, g_mask, b_mask, a_mask, width).map(
+        |r8g8b8a8_Srgb { r, g, b, a }| {
+          if a == 0 {
+            r8g8b8a8_Unorm::TRANSPARENT_BLACK
+          } else {
+            r8g8b8a8_Unorm { a, ..opaque([b, g, r]) }
+          }
+        },
+      ),
+    ),
+    _ => unreachable!(),
+  }
+  pixels.resize(target_pixel_count, r8g8b8a8_Unorm::TRANSPARENT_BLACK);
+  let mut bitmap = crate::Bitmap { width: header.width, height: header.height, pixels };
+  if header.origin_top_left != origin_top_left {
+    bitmap.vertical_flip();
+  }
+  Ok(bitmap)
+}
+
+/// Like [bmp_try_bitmap_rgba], but never flips the image.
+///
+/// The image data is left in the line order that the file uses, and the
+/// [Orientation](crate::Orientation) you get back says what that order is.
+/// Most BMP files are stored with the origin in the bottom left.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_try_bitmap_rgba_oriented<P>(
+  bytes: &[u8],
+) -> Result<(crate::Bitmap<P>, crate::Orientation), ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  let header = bmp_get_decode_header(bytes)?;
+  let orientation = if header.origin_top_left {
+    crate::Orientation::TopLeft
+  } else {
+    crate::Orientation::BottomLeft
+  };
+  bmp_decode_rgba(bytes, header, header.origin_top_left, NO_TRANSFORM)
+    .map(|bitmap| (bitmap, orientation))
+}
+
+/// Decodes an uncompressed indexed BMP into a [PackedPalmap](crate::PackedPalmap).
+///
+/// The indexes keep the bit depth of the file (1, 2, 4, or 8 bits per pixel)
+/// instead of being expanded to one byte each, which saves a lot of memory
+/// with large bilevel images. The palette colors are converted from sRGB like
+/// with [bmp_try_bitmap_rgba], and they're all fully opaque.
+///
+/// The rows are flipped as necessary so that the output will be oriented with
+/// the origin in the top left if `origin_top_left` is set, or in the bottom
+/// left otherwise.
+///
+/// ## Failure
+/// * Images that aren't uncompressed indexed color give [ImagineError::Parse].
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_try_packed_palmap_rgba<P>(
+  bytes: &[u8], origin_top_left: bool,
+) -> Result<crate::PackedPalmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  use alloc::vec::Vec;
+
+  let header = bmp_get_decode_header(bytes)?;
+  let (bits_per_index, palette_span) = match header.data_format {
+    BmpDataFormat::Indexed1 { palette_span } => (1, palette_span),
+    BmpDataFormat::Indexed2 { palette_span } => (2, palette_span),
+    BmpDataFormat::Indexed4 { palette_span } => (4, palette_span),
+    BmpDataFormat::Indexed8 { palette_span } => (8, palette_span),
+    _ => return Err(ImagineError::Parse),
+  };
+  let pal_bytes = bytes.get(palette_span.0..palette_span.1).ok_or(ImagineError::Parse)?;
+  let mut palette = Vec::new();
+  palette.try_reserve(pal_bytes.len() / 4)?;
+  palette.extend(pal_bytes.chunks_exact(4).map(|chunk| {
+    P::from(r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb {
+      b: chunk[0],
+      g: chunk[1],
+      r: chunk[2],
+      a: u8::MAX,
+    }))
+  }));
+  let image_bytes = bytes.get(header.data_span.0..header.data_span.1).ok_or(ImagineError::Parse)?;
+  let padded_len = padded_bytes_per_line(header.width, u16::from(bits_per_index))?;
+  let row_len = (header.width as usize * usize::from(bits_per_index)).div_ceil(8);
+  let mut packed = Vec::new();
+  packed
+    .try_reserve(row_len.checked_mul(header.height as usize).ok_or(ImagineError::CheckedMath)?)?;
+  let rows = image_bytes.chunks_exact(padded_len).map(|line| &line[..row_len]);
+  if header.origin_top_left == origin_top_left {
+    rows.for_each(|row| packed.extend_from_slice(row));
+  } else {
+    rows.rev().for_each(|row| packed.extend_from_slice(row));
+  }
+  Ok(crate::PackedPalmap {
+    width: header.width,
+    height: header.height,
+    bits_per_index,
+    packed,
+    palette,
+  })
+}
+
+/// Gets the ICC profile embedded in a BMP, if any.
+///
+/// Only V5 headers can have an embedded profile. A linked profile (which just
+/// gives a file name) doesn't count.
+#[inline]
+pub fn bmp_get_icc_profile(bytes: &[u8]) -> Option<&[u8]> {
+  let (_file_header, rest) = try_pull_pod::<BitmapFileHeader>(bytes).ok()?;
+  let (v5, _rest) = try_pull_pod::<BitmapV5Header>(rest).ok()?;
+  if v5.size.get() as usize != size_of::<BitmapV5Header>()
+    || v5.colorspace_type.get() != PROFILE_EMBEDDED
+  {
+    return None;
+  }
+  // the offset is from the start of the info header, not the file.
+  let start =
+    size_of::<BitmapFileHeader>().checked_add(v5.color_profile_offset.get().try_into().ok()?)?;
+  let end = start.checked_add(v5.color_profile_size.get().try_into().ok()?)?;
+  bytes.get(start..end)
+}
+
+/// Gets the color table of a BMP, even when the image doesn't use it.
+///
+/// Images with more than 8 bits per pixel can still have a color table, which
+/// is a list of suggested colors for showing the image on a paletted display.
+/// The decoders ignore it, but it's useful if you're reducing the image to a
+/// palette and want to start from the author's choices. This gives the table
+/// whenever the header declares one, regardless of the bit depth, and for
+/// indexed images it's just the normal palette.
+///
+/// The entries are opaque. You get `None` if the header can't be parsed, if
+/// there's no color table, or if the table runs past the end of the file.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_get_suggested_palette(bytes: &[u8]) -> Option<alloc::vec::Vec<r8g8b8a8_Srgb>> {
+  let header = bmp_get_nice_header(bytes).ok()?;
+  if header.palette_len == 0 {
+    return None;
+  }
+  let (_file_header, rest) = try_pull_pod::<BitmapFileHeader>(bytes).ok()?;
+  let (info_header_size, _) = try_pull_pod::<U32LE>(rest).ok()?;
+  // with the smallest info header, any bitfield masks go before the table.
+  let masks_size = match header.compression {
+    _ if info_header_size.get() as usize != size_of::<BitmapInfoHeader>() => 0,
+    BmpCompression::Bitfields => 3 * 4,
+    BmpCompression::AlphaBitfields => 4 * 4,
+    _ => 0,
+  };
+  let start = size_of::<BitmapFileHeader>()
+    .checked_add(usize::try_from(info_header_size.get()).ok()?)?
+    .checked_add(masks_size)?;
+  let end = start.checked_add(header.palette_len.checked_mul(4)?)?;
+  let is_cmyk = matches!(
+    header.compression,
+    BmpCompression::CmykNoCompression | BmpCompression::CmykRLE4 | BmpCompression::CmykRLE8
+  );
+  let palette = bytes
+    .get(start..end)?
+    .chunks_exact(4)
+    .map(|chunk| {
+      let [b, g, r] = bmp_palette_entry_bgr(chunk, is_cmyk);
+      r8g8b8a8_Srgb { r, g, b, a: u8::MAX }
+    })
+    .collect();
+  Some(palette)
+}
+
+/// Like [bmp_try_bitmap_rgba], but using a [ColorTransform](crate::ColorTransform)
+/// when the BMP has an embedded ICC profile.
+///
+/// * If there's no embedded profile this is the same as [bmp_try_bitmap_rgba].
+/// * If there is one, each pixel's stored values are passed through
+///   `transform` instead of being treated as sRGB.
+///
+/// Use [bmp_get_icc_profile] to get the profile for building the transform.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_try_bitmap_rgba_with_transform<P, T>(
+  bytes: &[u8], origin_top_left: bool, transform: &T,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+  T: crate::ColorTransform + ?Sized,
+{
+  if bmp_get_icc_profile(bytes).is_none() {
+    return bmp_try_bitmap_rgba(bytes, origin_top_left);
+  }
+  bmp_decode_rgba(bytes, bmp_get_decode_header(bytes)?, origin_top_left, Some(transform))
+}
+
+/// Options for writing BMP data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BmpWriteOptions {
+  /// If the rows should be written top to bottom.
+  ///
+  /// By default (`false`) the image is written in the conventional bottom-up
+  /// form. When this is `true` the header gets a negative height and the rows
+  /// are stored starting with the top row, which some programs prefer.
+  pub top_down: bool,
+}
+
+/// Encodes a [Bitmap](crate::Bitmap) as the bytes of a 24bpp BMP file.
+///
+/// The pixels are expected to be linear and pre-multiplied (the same as what
+/// the decoders output), and they're stored as sRGB. BMP files written this way
+/// have no alpha channel, so alpha is dropped, which for pre-multiplied colors
+/// is the same as compositing the image over black.
+///
+/// ## Failure
+/// * If the bitmap doesn't have exactly `width * height` pixels you get
+///   [ImagineError::DimensionsMismatch].
+/// * Images too large for the BMP header fields give
+///   [ImagineError::DimensionsTooLarge].
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_to_bytes<P>(
+  bitmap: &crate::Bitmap<P>, options: BmpWriteOptions,
+) -> Result<alloc::vec::Vec<u8>, ImagineError>
+where
+  P: Copy,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  use alloc::vec::Vec;
+  use pack1::{I32LE, U16LE};
+
+  let pixel_count: usize =
+    bitmap.width.checked_mul(bitmap.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  if bitmap.pixels.len() != pixel_count {
+    return Err(ImagineError::DimensionsMismatch);
+  }
+  let width = i32::try_from(bitmap.width).map_err(|_| ImagineError::DimensionsTooLarge)?;
+  let height = i32::try_from(bitmap.height).map_err(|_| ImagineError::DimensionsTooLarge)?;
+  let bytes_per_line = padded_bytes_per_line(bitmap.width, 24)?;
+  let image_size =
+    bytes_per_line.checked_mul(bitmap.height as usize).ok_or(ImagineError::CheckedMath)?;
+  let bitmap_offset = size_of::<BitmapFileHeader>() + size_of::<BitmapInfoHeader>();
+  let file_size = bitmap_offset.checked_add(image_size).ok_or(ImagineError::CheckedMath)?;
+
+  let file_header = BitmapFileHeader {
+    ty: crate::ascii_array::AsciiArray(*b"BM"),
+    file_size: U32LE::new(file_size.try_into().map_err(|_| ImagineError::DimensionsTooLarge)?),
+    reserved1: U16LE::new(0),
+    reserved2: U16LE::new(0),
+    bitmap_offset: U32LE::new(bitmap_offset as u32),
+  };
+  let info_header = BitmapInfoHeader {
+    size: U32LE::new(size_of::<BitmapInfoHeader>() as u32),
+    width: I32LE::new(width),
+    height: I32LE::new(if options.top_down { -height } else { height }),
+    planes: U16LE::new(1),
+    bits_per_pixel: U16LE::new(24),
+    compression: U32LE::new(BI_RGB),
+    image_size: U32LE::new(
+      u32::try_from(image_size).map_err(|_| ImagineError::DimensionsTooLarge)?,
+    ),
+    // 72 dpi
+    pixels_per_meter_x: I32LE::new(2835),
+    pixels_per_meter_y: I32LE::new(2835),
+    colors_used: U32LE::new(0),
+    important_colors: U32LE::new(0),
+  };
+
+  let mut out: Vec<u8> = Vec::new();
+  out.try_reserve(file_size)?;
+  out.extend_from_slice(bytemuck::bytes_of(&file_header));
+  out.extend_from_slice(bytemuck::bytes_of(&info_header));
+  let mut write_row = |row: &[P]| {
+    let line_start = out.len();
+    for p in row {
+      let mut sfloat = r32g32b32a32_Sfloat::from(*p);
+      sfloat.a = 1.0;
+      let r8g8b8a8_Srgb { r, g, b, a: _ } = r8g8b8a8_Srgb::from(sfloat);
+      out.extend_from_slice(&[b, g, r]);
+    }
+    out.resize(line_start + bytes_per_line, 0);
+  };
+  let rows = bitmap.pixels.chunks_exact((bitmap.width as usize).max(1));
+  if options.top_down {
+    rows.for_each(&mut write_row);
+  } else {
+    rows.rev().for_each(&mut write_row);
+  }
+  Ok(out)
+}
+
+/// Encodes a [Palmap](crate::Palmap) as the bytes of an indexed BMP file.
+///
+/// The palette becomes the BMP color table and the indexes are stored
+/// directly, using 1, 2, 4, or 8 bits per pixel depending on how many palette
+/// entries are needed. Note that 2 bits per pixel is a less common form of
+/// BMP (originally from Windows CE) that some older programs can't read.
+///
+/// Palette colors are handled like with [bmp_to_bytes]: they're expected to be
+/// linear and pre-multiplied, they're stored as sRGB, and alpha is dropped.
+///
+/// * Only the first 256 palette entries can be used by `u8` indexes, so any
+///   others are left out.
+/// * If some index is past the end of the palette, the color table is padded
+///   out with black to cover it.
+///
+/// ## Failure
+/// * If the palmap doesn't have exactly `width * height` indexes you get
+///   [ImagineError::DimensionsMismatch].
+/// * Images too large for the BMP header fields give
+///   [ImagineError::DimensionsTooLarge].
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn bmp_palmap_to_bytes<P>(
+  palmap: &crate::Palmap<u8, P>, options: BmpWriteOptions,
+) -> Result<alloc::vec::Vec<u8>, ImagineError>
+where
+  P: Copy,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  use alloc::vec::Vec;
+  use pack1::{I32LE, U16LE};
+
+  let pixel_count: usize =
+    palmap.width.checked_mul(palmap.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  if palmap.indexes.len() != pixel_count {
+    return Err(ImagineError::DimensionsMismatch);
+  }
+  let width = i32::try_from(palmap.width).map_err(|_| ImagineError::DimensionsTooLarge)?;
+  let height = i32::try_from(palmap.height).map_err(|_| ImagineError::DimensionsTooLarge)?;
+  let max_index_used = palmap.max_index_used().map_or(0, usize::from);
+  let color_count = palmap.palette.len().min(256).max(max_index_used + 1);
+  let bits_per_pixel: u16 = match color_count {
+    0..=2 => 1,
+    3..=4 => 2,
+    5..=16 => 4,
+    _ => 8,
+  };
+  let bytes_per_line = padded_bytes_per_line(palmap.width, bits_per_pixel)?;
+  let image_size =
+    bytes_per_line.checked_mul(palmap.height as usize).ok_or(ImagineError::CheckedMath)?;
+  let bitmap_offset =
+    size_of::<BitmapFileHeader>() + size_of::<BitmapInfoHeader>() + color_count * 4;
+  let file_size = bitmap_offset.checked_add(image_size).ok_or(ImagineError::CheckedMath)?;
+
+  let file_header = BitmapFileHeader {
+    ty: crate::ascii_array::AsciiArray(*b"BM"),
+    file_size: U32LE::new(file_size.try_into().map_err(|_| ImagineError::DimensionsTooLarge)?),
+    reserved1: U16LE::new(0),
+    reserved2: U16LE::new(0),
+    bitmap_offset: U32LE::new(bitmap_offset as u32),
+  };
+  let info_header = BitmapInfoHeader {
+    size: U32LE::new(size_of::<BitmapInfoHeader>() as u32),
+    width: I32LE::new(width),
+    height: I32LE::new(if options.top_down { -height } else { height }),
+    planes: U16LE::new(1),
+    bits_per_pixel: U16LE::new(bits_per_pixel),
+    compression: U32LE::new(BI_RGB),
+    image_size: U32LE::new(
+      u32::try_from(image_size).map_err(|_| ImagineError::DimensionsTooLarge)?,
+    ),
+    // 72 dpi
+    pixels_per_meter_x: I32LE::new(2835),
+    pixels_per_meter_y: I32LE::new(2835),
+    colors_used: U32LE::new(color_count as u32),
+    important_colors: U32LE::new(0),
+  };
+
+  let mut out: Vec<u8> = Vec::new();
+  out.try_reserve(file_size)?;
+  out.extend_from_slice(bytemuck::bytes_of(&file_header));
+  out.extend_from_slice(bytemuck::bytes_of(&info_header));
+  for i in 0..color_count {
+    let mut sfloat = palmap
+      .palette
+      .get(i)
+      .map_or(r32g32b32a32_Sfloat::OPAQUE_BLACK, |p| r32g32b32a32_Sfloat::from(*p));
+    sfloat.a = 1.0;
+    let r8g8b8a8_Srgb { r, g, b, a: _ } = r8g8b8a8_Srgb::from(sfloat);
+    out.extend_from_slice(&[b, g, r, 0]);
+  }
+  let per_byte = 8 / usize::from(bits_per_pixel);
+  let mut write_row = |row: &[u8]| {
+    let line_start = out.len();
+    for chunk in row.chunks(per_byte) {
+      let mut byte = 0_u8;
+      for (n, i) in chunk.iter().enumerate() {
+        byte |= i << (8 - bits_per_pixel as usize * (n + 1));
+      }
+      out.push(byte);
+    }
+    out.resize(line_start + bytes_per_line, 0);
+  };
+  let rows = palmap.indexes.chunks_exact((palmap.width as usize).max(1));
+  if options.top_down {
+    rows.for_each(&mut write_row);
+  } else {
+    rows.rev().for_each(&mut write_row);
+  }
+  Ok(out)
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "bmp", feature = "alloc"))))]
+impl<P> crate::Bitmap<P>
+where
+  P: Copy,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  /// Encodes the bitmap as the bytes of a 24bpp BMP file.
+  ///
+  /// This is the method form of [bmp_to_bytes].
+  #[inline]
+  pub fn to_bmp_bytes(
+    &self, options: BmpWriteOptions,
+  ) -> Result<alloc::vec::Vec<u8>, ImagineError> {
+    bmp_to_bytes(self, options)
+  }
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "bmp", feature = "alloc"))))]
+impl<P> crate::Palmap<u8, P>
+where
+  P: Copy,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  /// Encodes the palmap as the bytes of an indexed BMP file.
+  ///
+  /// This is the method form of [bmp_palmap_to_bytes].
+  #[inline]
+  pub fn to_bmp_bytes(
+    &self, options: BmpWriteOptions,
+  ) -> Result<alloc::vec::Vec<u8>, ImagineError> {
+    bmp_palmap_to_bytes(self, options)
+  }
+}
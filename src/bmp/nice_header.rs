@@ -8,6 +8,8 @@ use super::*;
 pub enum BmpDataFormat {
   /// Indexed color, 1bpp
   Indexed1 { palette_span: (usize, usize) },
+  /// Indexed color, 2bpp
+  Indexed2 { palette_span: (usize, usize) },
   /// Indexed color, 4bpp
   Indexed4 { palette_span: (usize, usize) },
   /// Indexed color, 4bpp and run-length encoding
@@ -16,6 +18,12 @@ pub enum BmpDataFormat {
   Indexed8 { palette_span: (usize, usize) },
   /// Indexed color, 8bpp and run-length encoding
   Indexed8Rle { palette_span: (usize, usize) },
+  /// Indexed color, 1bpp and OS/2 Huffman 1D encoding
+  Indexed1Huffman { palette_span: (usize, usize) },
+  /// Indexed CMYK color, 4bpp and run-length encoding
+  Indexed4RleCmyk { palette_span: (usize, usize) },
+  /// Indexed CMYK color, 8bpp and run-length encoding
+  Indexed8RleCmyk { palette_span: (usize, usize) },
   /// RGB color, u16 entries that are stored via bitmasking
   Bitmask16RGB { r_mask: u16, g_mask: u16, b_mask: u16 },
   /// RGBA color, u16 entries that are stored via bitmasking
@@ -32,7 +40,64 @@ impl BmpDataFormat {
   #[inline]
   #[must_use]
   pub const fn is_rle(self) -> bool {
-    matches!(self, Self::Indexed4Rle { .. } | Self::Indexed8Rle { .. })
+    matches!(
+      self,
+      Self::Indexed4Rle { .. }
+        | Self::Indexed8Rle { .. }
+        | Self::Indexed4RleCmyk { .. }
+        | Self::Indexed8RleCmyk { .. }
+    )
+  }
+  /// If the format's palette holds CMYK entries instead of RGB entries.
+  #[inline]
+  #[must_use]
+  pub const fn is_cmyk(self) -> bool {
+    matches!(self, Self::Indexed4RleCmyk { .. } | Self::Indexed8RleCmyk { .. })
+  }
+  /// If the format is the OS/2 Huffman 1D format.
+  #[inline]
+  #[must_use]
+  pub const fn is_huffman(self) -> bool {
+    matches!(self, Self::Indexed1Huffman { .. })
+  }
+}
+
+/// The compression method declared by a BMP header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(missing_docs)]
+pub enum BmpCompression {
+  RgbNoCompression,
+  RgbRLE8,
+  RgbRLE4,
+  Bitfields,
+  Jpeg,
+  Png,
+  AlphaBitfields,
+  CmykNoCompression,
+  CmykRLE8,
+  CmykRLE4,
+  /// Only used by OS/2 headers.
+  Huffman1D,
+  /// Only used by OS/2 headers.
+  Rle24,
+}
+impl TryFrom<u32> for BmpCompression {
+  type Error = ();
+  #[inline]
+  fn try_from(value: u32) -> Result<Self, Self::Error> {
+    Ok(match value {
+      BI_RGB => Self::RgbNoCompression,
+      BI_RLE8 => Self::RgbRLE8,
+      BI_RLE4 => Self::RgbRLE4,
+      BI_BITFIELDS => Self::Bitfields,
+      BI_JPEG => Self::Jpeg,
+      BI_PNG => Self::Png,
+      BI_ALPHABITFIELDS => Self::AlphaBitfields,
+      BI_CMYK => Self::CmykNoCompression,
+      BI_CMYKRLE8 => Self::CmykRLE8,
+      BI_CMYKRLE4 => Self::CmykRLE4,
+      _ => return Err(()),
+    })
   }
 }
 
@@ -40,7 +105,10 @@ impl BmpDataFormat {
 ///
 /// It collects the important info, and discards all the rest of the stuff you
 /// don't need.
+///
+/// More info might be added in the future, so this is `non_exhaustive`.
 #[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub struct BmpNiceHeader {
   /// Width in pixels.
   pub width: u32,
@@ -48,18 +116,85 @@ pub struct BmpNiceHeader {
   pub height: u32,
   /// If the origin is in the top left (otherwise it'll be the bottom left).
   pub origin_top_left: bool,
+  /// Bits per pixel of the image data.
+  pub bits_per_pixel: u16,
+  /// Compression method declared by the header.
+  pub compression: BmpCompression,
+  /// Number of entries in the color table.
+  ///
+  /// For images of 8 bits per pixel or less this is never 0: when the header
+  /// doesn't say, the palette is assumed to be full size (the same count that
+  /// the `palette_span` of the [data_format](Self::data_format) covers).
+  ///
+  /// Images with more bits per pixel don't need a color table, so for them
+  /// this is only non-zero when the header declares one (as a suggested
+  /// palette). This differs from the old "full size" guess for those images,
+  /// which gave sizes such as `1 << 24` entries for tables that weren't there.
+  pub palette_len: usize,
   /// Format of the data.
   pub data_format: BmpDataFormat,
   /// `(start, end)` of the data.
+  ///
+  /// For uncompressed data the span is always exactly `height` lines, based
+  /// on the header's dimensions, so any extra data after that (such as
+  /// padding rows, or an `image_size` field that's too big) is ignored.
   pub data_span: (usize, usize),
+  /// Horizontal and vertical resolution in pixels per meter.
+  ///
+  /// Many files leave these as 0, which means the resolution isn't known.
+  pub pixels_per_meter: (i32, i32),
+}
+impl BmpNiceHeader {
+  /// The horizontal and vertical resolution in dots per inch.
+  ///
+  /// This is `None` if either of the [pixels_per_meter](Self::pixels_per_meter)
+  /// values is 0 (or negative).
+  #[inline]
+  #[must_use]
+  pub fn dpi(&self) -> Option<(f32, f32)> {
+    let (x, y) = self.pixels_per_meter;
+    if x > 0 && y > 0 {
+      Some((x as f32 * 0.0254, y as f32 * 0.0254))
+    } else {
+      None
+    }
+  }
 }
 
 /// Reads the bytes to assemble a "nice" version of the necessary header info.
+///
+/// All seven versions of the Windows BMP info header, as well as the OS/2 2.x
+/// header, are normalized into the same [BmpNiceHeader] form.
+///
+/// The 16 byte form of the OS/2 2.x header has no compression or color table
+/// size fields, so it's always uncompressed, and the color table size comes
+/// from the bits per pixel.
 #[inline]
-#[allow(bad_style)]
 pub fn bmp_get_nice_header(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError> {
+  get_nice_header(bytes, false)
+}
+
+/// As [bmp_get_nice_header], but tries to handle an unknown info header size.
+///
+/// Some programs write an info header size that doesn't match any of the
+/// known header versions. If the declared size is unknown but at least 40, the
+/// header is read as the largest known Windows header that fits within both
+/// the declared size and the available bytes, and the rest of the declared
+/// header is skipped. The color table still starts after the full declared
+/// size.
+///
+/// Headers of a known size are handled exactly like the strict version.
+#[inline]
+pub fn bmp_get_nice_header_lenient(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError> {
+  get_nice_header(bytes, true)
+}
+
+#[allow(bad_style)]
+fn get_nice_header(bytes: &[u8], lenient: bool) -> Result<BmpNiceHeader, ImagineError> {
   const size_BitmapCoreHeader: usize = size_of::<BitmapCoreHeader>();
   const size_BitmapInfoHeader: usize = size_of::<BitmapInfoHeader>();
+  const size_BitmapOs22xHeader: usize = size_of::<BitmapOs22xHeader>();
+  const size_BitmapOs22xShortHeader: usize = size_of::<BitmapOs22xShortHeader>();
   const size_BitmapV2InfoHeader: usize = size_of::<BitmapV2InfoHeader>();
   const size_BitmapV3InfoHeader: usize = size_of::<BitmapV3InfoHeader>();
   const size_BitmapV4Header: usize = size_of::<BitmapV4Header>();
@@ -67,9 +202,35 @@ pub fn bmp_get_nice_header(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError>
   //
   let (file_header, rest) = try_pull_pod::<BitmapFileHeader>(bytes)?;
   let (info_header_size, _) = try_pull_pod::<U32LE>(rest)?;
+  let declared_size = usize::try_from(info_header_size.get())?;
+  let parse_size = match declared_size {
+    size_BitmapCoreHeader
+    | size_BitmapInfoHeader
+    | size_BitmapOs22xHeader
+    | size_BitmapOs22xShortHeader
+    | size_BitmapV2InfoHeader
+    | size_BitmapV3InfoHeader
+    | size_BitmapV4Header
+    | size_BitmapV5Header => declared_size,
+    _ if lenient => {
+      let limit = declared_size.min(rest.len());
+      [
+        size_BitmapV5Header,
+        size_BitmapV4Header,
+        size_BitmapV3InfoHeader,
+        size_BitmapV2InfoHeader,
+        size_BitmapInfoHeader,
+      ]
+      .into_iter()
+      .find(|&size| size <= limit)
+      .ok_or(ImagineError::Parse)?
+    }
+    _ => return Err(ImagineError::Parse),
+  };
+  let is_os2 = matches!(parse_size, size_BitmapOs22xHeader | size_BitmapOs22xShortHeader);
   // We "normalize" all headers into looking like a v5 header, and then write the
   // conversion to the nice header format just once.
-  let (v5, _rest) = match usize::try_from(info_header_size.get())? {
+  let (v5, _rest) = match parse_size {
     size_BitmapCoreHeader => {
       let (info, rest) = try_pull_pod::<BitmapCoreHeader>(rest)?;
       (BitmapV5Header::from(info), rest)
@@ -95,6 +256,14 @@ pub fn bmp_get_nice_header(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError>
       }
       (v5, rest)
     }
+    size_BitmapOs22xHeader => {
+      let (info, rest) = try_pull_pod::<BitmapOs22xHeader>(rest)?;
+      (BitmapV5Header::from(info), rest)
+    }
+    size_BitmapOs22xShortHeader => {
+      let (info, rest) = try_pull_pod::<BitmapOs22xShortHeader>(rest)?;
+      (BitmapV5Header::from(BitmapOs22xHeader::from(info)), rest)
+    }
     size_BitmapV2InfoHeader => {
       let (info, rest) = try_pull_pod::<BitmapV2InfoHeader>(rest)?;
       (BitmapV5Header::from(info), rest)
@@ -120,60 +289,89 @@ pub fn bmp_get_nice_header(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError>
     return Err(ImagineError::WidthOrHeightZero);
   }
   let origin_top_left = v5.height.get().is_negative();
+  let bits_per_pixel = v5.bits_per_pixel.get();
+  let compression = match v5.compression.get() {
+    BCA_HUFFMAN1D if is_os2 => BmpCompression::Huffman1D,
+    BCA_RLE24 if is_os2 => BmpCompression::Rle24,
+    other => BmpCompression::try_from(other).map_err(|_| ImagineError::Parse)?,
+  };
+  let palette_len: usize = if v5.colors_used.get() != 0 {
+    v5.colors_used.get().try_into()?
+  } else if bits_per_pixel <= 8 {
+    1_usize << bits_per_pixel
+  } else {
+    0
+  };
   let data_format = {
-    let bits_per_pixel = v5.bits_per_pixel.get();
     let compression = v5.compression.get();
     let pal_start: usize = size_of::<BitmapFileHeader>()
       .checked_add(info_header_size.get().try_into()?)
       .ok_or(ImagineError::CheckedMath)?;
-    let pal_entry_count: usize = if v5.colors_used.get() == 0 {
-      1_usize.wrapping_shl(u32::from(bits_per_pixel))
-    } else {
-      v5.colors_used.get().try_into()?
+    // `colors_used` comes straight from the file. A palette with a byte size
+    // past `u32::MAX` can't fit in any BMP, so that's an overflow on every
+    // target. Too big for the file is caught later.
+    let pal_bytes: usize = u32::try_from(palette_len)
+      .ok()
+      .and_then(|len| len.checked_mul(4))
+      .ok_or(ImagineError::CheckedMath)?
+      .try_into()?;
+    let pal_end: usize = pal_start.checked_add(pal_bytes).ok_or(ImagineError::CheckedMath)?;
+    // Some files declare bitfields but leave all the color masks zeroed, in
+    // which case we use the same masks that `BI_RGB` data would have.
+    let rgb_masks_or = |defaults: [u32; 3]| {
+      let masks = [v5.r_mask.get(), v5.g_mask.get(), v5.b_mask.get()];
+      if masks == [0; 3] {
+        defaults
+      } else {
+        masks
+      }
     };
-    let pal_end: usize = pal_start
-      .checked_add(pal_entry_count.checked_mul(4).ok_or(ImagineError::CheckedMath)?)
-      .ok_or(ImagineError::CheckedMath)?;
     match (bits_per_pixel, compression) {
+      (1, BCA_HUFFMAN1D) if is_os2 => {
+        BmpDataFormat::Indexed1Huffman { palette_span: (pal_start, pal_end) }
+      }
+      (_, BCA_HUFFMAN1D | BCA_RLE24) if is_os2 => return Err(ImagineError::Parse),
       (1, BI_RGB) => BmpDataFormat::Indexed1 { palette_span: (pal_start, pal_end) },
+      (2, BI_RGB) => BmpDataFormat::Indexed2 { palette_span: (pal_start, pal_end) },
       (4, BI_RGB) => BmpDataFormat::Indexed4 { palette_span: (pal_start, pal_end) },
       (4, BI_RLE4) => BmpDataFormat::Indexed4Rle { palette_span: (pal_start, pal_end) },
       (8, BI_RGB) => BmpDataFormat::Indexed8 { palette_span: (pal_start, pal_end) },
       (8, BI_RLE8) => BmpDataFormat::Indexed8Rle { palette_span: (pal_start, pal_end) },
+      (4, BI_CMYKRLE4) => BmpDataFormat::Indexed4RleCmyk { palette_span: (pal_start, pal_end) },
+      (8, BI_CMYKRLE8) => BmpDataFormat::Indexed8RleCmyk { palette_span: (pal_start, pal_end) },
+      // officially 555. Some files are really 565, which can't be detected,
+      // so callers that know better use `bmp_try_bitmap_rgba_assume_565`.
       (16, BI_RGB) => {
         BmpDataFormat::Bitmask16RGB { r_mask: 0b11111 << 10, g_mask: 0b11111 << 5, b_mask: 0b11111 }
       }
       (16, BI_BITFIELDS) | (16, BI_ALPHABITFIELDS) => {
+        let [r_mask, g_mask, b_mask] = rgb_masks_or([0b11111 << 10, 0b11111 << 5, 0b11111]);
         if v5.a_mask.get() != 0 {
           BmpDataFormat::Bitmask16RGBA {
-            r_mask: v5.r_mask.get().try_into()?,
-            g_mask: v5.g_mask.get().try_into()?,
-            b_mask: v5.b_mask.get().try_into()?,
+            r_mask: r_mask.try_into()?,
+            g_mask: g_mask.try_into()?,
+            b_mask: b_mask.try_into()?,
             a_mask: v5.a_mask.get().try_into()?,
           }
         } else {
           BmpDataFormat::Bitmask16RGB {
-            r_mask: v5.r_mask.get().try_into()?,
-            g_mask: v5.g_mask.get().try_into()?,
-            b_mask: v5.b_mask.get().try_into()?,
+            r_mask: r_mask.try_into()?,
+            g_mask: g_mask.try_into()?,
+            b_mask: b_mask.try_into()?,
           }
         }
       }
       (24, BI_RGB) => BmpDataFormat::BGR24,
+      // the top byte of each pixel is unused.
+      (32, BI_RGB) => {
+        BmpDataFormat::Bitmask32RGB { r_mask: 0xFF << 16, g_mask: 0xFF << 8, b_mask: 0xFF }
+      }
       (32, BI_BITFIELDS) | (32, BI_ALPHABITFIELDS) => {
+        let [r_mask, g_mask, b_mask] = rgb_masks_or([0xFF << 16, 0xFF << 8, 0xFF]);
         if v5.a_mask.get() != 0 {
-          BmpDataFormat::Bitmask32RGBA {
-            r_mask: v5.r_mask.get(),
-            g_mask: v5.g_mask.get(),
-            b_mask: v5.b_mask.get(),
-            a_mask: v5.a_mask.get(),
-          }
+          BmpDataFormat::Bitmask32RGBA { r_mask, g_mask, b_mask, a_mask: v5.a_mask.get() }
         } else {
-          BmpDataFormat::Bitmask32RGB {
-            r_mask: v5.r_mask.get(),
-            g_mask: v5.g_mask.get(),
-            b_mask: v5.b_mask.get(),
-          }
+          BmpDataFormat::Bitmask32RGB { r_mask, g_mask, b_mask }
         }
       }
       _ => return Err(ImagineError::Parse),
@@ -186,15 +384,32 @@ pub fn bmp_get_nice_header(bytes: &[u8]) -> Result<BmpNiceHeader, ImagineError>
       // that the data goes all the way to the end of the file, rounded down to a byte
       // pair, and the RLE decode will work fine.
       (bytes.len() / 2) * 2
+    } else if data_format.is_huffman() {
+      // Huffman lines are variable length, so the data just runs to the end of the
+      // file and the decoder stops once it has enough lines.
+      bytes.len()
     } else {
-      padded_bytes_per_line(width, v5.bits_per_pixel.get())?
-        .checked_mul(height.try_into()?)
+      // If the size of the data doesn't even fit in `usize` then there's no
+      // way to hold the image, so that's reported as the image being too large.
+      padded_bytes_per_line(width, v5.bits_per_pixel.get())
+        .ok()
+        .and_then(|per_line| per_line.checked_mul(usize::try_from(height).ok()?))
         .and_then(|count| data_start.checked_add(count))
-        .ok_or(ImagineError::CheckedMath)?
+        .ok_or(ImagineError::DimensionsTooLarge)?
     };
     (data_start, data_end)
   };
-  let header = BmpNiceHeader { width, height, origin_top_left, data_format, data_span };
+  let header = BmpNiceHeader {
+    width,
+    height,
+    origin_top_left,
+    bits_per_pixel,
+    compression,
+    palette_len,
+    data_format,
+    data_span,
+    pixels_per_meter: (v5.pixels_per_meter_x.get(), v5.pixels_per_meter_y.get()),
+  };
   //dbg!(header);
   Ok(header)
 }
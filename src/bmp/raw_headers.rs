@@ -13,7 +13,18 @@ pub(super) const BI_RGB: u32 = 0;
 pub(super) const BI_RLE8: u32 = 1;
 pub(super) const BI_RLE4: u32 = 2;
 pub(super) const BI_BITFIELDS: u32 = 3;
+pub(super) const BI_JPEG: u32 = 4;
+pub(super) const BI_PNG: u32 = 5;
 pub(super) const BI_ALPHABITFIELDS: u32 = 6;
+pub(super) const BI_CMYK: u32 = 11;
+pub(super) const BI_CMYKRLE8: u32 = 12;
+pub(super) const BI_CMYKRLE4: u32 = 13;
+/// OS/2 2.x headers use this value (which is `BI_BITFIELDS` in the Windows
+/// headers) for Huffman 1D compression.
+pub(super) const BCA_HUFFMAN1D: u32 = 3;
+/// OS/2 2.x headers use this value (which is `BI_JPEG` in the Windows headers)
+/// for 24bpp run-length encoding.
+pub(super) const BCA_RLE24: u32 = 4;
 pub(super) const LCS_GM_ABS_COLORIMETRIC: u32 = 0x00000008;
 pub(super) const LCS_GM_BUSINESS: u32 = 0x00000001;
 pub(super) const LCS_GM_GRAPHICS: u32 = 0x00000002;
@@ -60,6 +71,45 @@ pub(crate) struct BitmapInfoHeader {
   pub important_colors: U32LE,
 }
 
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+pub(crate) struct BitmapOs22xHeader {
+  pub size: U32LE,
+  pub width: I32LE,
+  pub height: I32LE,
+  pub planes: U16LE,
+  pub bits_per_pixel: U16LE,
+  pub compression: U32LE,
+  pub image_size: U32LE,
+  pub pixels_per_meter_x: I32LE,
+  pub pixels_per_meter_y: I32LE,
+  pub colors_used: U32LE,
+  pub important_colors: U32LE,
+  pub units: U16LE,
+  pub reserved: U16LE,
+  pub recording: U16LE,
+  pub rendering: U16LE,
+  pub size1: U32LE,
+  pub size2: U32LE,
+  pub color_encoding: U32LE,
+  pub identifier: U32LE,
+}
+
+/// The short form of the OS/2 2.x header.
+///
+/// OS/2 2.x headers can be cut short, leaving out any of the fields after
+/// `bits_per_pixel`, and the missing fields are all taken to be 0. This is
+/// the shortest form, which has no other fields at all.
+#[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+pub(crate) struct BitmapOs22xShortHeader {
+  pub size: U32LE,
+  pub width: I32LE,
+  pub height: I32LE,
+  pub planes: U16LE,
+  pub bits_per_pixel: U16LE,
+}
+
 #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 #[repr(C)]
 pub(crate) struct BitmapV2InfoHeader {
@@ -299,6 +349,51 @@ impl From<BitmapInfoHeader> for BitmapV5Header {
   }
 }
 
+impl From<BitmapOs22xShortHeader> for BitmapOs22xHeader {
+  fn from(
+    BitmapOs22xShortHeader { size, width, height, planes, bits_per_pixel }: BitmapOs22xShortHeader,
+  ) -> Self {
+    // All the missing fields are 0, which means no compression (`BI_RGB`), no
+    // halftoning, and a color table size based on the bits per pixel.
+    Self { size, width, height, planes, bits_per_pixel, ..bytemuck::Zeroable::zeroed() }
+  }
+}
+
+impl From<BitmapOs22xHeader> for BitmapV5Header {
+  fn from(
+    BitmapOs22xHeader {
+      size,
+      width,
+      height,
+      planes,
+      bits_per_pixel,
+      compression,
+      image_size,
+      pixels_per_meter_x,
+      pixels_per_meter_y,
+      colors_used,
+      important_colors,
+      ..
+    }: BitmapOs22xHeader,
+  ) -> Self {
+    // The first 40 bytes are the same as the Windows info header, and the
+    // OS/2 specific fields after that don't affect decoding.
+    Self::from(BitmapInfoHeader {
+      size,
+      width,
+      height,
+      planes,
+      bits_per_pixel,
+      compression,
+      image_size,
+      pixels_per_meter_x,
+      pixels_per_meter_y,
+      colors_used,
+      important_colors,
+    })
+  }
+}
+
 impl From<BitmapV2InfoHeader> for BitmapV5Header {
   fn from(
     BitmapV2InfoHeader {
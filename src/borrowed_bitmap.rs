@@ -15,6 +15,17 @@ pub const fn xy_width_to_index(x: u32, y: u32, width: u32) -> usize {
   y.wrapping_mul(width).wrapping_add(x) as usize
 }
 
+/// An axis to flip an image across.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Axis {
+  /// Left to right (mirrors each row).
+  Horizontal,
+  /// Top to bottom (reverses the order of the rows).
+  Vertical,
+  /// Both ways at once, which is the same as a 180 degree rotation.
+  Both,
+}
+
 /// Borrow of bitmap data.
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct BorrowedBitmap<'a, P = r8g8b8a8_Srgb> {
@@ -56,4 +67,50 @@ impl<'a, P> BorrowedBitmap<'a, P> {
       }
     }
   }
+  /// Flips the image, left to right.
+  ///
+  /// If the buffer contains less pixels than `width * height` would indicate,
+  /// this will do nothing.
+  #[inline]
+  pub fn horizontal_flip(&mut self) {
+    let num_pixels = self.width.wrapping_mul(self.height) as usize;
+    if let Some(data) = self.pixels.get_mut(..num_pixels) {
+      data.chunks_exact_mut((self.width as usize).max(1)).for_each(|row| row.reverse());
+    }
+  }
+  /// Flips the image across the given axis.
+  ///
+  /// If the buffer contains less pixels than `width * height` would indicate,
+  /// this will do nothing.
+  #[inline]
+  pub fn flip(&mut self, axis: Axis) {
+    match axis {
+      Axis::Horizontal => self.horizontal_flip(),
+      Axis::Vertical => self.vertical_flip(),
+      Axis::Both => {
+        // reversing all the pixels flips both ways in a single pass.
+        let num_pixels = self.width.wrapping_mul(self.height) as usize;
+        if let Some(data) = self.pixels.get_mut(..num_pixels) {
+          data.reverse();
+        }
+      }
+    }
+  }
+  /// Splits the image into two images, above and below `row`.
+  ///
+  /// The top image has the rows `0 .. row` and the bottom image has the rest.
+  /// A `row` past the bottom of the image is treated as the height, so the
+  /// bottom image is empty. The two images don't overlap, so they can be
+  /// processed on different threads, and each can be split again.
+  #[inline]
+  #[must_use]
+  pub fn split_at_row(self, row: u32) -> (BorrowedBitmap<'a, P>, BorrowedBitmap<'a, P>) {
+    let row = row.min(self.height);
+    let mid = (row as usize).saturating_mul(self.width as usize).min(self.pixels.len());
+    let (top, bottom) = self.pixels.split_at_mut(mid);
+    (
+      BorrowedBitmap { width: self.width, height: row, pixels: top },
+      BorrowedBitmap { width: self.width, height: self.height - row, pixels: bottom },
+    )
+  }
 }
@@ -0,0 +1,22 @@
+use pixel_formats::r32g32b32a32_Sfloat;
+
+/// A color conversion that a decoder can use in place of its own.
+///
+/// When an image has an embedded ICC profile, the decoders' built-in sRGB and
+/// gamma handling isn't really correct. This crate doesn't do color management
+/// itself, so instead you can get the profile bytes (eg:
+/// [iCCP::decompress_profile](crate::png::iCCP::decompress_profile) or
+/// [bmp_get_icc_profile](crate::bmp::bmp_get_icc_profile)), build a transform
+/// with the color management library of your choice, and then give that to a
+/// decoder function that accepts a `ColorTransform`.
+pub trait ColorTransform {
+  /// Converts one pixel.
+  ///
+  /// * The input is the pixel's stored `[r, g, b, a]` values, each scaled to
+  ///   `0.0 ..= 1.0`, without any transfer function applied. Grayscale pixels
+  ///   have `r == g == b`, and pixels without alpha have `a == 1.0` (except
+  ///   for pixels made transparent by a color key, which have `a == 0.0`).
+  /// * The output should be linear color with straight alpha in the target
+  ///   color space. The decoder handles making it pre-multiplied.
+  fn apply(&self, rgba: [f32; 4]) -> r32g32b32a32_Sfloat;
+}
@@ -4,7 +4,11 @@ use core::{
 };
 
 /// An error from the `imagine` crate.
+///
+/// More kinds of error might be added in the future, so this is
+/// `non_exhaustive`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ImagineError {
   /// Failed to parse the data given.
   Parse,
@@ -24,6 +28,24 @@ pub enum ImagineError {
 
   /// A checked math operation failed.
   CheckedMath,
+
+  /// The image's width and height don't match the destination given.
+  DimensionsMismatch,
+
+  /// A caller provided buffer is too small for the operation.
+  BufferTooSmall,
+
+  /// The data is in a format that's known, but that this crate can't decode.
+  Unsupported(UnsupportedFormat),
+}
+
+/// A format that's recognized but not decoded, see [ImagineError::Unsupported].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum UnsupportedFormat {
+  /// An MNG (animated) or JNG (JPEG based) file. These are relatives of PNG
+  /// with a different signature, and they're easy to mistake for PNG files.
+  MngOrJng,
 }
 #[cfg(feature = "alloc")]
 impl From<alloc::collections::TryReserveError> for ImagineError {
@@ -0,0 +1,92 @@
+/// The image formats that this crate knows about.
+///
+/// This doesn't depend on which formats are enabled by cargo features, so you
+/// can identify a file even if you can't decode it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImageFormat {
+  /// Portable Network Graphics
+  Png,
+  /// Windows Bitmap
+  Bmp,
+  /// Any of the Netpbm formats (pbm, pgm, ppm, pam)
+  Netpbm,
+}
+impl ImageFormat {
+  /// The MIME type of the format.
+  ///
+  /// Netpbm doesn't tell the sub-formats apart, so it uses the general
+  /// `image/x-portable-anymap` type.
+  #[inline]
+  #[must_use]
+  pub const fn mime_type(self) -> &'static str {
+    match self {
+      ImageFormat::Png => "image/png",
+      ImageFormat::Bmp => "image/bmp",
+      ImageFormat::Netpbm => "image/x-portable-anymap",
+    }
+  }
+
+  /// The usual file extension of the format, without the leading dot.
+  ///
+  /// Netpbm doesn't tell the sub-formats apart, so it uses the general `pnm`
+  /// extension.
+  #[inline]
+  #[must_use]
+  pub const fn preferred_extension(self) -> &'static str {
+    match self {
+      ImageFormat::Png => "png",
+      ImageFormat::Bmp => "bmp",
+      ImageFormat::Netpbm => "pnm",
+    }
+  }
+}
+
+/// Guesses the format of an image from the first few bytes of data.
+///
+/// Only the signature at the very start of the data is checked, so you can
+/// pass just the start of a file. 16 bytes is always enough.
+///
+/// * PNG: the full 8 byte PNG signature.
+/// * BMP: the `BM` tag. The rest of the BMP file header depends on the length
+///   of the whole file, so that's not checked.
+/// * Netpbm: a `P1` through `P7` tag followed by whitespace.
+#[inline]
+#[must_use]
+pub const fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
+  match bytes {
+    [137, 80, 78, 71, 13, 10, 26, 10, ..] => Some(ImageFormat::Png),
+    [b'B', b'M', ..] => Some(ImageFormat::Bmp),
+    [b'P', b'1'..=b'7', b' ' | b'\t' | b'\n' | b'\r', ..] => Some(ImageFormat::Netpbm),
+    _ => None,
+  }
+}
+
+/// Guesses the format of an image from the first few bytes of a reader.
+///
+/// Up to 16 bytes are read (stopping early only at the end of the data), and
+/// then those are given to [detect_format]. The bytes read are consumed from
+/// the reader, so if you want to decode the image afterward you should use a
+/// reader you can rewind (or read the start of the data yourself and call
+/// [detect_format] directly).
+///
+/// ## Failure
+/// * Any IO error other than [Interrupted](std::io::ErrorKind::Interrupted) is
+///   returned.
+#[inline]
+#[cfg(feature = "std")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "std")))]
+pub fn detect_format_from_reader<R: std::io::Read>(
+  r: &mut R,
+) -> std::io::Result<Option<ImageFormat>> {
+  let mut buf = [0_u8; 16];
+  let mut len = 0;
+  while len < buf.len() {
+    match r.read(&mut buf[len..]) {
+      Ok(0) => break,
+      Ok(n) => len += n,
+      Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(detect_format(&buf[..len]))
+}
@@ -15,6 +15,8 @@
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 use pixel_formats::*;
 
@@ -23,14 +25,30 @@ mod error;
 mod util;
 
 mod borrowed_bitmap;
-pub use self::{borrowed_bitmap::BorrowedBitmap, error::*};
+mod color_transform;
+mod image_format;
+pub use self::{
+  borrowed_bitmap::{Axis, BorrowedBitmap},
+  color_transform::ColorTransform,
+  error::*,
+  image_format::*,
+};
 
 #[cfg(feature = "alloc")]
 #[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
 mod alloc_bitmap;
 #[cfg(feature = "alloc")]
 #[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
-pub use self::alloc_bitmap::{Bitmap, Palmap};
+pub use self::alloc_bitmap::{
+  AlphaHandling, Bitmap, BorderMode, Channel, ChannelOrder, PackedPalmap, Palmap, WrapMode,
+};
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+mod animation;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub use self::animation::{Animation, Frame};
 
 #[cfg(feature = "png")]
 #[cfg_attr(docs_rs, doc(cfg(feature = "png")))]
@@ -58,13 +76,37 @@ pub enum sRGBIntent {
   AbsoluteColorimetric,
 }
 
+/// Which corner of the image the first pixel of the image data is in.
+///
+/// The `*_oriented` decoding functions give you the image data in the order
+/// that the file stores it, along with one of these values, instead of doing
+/// a vertical flip. This is useful if you'd rather flip the image later (eg:
+/// with texture coordinates) than pay for a pass over all the pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Orientation {
+  /// The first line of pixels is the top of the image.
+  TopLeft,
+  /// The first line of pixels is the bottom of the image.
+  BottomLeft,
+}
+impl Orientation {
+  /// If this is [Orientation::TopLeft].
+  #[inline]
+  #[must_use]
+  pub const fn is_top_left(self) -> bool {
+    matches!(self, Self::TopLeft)
+  }
+}
+
 /// Automatically allocate and fill in a [Bitmap](crate::Bitmap).
 ///
-/// This will try every format compiled into the library until one of them
-/// works, or will return a parse error if no format works. The order of trying
-/// each format is unspecified, but that basically doesn't matter because you
-/// can't really have a file that successfully parses as more than one format at
-/// the same time.
+/// The start of the data is checked against the signature of each format
+/// compiled into the library, and if one matches then only that format is
+/// tried (and you get its error if it fails). Otherwise this will try every
+/// format until one of them works, or will return a parse error if no format
+/// works. The order of trying each format is unspecified, but that basically
+/// doesn't matter because you can't really have a file that successfully
+/// parses as more than one format at the same time.
 ///
 /// The output image will automatically be vertically flipped as necessary to
 /// respect the `origin_top_left` value given.
@@ -79,7 +121,22 @@ pub fn try_bitmap_rgba<P>(
 where
   P: Copy + From<r32g32b32a32_Sfloat>,
 {
-  #[cfg(feature = "png")]
+  // cheap signature checks first, so that we only run the one decoder.
+  #[cfg(all(feature = "png", feature = "miniz_oxide"))]
+  if png::png_signature_is_correct(bytes) || png::png_signature_is_mng_or_jng(bytes) {
+    return png::png_try_bitmap_rgba(bytes, origin_top_left);
+  }
+  #[cfg(feature = "bmp")]
+  if bmp::bmp_signature_is_correct(bytes) {
+    return bmp::bmp_try_bitmap_rgba(bytes, origin_top_left);
+  }
+  #[cfg(feature = "netpbm")]
+  if matches!(netpbm::netpbm_pull_tag(bytes), Ok((1..=6, _))) {
+    return netpbm::netpbm_try_bitmap_rgba(bytes, origin_top_left);
+  }
+
+  // no signature matched, so just try everything.
+  #[cfg(all(feature = "png", feature = "miniz_oxide"))]
   if let Ok(bitmap) = png::png_try_bitmap_rgba(bytes, origin_top_left) {
     return Ok(bitmap);
   }
@@ -93,3 +150,112 @@ where
   }
   Err(ImagineError::Parse)
 }
+
+/// Automatically allocate and fill in an [Animation](crate::Animation).
+///
+/// None of the formats this crate supports are animated yet, so this decodes
+/// the image with [try_bitmap_rgba] and gives back an animation with just that
+/// one frame (with a delay of zero and a `loop_count` of 1). When animated
+/// formats are added, they'll give all of their frames through this same
+/// function, so you can use it to handle still and animated images alike.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn try_animation_rgba<P>(
+  bytes: &[u8], origin_top_left: bool,
+) -> Result<crate::Animation<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  let bitmap = try_bitmap_rgba(bytes, origin_top_left)?;
+  Ok(crate::Animation::from(bitmap))
+}
+
+/// Automatically decode an image into 8-bit RGBA with straight alpha.
+///
+/// This is for when you just want some RGBA8 pixels. The image is decoded like
+/// with [try_bitmap_rgba], and then:
+/// * The origin is always in the top left.
+/// * The alpha is straight (not pre-multiplied). Fully transparent pixels are
+///   transparent black.
+/// * The color channels are linear, like all [r8g8b8a8_Unorm] data. Note that
+///   8 bits is too little precision to store linear colors well, so if you
+///   want sRGB encoded bytes use [try_bitmap_rgba] with [r8g8b8a8_Srgb]
+///   instead.
+///
+/// A PNG that's already 8-bit RGBA without any gamma info (so, linear) is
+/// copied directly out of the image data, skipping the float conversions.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn decode_rgba8(bytes: &[u8]) -> Result<crate::Bitmap<r8g8b8a8_Unorm>, ImagineError> {
+  use alloc::vec::Vec;
+
+  #[cfg(all(feature = "png", feature = "miniz_oxide"))]
+  if let Some(ihdr) = png::png_get_header(bytes).filter(|ihdr| {
+    png::png_signature_is_correct(bytes)
+      && ihdr.color_type == png::PngColorType::RGBA
+      && ihdr.bit_depth == 8
+      && png::png_get_srgb(bytes).is_none()
+      && matches!(png::png_get_gamma(bytes), None | Some(0) | Some(100_000))
+  }) {
+    if ihdr.width > 17_000 || ihdr.height > 17_000 {
+      return Err(ImagineError::DimensionsTooLarge);
+    }
+    let pixel_count: usize =
+      ihdr.width.checked_mul(ihdr.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+    let mut pixels = Vec::new();
+    pixels.try_reserve(pixel_count)?;
+    pixels.resize(pixel_count, r8g8b8a8_Unorm::TRANSPARENT_BLACK);
+    let mut scratch = Vec::new();
+    scratch.try_reserve(ihdr.get_zlib_decompression_requirement())?;
+    scratch.resize(ihdr.get_zlib_decompression_requirement(), 0);
+    let width = ihdr.width as usize;
+    png::png_for_each_pixel(bytes, &mut scratch, |x, y, sample| {
+      if let (png::PngSample::Rgba8([r, g, b, a @ 1..=u8::MAX]), Some(p)) =
+        (sample, pixels.get_mut((y as usize) * width + (x as usize)))
+      {
+        *p = r8g8b8a8_Unorm { r, g, b, a };
+      }
+    })?;
+    return Ok(crate::Bitmap { width: ihdr.width, height: ihdr.height, pixels });
+  }
+
+  let bitmap: crate::Bitmap<r32g32b32a32_Sfloat> = try_bitmap_rgba(bytes, true)?;
+  let u8f = |c: f32| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+  let mut pixels = Vec::new();
+  pixels.try_reserve(bitmap.pixels.len())?;
+  pixels.extend(bitmap.pixels.iter().map(|&p| {
+    let s = alloc_bitmap::unpremultiply(p);
+    r8g8b8a8_Unorm { r: u8f(s.r), g: u8f(s.g), b: u8f(s.b), a: u8f(s.a) }
+  }));
+  Ok(crate::Bitmap { width: bitmap.width, height: bitmap.height, pixels })
+}
+
+/// Decodes an image in any supported format and re-encodes it as a PNG.
+///
+/// The image is decoded with [try_bitmap_rgba] into float pixels, and then
+/// written with [png::png_write_bitmap] using the smallest
+/// [png::PngWriteFormat] that holds it without loss:
+/// * If every pixel is opaque and grey, the PNG is 8-bit greyscale.
+/// * If every pixel is opaque, the PNG is 8-bit RGB.
+/// * Otherwise the PNG is 8-bit RGBA.
+///
+/// The PNG stores sRGB values, so an 8-bit sRGB source comes back out with
+/// exactly the same bytes.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "png", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "png", feature = "miniz_oxide"))))]
+pub fn transcode_to_png(bytes: &[u8]) -> Result<alloc::vec::Vec<u8>, ImagineError> {
+  use png::PngWriteFormat;
+
+  let bitmap: crate::Bitmap<r32g32b32a32_Sfloat> = try_bitmap_rgba(bytes, true)?;
+  let opaque = bitmap.pixels.iter().all(|p| p.a >= 1.0);
+  let grey = opaque && bitmap.pixels.iter().all(|p| p.r == p.g && p.g == p.b);
+  let format = match (opaque, grey) {
+    (true, true) => PngWriteFormat::Y8,
+    (true, false) => PngWriteFormat::Rgb8,
+    _ => PngWriteFormat::Rgba8,
+  };
+  png::png_write_bitmap(&bitmap, format)
+}
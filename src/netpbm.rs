@@ -4,7 +4,16 @@
 //!
 //! This supports the `P1` through `P6` formats:
 //! * `P2` and `P3` can have any maximum that fits in `u32`.
-//! * `P5` and `P6` can have any maximum that fits in `u8`.
+//! * `P5` and `P6` can have any maximum that fits in `u16`. With a maximum
+//!   over 255 each sample is two bytes, big-endian.
+//!
+//! RGBA images can also be written as `P7` (PAM) data with
+//! [`netpbm_write_pam`], but reading `P7` isn't supported.
+//!
+//! The format doesn't say if the samples are linear or gamma encoded. The
+//! basic decoders guess with [ColorAssumption::for_header], and the `_with`
+//! versions (such as [`netpbm_try_bitmap_rgba_with`]) let you pick a
+//! [ColorAssumption] instead.
 //!
 //! Generally, you should just use the [`netpbm_try_bitmap`] function to
 //! generate a [Bitmap](crate::image::Bitmap) from the RGB data with a single
@@ -24,6 +33,10 @@ pub struct NetpbmHeader {
   /// * 4: binary 1-bit
   /// * 5: binary grayscale
   /// * 6: binary rgb
+  ///
+  /// The floating point formats `PF` (rgb) and `Pf` (grayscale) have the tags
+  /// `b'F' - b'0'` and `b'f' - b'0'`. They aren't decoded by this module, but
+  /// the accessor methods of this type understand them.
   pub tag: u8,
   /// Image width
   pub width: u32,
@@ -32,6 +45,55 @@ pub struct NetpbmHeader {
   /// Max value per channel entry.
   pub max: u32,
 }
+impl NetpbmHeader {
+  /// The tag of a `PF` (floating point rgb) file.
+  pub const TAG_FLOAT_RGB: u8 = b'F' - b'0';
+  /// The tag of a `Pf` (floating point grayscale) file.
+  pub const TAG_FLOAT_GRAY: u8 = b'f' - b'0';
+
+  /// The number of channels per pixel: 1 for the 1-bit and grayscale tags, 3
+  /// for the rgb tags, and 0 for an unknown tag.
+  #[inline]
+  #[must_use]
+  pub const fn channel_count(&self) -> u8 {
+    match self.tag {
+      1 | 2 | 4 | 5 | Self::TAG_FLOAT_GRAY => 1,
+      3 | 6 | Self::TAG_FLOAT_RGB => 3,
+      _ => 0,
+    }
+  }
+  /// If the pixel data is ascii text (tags 1 through 3).
+  #[inline]
+  #[must_use]
+  pub const fn is_ascii(&self) -> bool {
+    matches!(self.tag, 1..=3)
+  }
+  /// If the pixel data is `f32` samples (the `PF` and `Pf` tags).
+  #[inline]
+  #[must_use]
+  pub const fn is_float(&self) -> bool {
+    matches!(self.tag, Self::TAG_FLOAT_RGB | Self::TAG_FLOAT_GRAY)
+  }
+  /// The number of bytes each pixel takes up in the data after the header.
+  ///
+  /// Only the binary grayscale, rgb, and float tags have a fixed size per
+  /// pixel. Each integer channel is one byte, or two bytes when `max` is more
+  /// than 255, and each float channel is four bytes. Ascii data has no fixed
+  /// size, 1-bit binary data packs 8 pixels per byte, and a binary `max` over
+  /// 65535 isn't allowed, so those (and unknown tags) give `None`.
+  #[inline]
+  #[must_use]
+  pub const fn bytes_per_pixel(&self) -> Option<usize> {
+    let bytes_per_channel = match self.tag {
+      5 | 6 if self.max > u16::MAX as u32 => return None,
+      5 | 6 if self.max > u8::MAX as u32 => 2,
+      5 | 6 => 1,
+      Self::TAG_FLOAT_RGB | Self::TAG_FLOAT_GRAY => 4,
+      _ => return None,
+    };
+    Some(bytes_per_channel * (self.channel_count() as usize))
+  }
+}
 
 /// Pulls the tag off the front of the bytes
 #[inline]
@@ -69,12 +131,15 @@ pub fn netpbm_trim(mut bytes: &[u8]) -> &[u8] {
 }
 
 /// Pulls an ascii u32 value off the front of the bytes
+///
+/// The digits can run all the way to the end of the bytes, in which case the
+/// spare bytes returned are empty.
 #[inline]
 #[doc(hidden)]
 pub fn netpbm_pull_ascii_u32(bytes: &[u8]) -> Result<(u32, &[u8]), ImagineError> {
   let mut it = bytes.splitn(2, |u| !u.is_ascii_digit());
   let digits = it.next().ok_or(ImagineError::Parse)?;
-  let spare = it.next().ok_or(ImagineError::Parse)?;
+  let spare = it.next().unwrap_or(&[]);
   let digits_str = from_utf8(digits)?;
   let number = digits_str.parse::<u32>()?;
   Ok((number, spare))
@@ -96,11 +161,12 @@ pub fn netpbm_pull_header(bytes: &[u8]) -> Result<(NetpbmHeader, &[u8]), Imagine
       let (max, rest) = netpbm_pull_ascii_u32(netpbm_trim(rest))?;
       (NetpbmHeader { tag, width, height, max }, netpbm_trim(rest))
     }
-    // binary paths must only trim to the end of the current line
-    4 => (NetpbmHeader { tag, width, height, max: 1 }, trim_to_eol(rest)),
+    // binary paths have exactly one whitespace byte before the data, and
+    // pulling the last number off already consumed it.
+    4 => (NetpbmHeader { tag, width, height, max: 1 }, rest),
     5 | 6 => {
-      let (max, rest) = netpbm_pull_ascii_u32(rest)?;
-      (NetpbmHeader { tag, width, height, max }, trim_to_eol(rest))
+      let (max, rest) = netpbm_pull_ascii_u32(netpbm_trim(rest))?;
+      (NetpbmHeader { tag, width, height, max }, rest)
     }
     _ => unreachable!(),
   })
@@ -161,6 +227,13 @@ pub fn netpbm_iter_p4(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
 pub fn netpbm_iter_p5(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
   bytes.iter().copied()
 }
+/// Iterate post-header P5 data with a `max` over 255.
+///
+/// Each sample is two bytes, big-endian. A lone byte at the end is skipped.
+#[inline]
+pub fn netpbm_iter_p5_16(bytes: &[u8]) -> impl Iterator<Item = u16> + '_ {
+  bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]]))
+}
 /// Iterate post-header P6 data.
 #[inline]
 pub fn netpbm_iter_p6(mut bytes: &[u8]) -> impl Iterator<Item = [u8; 3]> + '_ {
@@ -175,21 +248,75 @@ pub fn netpbm_iter_p6(mut bytes: &[u8]) -> impl Iterator<Item = [u8; 3]> + '_ {
     Some(out)
   })
 }
+/// Iterate post-header P6 data with a `max` over 255.
+///
+/// Each sample is two bytes, big-endian. Like with [netpbm_iter_p6], a
+/// partial pixel at the end has the missing samples as 0.
+#[inline]
+pub fn netpbm_iter_p6_16(bytes: &[u8]) -> impl Iterator<Item = [u16; 3]> + '_ {
+  let mut samples = netpbm_iter_p5_16(bytes).peekable();
+  core::iter::from_fn(move || {
+    samples.peek()?;
+    Some([(); 3].map(|_| samples.next().unwrap_or(0)))
+  })
+}
 
 /// Parse the file bytes for a header and then run the `f` given for each pixel.
 ///
 /// Pixels will be produced left to right, top to bottom.
 ///
+/// The samples are decoded to linear with [ColorAssumption::for_header]: sRGB
+/// for the color formats (P3 and P6) and linear for the grayscale and 1-bit
+/// formats. Use [`netpbm_for_each_rgb_with`] to pick a different assumption.
+///
 /// This iterator will automatically limit itself to processing *at most* the
 /// `width` and `height` found in the header. If there's more data than that it
 /// will be ignored.
+///
+/// Any sample that's larger than the header's `max` is clamped to `max`, so
+/// the color channels are always within `0.0 ..= 1.0`.
+///
+/// ## Failure
+/// * A binary format (P4, P5, or P6) file that ends right after the header,
+///   with no pixel data at all, is a parse error.
+/// * A binary format file with a `max` over 65535 is a parse error.
 #[inline]
 pub fn netpbm_for_each_rgb<F: FnMut(r32g32b32_Sfloat)>(
   bytes: &[u8], f: F,
 ) -> Result<(), ImagineError> {
   let (header, rest) = netpbm_pull_header(bytes)?;
+  for_each_rgb(header, rest, ColorAssumption::for_header(&header), f)
+}
+
+/// Like [`netpbm_for_each_rgb`], but with an explicit [ColorAssumption].
+///
+/// Each sample is normalized to `0.0 ..= 1.0` and then decoded to linear with
+/// `assume` before being passed to `f`. With [ColorAssumption::Linear] you get
+/// the normalized samples as they are.
+#[inline]
+pub fn netpbm_for_each_rgb_with<F: FnMut(r32g32b32_Sfloat)>(
+  bytes: &[u8], assume: ColorAssumption, f: F,
+) -> Result<(), ImagineError> {
+  let (header, rest) = netpbm_pull_header(bytes)?;
+  for_each_rgb(header, rest, assume, f)
+}
+
+fn for_each_rgb<F: FnMut(r32g32b32_Sfloat)>(
+  header: NetpbmHeader, rest: &[u8], assume: ColorAssumption, mut f: F,
+) -> Result<(), ImagineError> {
+  let f = |p: r32g32b32_Sfloat| {
+    f(r32g32b32_Sfloat { r: assume.decode(p.r), g: assume.decode(p.g), b: assume.decode(p.b) })
+  };
   let target_pixel_count: usize =
-    header.width.checked_mul(header.height).ok_or(ImagineError::CheckedMath)?.try_into().unwrap();
+    header.width.checked_mul(header.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  if (4..=6).contains(&header.tag) && rest.is_empty() && target_pixel_count > 0 {
+    return Err(ImagineError::Parse);
+  }
+  if (5..=6).contains(&header.tag) && header.max > u32::from(u16::MAX) {
+    return Err(ImagineError::Parse);
+  }
+  let wide = header.max > u32::from(u8::MAX);
+  let norm = |v: u32| ((v as f32) / (header.max as f32)).min(1.0);
   match header.tag {
     1 => netpbm_iter_p1(rest)
       .take(target_pixel_count)
@@ -203,16 +330,16 @@ pub fn netpbm_for_each_rgb<F: FnMut(r32g32b32_Sfloat)>(
       .for_each(f),
     2 => netpbm_iter_p2(rest)
       .map(|y| {
-        let yf = (y as f32) / (header.max as f32);
+        let yf = norm(y);
         r32g32b32_Sfloat { r: yf, g: yf, b: yf }
       })
       .for_each(f),
     3 => netpbm_iter_p3(rest)
       .take(target_pixel_count)
       .map(|[r, g, b]| {
-        let rf = (r as f32) / (header.max as f32);
-        let gf = (g as f32) / (header.max as f32);
-        let bf = (b as f32) / (header.max as f32);
+        let rf = norm(r);
+        let gf = norm(g);
+        let bf = norm(b);
         r32g32b32_Sfloat { r: rf, g: gf, b: bf }
       })
       .for_each(f),
@@ -226,19 +353,35 @@ pub fn netpbm_for_each_rgb<F: FnMut(r32g32b32_Sfloat)>(
         }
       })
       .for_each(f),
+    5 if wide => netpbm_iter_p5_16(rest)
+      .take(target_pixel_count)
+      .map(|y| {
+        let yf = norm(u32::from(y));
+        r32g32b32_Sfloat { r: yf, g: yf, b: yf }
+      })
+      .for_each(f),
     5 => netpbm_iter_p5(rest)
       .take(target_pixel_count)
       .map(|y| {
-        let yf = (y as f32) / (header.max as f32);
+        let yf = norm(u32::from(y));
         r32g32b32_Sfloat { r: yf, g: yf, b: yf }
       })
       .for_each(f),
+    6 if wide => netpbm_iter_p6_16(rest)
+      .take(target_pixel_count)
+      .map(|[r, g, b]| {
+        let rf = norm(u32::from(r));
+        let gf = norm(u32::from(g));
+        let bf = norm(u32::from(b));
+        r32g32b32_Sfloat { r: rf, g: gf, b: bf }
+      })
+      .for_each(f),
     6 => netpbm_iter_p6(rest)
       .take(target_pixel_count)
       .map(|[r, g, b]| {
-        let rf = (r as f32) / (header.max as f32);
-        let gf = (g as f32) / (header.max as f32);
-        let bf = (b as f32) / (header.max as f32);
+        let rf = norm(u32::from(r));
+        let gf = norm(u32::from(g));
+        let bf = norm(u32::from(b));
         r32g32b32_Sfloat { r: rf, g: gf, b: bf }
       })
       .for_each(f),
@@ -255,17 +398,34 @@ pub fn netpbm_for_each_rgb<F: FnMut(r32g32b32_Sfloat)>(
 ///
 /// Per the file format's definition, the origin of the image is always the top
 /// left.
+///
+/// The samples are decoded with [ColorAssumption::for_header], the same as
+/// with [`netpbm_for_each_rgb`].
 #[inline]
 #[cfg(feature = "alloc")]
 #[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
 pub fn netpbm_try_bitmap_rgb<P>(bytes: &[u8]) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32_Sfloat>,
+{
+  let (header, _rest) = netpbm_pull_header(bytes)?;
+  netpbm_try_bitmap_rgb_with(bytes, ColorAssumption::for_header(&header))
+}
+
+/// Like [`netpbm_try_bitmap_rgb`], but with an explicit [ColorAssumption].
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn netpbm_try_bitmap_rgb_with<P>(
+  bytes: &[u8], assume: ColorAssumption,
+) -> Result<crate::Bitmap<P>, ImagineError>
 where
   P: Copy + From<r32g32b32_Sfloat>,
 {
   #[allow(unused)]
   use alloc::vec::Vec;
   //
-  let (header, _rest) = netpbm_pull_header(bytes)?;
+  let (header, rest) = netpbm_pull_header(bytes)?;
   let target_pixel_count: usize =
     header.width.checked_mul(header.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
   let mut pixels: Vec<P> = {
@@ -273,7 +433,7 @@ where
     v.try_reserve(target_pixel_count)?;
     v
   };
-  netpbm_for_each_rgb(bytes, |p| pixels.push(p.into()))?;
+  for_each_rgb(header, rest, assume, |p| pixels.push(p.into()))?;
   let black: P = P::from(r32g32b32_Sfloat::BLACK);
   pixels.resize(target_pixel_count, black);
   Ok(crate::Bitmap { width: header.width, height: header.height, pixels })
@@ -282,20 +442,81 @@ where
 /// Automatically allocate and fill in a [Bitmap](crate::Bitmap).
 ///
 /// This works just like [`netpbm_try_bitmap_rgb`], but automatically adds an
-/// alpha value (full opacity).
+/// alpha value (full opacity), and can flip the image to put the origin in the
+/// bottom left.
+///
+/// The samples are decoded with [ColorAssumption::for_header], the same as
+/// with [`netpbm_try_bitmap_rgb`]. Use [`netpbm_try_bitmap_rgba_with`] to pick
+/// a different assumption.
 #[inline]
 #[cfg(feature = "alloc")]
 #[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
 pub fn netpbm_try_bitmap_rgba<P>(
   bytes: &[u8], origin_top_left: bool,
 ) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  let (header, _rest) = netpbm_pull_header(bytes)?;
+  netpbm_try_bitmap_rgba_with(bytes, origin_top_left, ColorAssumption::for_header(&header))
+}
+
+/// How the integer samples of a Netpbm file are encoded.
+///
+/// The format itself doesn't say. Color data is *often* sRGB (or Rec. 709),
+/// but grayscale data (particularly scientific data) is *often* linear. Use
+/// [ColorAssumption::for_header] to get that guess for a particular file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColorAssumption {
+  /// The samples use the sRGB transfer function.
+  Srgb,
+  /// The samples are linear, and are used as is.
+  Linear,
+  /// The samples use the Rec. 709 transfer function.
+  Rec709,
+}
+impl ColorAssumption {
+  /// The usual guess for a file: [Srgb](Self::Srgb) for the color formats
+  /// (P3 and P6) and [Linear](Self::Linear) for everything else.
+  #[inline]
+  #[must_use]
+  pub const fn for_header(header: &NetpbmHeader) -> Self {
+    match header.tag {
+      3 | 6 => Self::Srgb,
+      _ => Self::Linear,
+    }
+  }
+
+  /// Converts an encoded channel value in `0.0 ..= 1.0` to a linear value.
+  #[inline]
+  #[must_use]
+  pub fn decode(self, c: f32) -> f32 {
+    match self {
+      Self::Srgb => crate::util::srgb_to_linear(c),
+      Self::Linear => c,
+      Self::Rec709 if c < 0.081 => c / 4.5,
+      Self::Rec709 => ((c + 0.099) / 1.099).powf(1.0 / 0.45),
+    }
+  }
+}
+
+/// Like [`netpbm_try_bitmap_rgba`], but with an explicit [ColorAssumption].
+///
+/// Each sample is normalized to `0.0 ..= 1.0` and then decoded to linear with
+/// `assume` before being placed into the bitmap.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn netpbm_try_bitmap_rgba_with<P>(
+  bytes: &[u8], origin_top_left: bool, assume: ColorAssumption,
+) -> Result<crate::Bitmap<P>, ImagineError>
 where
   P: Copy + From<r32g32b32a32_Sfloat>,
 {
   #[allow(unused)]
   use alloc::vec::Vec;
   //
-  let (header, _rest) = netpbm_pull_header(bytes)?;
+  let (header, rest) = netpbm_pull_header(bytes)?;
   if header.width > 17_000 || header.height > 17_000 {
     return Err(ImagineError::DimensionsTooLarge);
   }
@@ -312,7 +533,7 @@ where
     v.try_reserve(target_pixel_count)?;
     v
   };
-  netpbm_for_each_rgb(bytes, |p| pixels.push(P::from(r32g32b32a32_Sfloat::from(p))))?;
+  for_each_rgb(header, rest, assume, |p| pixels.push(P::from(r32g32b32a32_Sfloat::from(p))))?;
   let black: P = P::from(r32g32b32a32_Sfloat::OPAQUE_BLACK);
   pixels.resize(target_pixel_count, black);
   let mut bitmap = crate::Bitmap { width: header.width, height: header.height, pixels };
@@ -321,3 +542,68 @@ where
   }
   Ok(bitmap)
 }
+
+/// Like [`netpbm_try_bitmap_rgba`], but never flips the image.
+///
+/// Netpbm always stores the image with the origin in the top left, so the
+/// [Orientation](crate::Orientation) you get back is always `TopLeft`.
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn netpbm_try_bitmap_rgba_oriented<P>(
+  bytes: &[u8],
+) -> Result<(crate::Bitmap<P>, crate::Orientation), ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  netpbm_try_bitmap_rgba(bytes, true).map(|bitmap| (bitmap, crate::Orientation::TopLeft))
+}
+
+/// Encodes a [Bitmap](crate::Bitmap) as the bytes of a `P7` (PAM) file.
+///
+/// PAM is the only Netpbm format that has an alpha channel, so this is a
+/// simple lossless way to store an RGBA image. The header uses a `DEPTH` of 4,
+/// a `MAXVAL` of 255, and a `TUPLTYPE` of `RGB_ALPHA`, and then the pixels
+/// are stored as `[r, g, b, a]` bytes, top to bottom.
+///
+/// The pixels are expected to be linear and pre-multiplied (the same as what
+/// the decoders output). They're stored as straight alpha, with the color
+/// channels sRGB encoded.
+///
+/// This module can't read `P7` data back in, so the output is only useful to
+/// other programs (or to code that parses the header itself).
+///
+/// ## Failure
+/// * If the bitmap doesn't have exactly `width * height` pixels you get
+///   [ImagineError::DimensionsMismatch].
+#[inline]
+#[cfg(feature = "alloc")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
+pub fn netpbm_write_pam<P>(bitmap: &crate::Bitmap<P>) -> Result<alloc::vec::Vec<u8>, ImagineError>
+where
+  P: Copy,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  use alloc::vec::Vec;
+
+  let pixel_count: usize =
+    bitmap.width.checked_mul(bitmap.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  if bitmap.pixels.len() != pixel_count {
+    return Err(ImagineError::DimensionsMismatch);
+  }
+  let header = alloc::format!(
+    "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+    bitmap.width,
+    bitmap.height
+  );
+  let data_len = pixel_count.checked_mul(4).ok_or(ImagineError::CheckedMath)?;
+  let mut out: Vec<u8> = Vec::new();
+  out.try_reserve(header.len().checked_add(data_len).ok_or(ImagineError::CheckedMath)?)?;
+  out.extend_from_slice(header.as_bytes());
+  for p in bitmap.pixels.iter() {
+    let straight = crate::alloc_bitmap::unpremultiply(r32g32b32a32_Sfloat::from(*p));
+    let r8g8b8a8_Srgb { r, g, b, a } = r8g8b8a8_Srgb::from(straight);
+    out.extend_from_slice(&[r, g, b, a]);
+  }
+  Ok(out)
+}
@@ -38,3 +38,36 @@ impl TryFrom<PngChunk<'_>> for bKGD {
     }
   }
 }
+impl bKGD {
+  /// Resolves the background into an actual color.
+  ///
+  /// * Grayscale and RGB values are scaled by the image's bit depth.
+  /// * Index values are looked up in the `palette`.
+  ///
+  /// The stored values are used as linear, which is what the decoder does for
+  /// a PNG without `sRGB` or `gAMA` info. Use [png_get_background_rgba] to
+  /// have those taken into account too.
+  ///
+  /// Gives `None` if the background doesn't fit the header's color type, or
+  /// the index is outside of the palette.
+  #[inline]
+  #[must_use]
+  pub fn resolve(&self, ihdr: &IHDR, palette: Option<&[[u8; 3]]>) -> Option<r32g32b32a32_Sfloat> {
+    let max = ((1_u32 << ihdr.bit_depth.min(16)) - 1) as f32;
+    let f = |v: u16| (f32::from(v) / max).min(1.0);
+    match (*self, ihdr.color_type) {
+      (bKGD::Greyscale { y }, PngColorType::Y | PngColorType::YA) => {
+        Some(r32g32b32a32_Sfloat { r: f(y), g: f(y), b: f(y), a: 1.0 })
+      }
+      (bKGD::RGB { r, g, b }, PngColorType::RGB | PngColorType::RGBA) => {
+        Some(r32g32b32a32_Sfloat { r: f(r), g: f(g), b: f(b), a: 1.0 })
+      }
+      (bKGD::Index { i }, PngColorType::Index) => {
+        let [r, g, b] = *palette?.get(usize::from(i))?;
+        let u = |v: u8| f32::from(v) / 255.0;
+        Some(r32g32b32a32_Sfloat { r: u(r), g: u(g), b: u(b), a: 1.0 })
+      }
+      _ => None,
+    }
+  }
+}
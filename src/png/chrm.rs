@@ -0,0 +1,41 @@
+use super::*;
+
+/// Primary chromaticities and white point.
+///
+/// Each value is a CIE 1931 `[x, y]` coordinate, times 100,000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(nonstandard_style)]
+pub struct cHRM {
+  /// The white point.
+  pub white: [u32; 2],
+  /// The red primary.
+  pub red: [u32; 2],
+  /// The green primary.
+  pub green: [u32; 2],
+  /// The blue primary.
+  pub blue: [u32; 2],
+}
+impl TryFrom<&[u8]> for cHRM {
+  type Error = ();
+  #[inline]
+  fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+    let data: &[u8; 32] = slice.try_into().map_err(|_| ())?;
+    let v = |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    Ok(cHRM {
+      white: [v(0), v(4)],
+      red: [v(8), v(12)],
+      green: [v(16), v(20)],
+      blue: [v(24), v(28)],
+    })
+  }
+}
+impl TryFrom<PngChunk<'_>> for cHRM {
+  type Error = ();
+  #[inline]
+  fn try_from(value: PngChunk<'_>) -> Result<Self, Self::Error> {
+    match value {
+      PngChunk::cHRM(chrm) => Ok(chrm),
+      _ => Err(()),
+    }
+  }
+}
@@ -0,0 +1,101 @@
+use super::*;
+
+/// Embedded ICC profile.
+///
+/// The chunk holds a profile name, then the profile data itself, which is
+/// always zlib compressed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(nonstandard_style)]
+pub struct iCCP<'b> {
+  name: &'b [u8],
+  compressed_profile: &'b [u8],
+}
+impl<'b> TryFrom<&'b [u8]> for iCCP<'b> {
+  type Error = ();
+  #[inline]
+  fn try_from(data: &'b [u8]) -> Result<Self, Self::Error> {
+    let name_len = data.iter().position(|&u| u == 0).ok_or(())?;
+    if !(1..=79).contains(&name_len) {
+      return Err(());
+    }
+    let (name, rest) = data.split_at(name_len);
+    match rest {
+      // null separator, then compression method 0 (zlib)
+      [0, 0, compressed_profile @ ..] => Ok(Self { name, compressed_profile }),
+      _ => Err(()),
+    }
+  }
+}
+impl<'b> TryFrom<PngChunk<'b>> for iCCP<'b> {
+  type Error = ();
+  #[inline]
+  fn try_from(value: PngChunk<'b>) -> Result<Self, Self::Error> {
+    match value {
+      PngChunk::iCCP(iccp) => Ok(iccp),
+      _ => Err(()),
+    }
+  }
+}
+impl Debug for iCCP<'_> {
+  #[inline]
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("iCCP")
+      .field("name", &self.name)
+      .field("compressed_profile_len", &self.compressed_profile.len())
+      .finish()
+  }
+}
+impl<'b> iCCP<'b> {
+  /// The profile's name (Latin-1 text, 1 to 79 bytes).
+  #[inline]
+  #[must_use]
+  pub const fn name(&self) -> &'b [u8] {
+    self.name
+  }
+
+  /// The zlib compressed profile data.
+  #[inline]
+  #[must_use]
+  pub const fn compressed_profile(&self) -> &'b [u8] {
+    self.compressed_profile
+  }
+
+  /// Decompresses the profile data.
+  ///
+  /// The output is the ICC profile itself, ready to give to a color management
+  /// library.
+  ///
+  /// ## Failure
+  /// * If the data doesn't decompress, or the profile would be over 16 MiB,
+  ///   you get a parse error.
+  #[inline]
+  #[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+  #[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+  pub fn decompress_profile(&self) -> Result<alloc::vec::Vec<u8>, ImagineError> {
+    use alloc::vec::Vec;
+    use miniz_oxide::inflate::{decompress_slice_iter_to_slice, TINFLStatus};
+    const MAX_PROFILE_SIZE: usize = 16 * 1024 * 1024;
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut capacity = 4096;
+    // We don't know the decompressed size up front, so just keep trying with
+    // a larger buffer until it fits.
+    while capacity <= MAX_PROFILE_SIZE {
+      buffer.try_reserve(capacity - buffer.len())?;
+      buffer.resize(capacity, 0);
+      match decompress_slice_iter_to_slice(
+        &mut buffer,
+        core::iter::once(self.compressed_profile),
+        true,
+        true,
+      ) {
+        Ok(len) => {
+          buffer.truncate(len);
+          return Ok(buffer);
+        }
+        Err(TINFLStatus::HasMoreOutput) => capacity *= 2,
+        Err(_) => return Err(ImagineError::Parse),
+      }
+    }
+    Err(ImagineError::Parse)
+  }
+}
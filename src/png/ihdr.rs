@@ -32,6 +32,17 @@ impl PngColorType {
       Self::RGBA => 4,
     }
   }
+
+  /// The bit depths that PNG allows for this type of color.
+  #[inline]
+  #[must_use]
+  pub const fn allowed_bit_depths(self) -> &'static [u8] {
+    match self {
+      Self::Y => &[1, 2, 4, 8, 16],
+      Self::Index => &[1, 2, 4, 8],
+      Self::RGB | Self::YA | Self::RGBA => &[8, 16],
+    }
+  }
 }
 impl TryFrom<u8> for PngColorType {
   type Error = ();
@@ -49,6 +60,9 @@ impl TryFrom<u8> for PngColorType {
 }
 
 /// Image Header
+///
+/// When parsed from a file the width and height are never 0, since that's not
+/// allowed by the spec, so a PNG with an empty image has no usable header.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct IHDR {
   /// width in pixels
@@ -138,19 +152,23 @@ impl TryFrom<&[u8]> for IHDR {
   #[inline]
   fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
     match value {
-      [w0, w1, w2, w3, h0, h1, h2, h3, bit_depth, color_type, _compression_method, _filter_method, interlace_method] => {
+      [w0, w1, w2, w3, h0, h1, h2, h3, bit_depth, color_type, _compression_method, _filter_method, interlace_method] =>
+      {
+        let color_type = PngColorType::try_from(*color_type)?;
+        if !color_type.allowed_bit_depths().contains(bit_depth) {
+          return Err(());
+        }
+        let width = u32::from_be_bytes([*w0, *w1, *w2, *w3]);
+        let height = u32::from_be_bytes([*h0, *h1, *h2, *h3]);
+        // the spec doesn't allow an empty image.
+        if width == 0 || height == 0 {
+          return Err(());
+        }
         Ok(Self {
-          width: u32::from_be_bytes([*w0, *w1, *w2, *w3]),
-          height: u32::from_be_bytes([*h0, *h1, *h2, *h3]),
-          bit_depth: match *color_type {
-            0 if [1, 2, 4, 8, 16].contains(bit_depth) => *bit_depth,
-            2 if [8, 16].contains(bit_depth) => *bit_depth,
-            3 if [1, 2, 4, 8].contains(bit_depth) => *bit_depth,
-            4 if [8, 16].contains(bit_depth) => *bit_depth,
-            6 if [8, 16].contains(bit_depth) => *bit_depth,
-            _ => return Err(()),
-          },
-          color_type: PngColorType::try_from(*color_type)?,
+          width,
+          height,
+          bit_depth: *bit_depth,
+          color_type,
           is_interlaced: match interlace_method {
             0 => false,
             1 => true,
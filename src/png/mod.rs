@@ -6,21 +6,30 @@
 
 use crate::{sRGBIntent, ImagineError};
 use core::fmt::{Debug, Write};
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+use pixel_formats::r32g32b32_Sfloat;
 use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8_Unorm, r8g8b8a8_Unorm};
 
-// TODO: CRC support for raw chunks is needed later to write PNG data.
-
 mod tests;
 
 mod bkgd;
+mod chrm;
+mod iccp;
 mod idat;
 mod ihdr;
+mod phys;
 mod plte;
 mod png_chunk;
 mod raw_chunk;
+mod splt;
+mod text;
+mod time;
 mod trns;
 
-pub use self::{bkgd::*, idat::*, ihdr::*, plte::*, png_chunk::*, raw_chunk::*, trns::*};
+pub use self::{
+  bkgd::*, chrm::*, iccp::*, idat::*, ihdr::*, phys::*, plte::*, png_chunk::*, raw_chunk::*,
+  splt::*, text::*, time::*, trns::*,
+};
 
 /// Checks if the PNG's initial 8 bytes are correct.
 #[inline]
@@ -28,6 +37,19 @@ pub const fn png_signature_is_correct(bytes: &[u8]) -> bool {
   matches!(bytes, [137, 80, 78, 71, 13, 10, 26, 10, ..])
 }
 
+/// Checks if the initial 8 bytes are an MNG or JNG signature instead.
+///
+/// MNG and JNG use the PNG chunk layout, but this crate can't decode them. The
+/// decoders give [ImagineError::Unsupported] for these files rather than a
+/// parse error.
+#[inline]
+pub const fn png_signature_is_mng_or_jng(bytes: &[u8]) -> bool {
+  matches!(
+    bytes,
+    [0x8A, b'M', b'N', b'G', 13, 10, 26, 10, ..] | [0x8B, b'J', b'N', b'G', 13, 10, 26, 10, ..]
+  )
+}
+
 /// Gets the [IHDR] out of the PNG bytes.
 #[inline]
 pub fn png_get_header(bytes: &[u8]) -> Option<IHDR> {
@@ -37,6 +59,23 @@ pub fn png_get_header(bytes: &[u8]) -> Option<IHDR> {
   })
 }
 
+/// Computes how many bytes the PNG will take when decoded to RGBA8.
+///
+/// This only reads the [IHDR], so it's a cheap way to size an output buffer
+/// before decoding. The result is `width * height * 4`.
+///
+/// ## Failure
+/// * If there's no valid [IHDR] you get [ImagineError::Parse].
+/// * If the size overflows `usize` you get [ImagineError::CheckedMath].
+#[inline]
+pub fn png_rgba8_byte_size(bytes: &[u8]) -> Result<usize, ImagineError> {
+  let ihdr = png_get_header(bytes).ok_or(ImagineError::Parse)?;
+  usize::try_from(ihdr.width)?
+    .checked_mul(usize::try_from(ihdr.height)?)
+    .and_then(|pixels| pixels.checked_mul(4))
+    .ok_or(ImagineError::CheckedMath)
+}
+
 /// Gets the transparency chunk for the PNG bytes, if any.
 #[inline]
 pub fn png_get_transparency(bytes: &[u8]) -> Option<tRNS<'_>> {
@@ -57,6 +96,85 @@ pub fn png_get_background_color(bytes: &[u8]) -> Option<bKGD> {
   })
 }
 
+/// Gets the background color as a linear color, if any.
+///
+/// This uses [bKGD::resolve], and then applies the same `sRGB` or `gAMA`
+/// handling that the decoder uses for the image's pixels, so the output can be
+/// directly composited with decoded pixels.
+#[inline]
+pub fn png_get_background_rgba(bytes: &[u8]) -> Option<r32g32b32a32_Sfloat> {
+  let ihdr = png_get_header(bytes)?;
+  let stored = png_get_background_color(bytes)?.resolve(&ihdr, png_get_palette(bytes))?;
+  let curve = |c: f32| -> f32 {
+    if png_get_srgb(bytes).is_some() {
+      crate::util::srgb_to_linear(c)
+    } else {
+      c.powf(png_gamma_exponent(bytes))
+    }
+  };
+  Some(r32g32b32a32_Sfloat { r: curve(stored.r), g: curve(stored.g), b: curve(stored.b), a: 1.0 })
+}
+
+/// Gets the embedded ICC profile info, if any.
+#[inline]
+pub fn png_get_icc_profile(bytes: &[u8]) -> Option<iCCP<'_>> {
+  PngRawChunkIter::new(bytes).find_map(|raw_chunk| {
+    let png_chunk = PngChunk::try_from(raw_chunk).ok()?;
+    let iccp = iCCP::try_from(png_chunk).ok()?;
+    Some(iccp)
+  })
+}
+
+/// Iterates all of the suggested palettes in the PNG.
+///
+/// Any `sPLT` chunk that doesn't parse is skipped.
+#[inline]
+pub fn png_get_suggested_palettes(bytes: &[u8]) -> impl Iterator<Item = sPLT<'_>> {
+  PngRawChunkIter::new(bytes).filter_map(|raw_chunk| {
+    let png_chunk = PngChunk::try_from(raw_chunk).ok()?;
+    sPLT::try_from(png_chunk).ok()
+  })
+}
+
+/// Gets the primary chromaticities and white point in the PNG, if any
+#[inline]
+pub fn png_get_chromaticities(bytes: &[u8]) -> Option<cHRM> {
+  PngRawChunkIter::new(bytes).find_map(|raw_chunk| {
+    let png_chunk = PngChunk::try_from(raw_chunk).ok()?;
+    cHRM::try_from(png_chunk).ok()
+  })
+}
+
+/// Gets the physical pixel dimensions in the PNG, if any
+#[inline]
+pub fn png_get_physical_dimensions(bytes: &[u8]) -> Option<pHYs> {
+  PngRawChunkIter::new(bytes).find_map(|raw_chunk| {
+    let png_chunk = PngChunk::try_from(raw_chunk).ok()?;
+    pHYs::try_from(png_chunk).ok()
+  })
+}
+
+/// Gets the last modification time in the PNG, if any
+#[inline]
+pub fn png_get_time(bytes: &[u8]) -> Option<tIME> {
+  PngRawChunkIter::new(bytes).find_map(|raw_chunk| {
+    let png_chunk = PngChunk::try_from(raw_chunk).ok()?;
+    tIME::try_from(png_chunk).ok()
+  })
+}
+
+/// Iterates all of the `tEXt` chunks in the PNG.
+///
+/// Any `tEXt` chunk that doesn't parse is skipped. The compressed (`zTXt`) and
+/// international (`iTXt`) text chunks aren't read.
+#[inline]
+pub fn png_get_text(bytes: &[u8]) -> impl Iterator<Item = tEXt<'_>> {
+  PngRawChunkIter::new(bytes).filter_map(|raw_chunk| {
+    let png_chunk = PngChunk::try_from(raw_chunk).ok()?;
+    tEXt::try_from(png_chunk).ok()
+  })
+}
+
 /// Gets the sRGB info in the PNG, if any
 #[inline]
 pub fn png_get_srgb(bytes: &[u8]) -> Option<sRGBIntent> {
@@ -81,6 +199,20 @@ pub fn png_get_gamma(bytes: &[u8]) -> Option<u32> {
   })
 }
 
+/// Gets the exponent that turns stored values into linear values.
+///
+/// This is `1.0 / gamma`, with a missing `gAMA` counting as a gamma of 1.0. A
+/// `gAMA` of 0 isn't allowed by the PNG spec (and would give an infinite
+/// exponent), so it's treated the same as a missing `gAMA`.
+#[inline]
+#[must_use]
+fn png_gamma_exponent(bytes: &[u8]) -> f32 {
+  match png_get_gamma(bytes) {
+    None | Some(0) => 1.0,
+    Some(g) => 100_000.0_f32 / g as f32,
+  }
+}
+
 /// Gets the palette out of the PNG bytes.
 ///
 /// Each `[u8;3]` in the palette is an `[r8, g8, b8]` color entry.
@@ -93,6 +225,193 @@ pub fn png_get_palette(bytes: &[u8]) -> Option<&[[u8; 3]]> {
   })
 }
 
+/// Gets the header of a PNG that's about to be decoded.
+///
+/// An indexed image can't be decoded without its palette, so if the `PLTE` is
+/// missing you get [ImagineError::Parse]. A `PLTE` in any other kind of image
+/// is only a suggestion, and it's ignored.
+#[inline]
+#[cfg(feature = "miniz_oxide")]
+fn png_get_decode_header(bytes: &[u8]) -> Result<IHDR, ImagineError> {
+  let ihdr = png_get_header(bytes).ok_or(ImagineError::Parse)?;
+  if ihdr.color_type == PngColorType::Index && png_get_palette(bytes).is_none() {
+    return Err(ImagineError::Parse);
+  }
+  Ok(ihdr)
+}
+
+/// Allocates the pixels to decode a PNG into, all set to `fill`.
+///
+/// The output is `(width, height, pixels)`. Images more than 17,000 pixels
+/// wide or tall give [ImagineError::DimensionsTooLarge].
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn png_alloc_decode_pixels<P: Clone>(
+  ihdr: &IHDR, fill: P,
+) -> Result<(u32, u32, alloc::vec::Vec<P>), ImagineError> {
+  use alloc::vec::Vec;
+
+  if ihdr.width > 17_000 || ihdr.height > 17_000 {
+    return Err(ImagineError::DimensionsTooLarge);
+  }
+  let pixel_count: usize =
+    ihdr.width.checked_mul(ihdr.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  let mut pixels: Vec<P> = Vec::new();
+  pixels.try_reserve(pixel_count)?;
+  pixels.resize(pixel_count, fill);
+  Ok((ihdr.width, ihdr.height, pixels))
+}
+
+/// All the header and metadata info of a PNG, see [png_collect_metadata].
+///
+/// Each field holds the first chunk of that type which parsed correctly, the
+/// same as what the matching `png_get_*` function would give you.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PngMetadata<'b> {
+  /// The image header.
+  pub header: IHDR,
+  /// The sRGB info.
+  pub srgb: Option<sRGBIntent>,
+  /// The gamma value times 100,000.
+  pub gamma: Option<u32>,
+  /// The palette entries, as `[r8, g8, b8]`.
+  pub palette: Option<&'b [[u8; 3]]>,
+  /// The transparency info.
+  pub transparency: Option<tRNS<'b>>,
+  /// The background color.
+  pub background: Option<bKGD>,
+  /// The embedded ICC profile.
+  pub icc_profile: Option<iCCP<'b>>,
+  /// The primary chromaticities and white point.
+  pub chromaticities: Option<cHRM>,
+  /// The physical pixel dimensions.
+  pub physical_dimensions: Option<pHYs>,
+  /// The last modification time.
+  pub time: Option<tIME>,
+  /// The first `tEXt` chunk. Use [png_get_text] to get all of them.
+  pub text: Option<tEXt<'b>>,
+  /// If all the `IDAT` chunks are next to each other, see
+  /// [png_idat_is_contiguous].
+  pub idat_is_contiguous: bool,
+}
+
+/// Gets all the header and metadata info of a PNG in a single pass.
+///
+/// This gives the same info as calling [png_get_header], [png_get_srgb],
+/// [png_get_gamma], [png_get_palette], [png_get_transparency],
+/// [png_get_background_color], [png_get_icc_profile],
+/// [png_get_chromaticities], [png_get_physical_dimensions], [png_get_time],
+/// and [png_get_text], but each of those scans over the chunks separately
+/// while this only goes over them once.
+///
+/// ## Failure
+/// * If there's no valid [IHDR] you get [ImagineError::Parse].
+#[inline]
+pub fn png_collect_metadata(bytes: &[u8]) -> Result<PngMetadata<'_>, ImagineError> {
+  let mut header = None;
+  let mut srgb = None;
+  let mut gamma = None;
+  let mut palette = None;
+  let mut transparency = None;
+  let mut background = None;
+  let mut icc_profile = None;
+  let mut chromaticities = None;
+  let mut physical_dimensions = None;
+  let mut time = None;
+  let mut text = None;
+  let mut idat_runs = IdatRuns::default();
+  for raw_chunk in PngRawChunkIter::new(bytes) {
+    idat_runs.push(raw_chunk.type_);
+    match PngChunk::try_from(raw_chunk) {
+      Ok(PngChunk::IHDR(ihdr)) => {
+        header.get_or_insert(ihdr);
+      }
+      Ok(PngChunk::sRGB(intent)) => {
+        srgb.get_or_insert(intent);
+      }
+      Ok(PngChunk::gAMA(g)) => {
+        gamma.get_or_insert(g);
+      }
+      Ok(PngChunk::PLTE(plte)) => {
+        palette.get_or_insert(plte.entries());
+      }
+      Ok(PngChunk::tRNS(trns)) => {
+        transparency.get_or_insert(trns);
+      }
+      Ok(PngChunk::bKGD(bkgd)) => {
+        background.get_or_insert(bkgd);
+      }
+      Ok(PngChunk::iCCP(iccp)) => {
+        icc_profile.get_or_insert(iccp);
+      }
+      Ok(PngChunk::cHRM(chrm)) => {
+        chromaticities.get_or_insert(chrm);
+      }
+      Ok(PngChunk::pHYs(phys)) => {
+        physical_dimensions.get_or_insert(phys);
+      }
+      Ok(PngChunk::tIME(t)) => {
+        time.get_or_insert(t);
+      }
+      Ok(PngChunk::tEXt(t)) => {
+        text.get_or_insert(t);
+      }
+      _ => (),
+    }
+  }
+  Ok(PngMetadata {
+    header: header.ok_or(ImagineError::Parse)?,
+    srgb,
+    gamma,
+    palette,
+    transparency,
+    background,
+    icc_profile,
+    chromaticities,
+    physical_dimensions,
+    time,
+    text,
+    idat_is_contiguous: idat_runs.is_contiguous(),
+  })
+}
+
+/// Counts the separate runs of `IDAT` chunks in a series of chunks.
+#[derive(Debug, Clone, Copy, Default)]
+struct IdatRuns {
+  runs: u32,
+  in_idat: bool,
+}
+impl IdatRuns {
+  #[inline]
+  fn push(&mut self, type_: PngRawChunkType) {
+    let is_idat = type_ == PngRawChunkType::IDAT;
+    if is_idat && !self.in_idat {
+      self.runs += 1;
+    }
+    self.in_idat = is_idat;
+  }
+  #[inline]
+  #[must_use]
+  const fn is_contiguous(self) -> bool {
+    self.runs <= 1
+  }
+}
+
+/// Checks if all the `IDAT` chunks are next to each other.
+///
+/// The PNG spec requires that the `IDAT` chunks all come in one unbroken run,
+/// but some encoders put other chunks in between them. The decoders in this
+/// crate accept that anyway (unless you use
+/// [PngDecodeOptions::require_contiguous_idat]), so this is mostly for
+/// validating files. A PNG without any `IDAT` counts as contiguous.
+#[inline]
+#[must_use]
+pub fn png_idat_is_contiguous(bytes: &[u8]) -> bool {
+  let mut idat_runs = IdatRuns::default();
+  PngRawChunkIter::new(bytes).for_each(|raw_chunk| idat_runs.push(raw_chunk.type_));
+  idat_runs.is_contiguous()
+}
+
 /// Gets an iterator over all the [IDAT] slices in the PNG bytes.
 #[inline]
 pub fn png_get_idat(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
@@ -183,95 +502,156 @@ const fn interlaced_pos_to_full_pos(
   }
 }
 
-/// Automatically allocate and fill in a [Bitmap](crate::Bitmap).
+/// Allocates a zlib buffer of the correct size for the image and decompresses
+/// all of the [IDAT] data into it.
 ///
-/// The output is automatically flipped as necessary so that the output will be
-/// oriented with the origin in the top left.
-#[inline]
-#[cfg(feature = "alloc")]
-#[cfg_attr(docs_rs, doc(cfg(feature = "alloc")))]
-pub fn png_try_bitmap_rgba<P>(
-  bytes: &[u8], origin_top_left: bool,
-) -> Result<crate::Bitmap<P>, ImagineError>
+/// The buffer still holds *filtered* data, which is what
+/// [IHDR::unfilter_decompressed_data] expects.
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn png_decompress_idat_to_vec(
+  bytes: &[u8], ihdr: &IHDR,
+) -> Result<alloc::vec::Vec<u8>, ImagineError> {
+  use alloc::vec::Vec;
+  let zlib_len = ihdr.get_zlib_decompression_requirement();
+  let mut zlib_buffer: Vec<u8> = Vec::new();
+  zlib_buffer.try_reserve(zlib_len)?;
+  zlib_buffer.resize(zlib_len, 0);
+  let _who_cares = miniz_oxide::inflate::decompress_slice_iter_to_slice(
+    &mut zlib_buffer,
+    png_get_idat(bytes),
+    true,
+    true,
+  );
+  Ok(zlib_buffer)
+}
+
+/// Unfilters the decompressed image data and writes each pixel into the
+/// bitmap, with the origin in the top left.
+///
+/// Pixels outside the bitmap are skipped.
+#[cfg(feature = "miniz_oxide")]
+fn png_unfilter_to_pixels<P>(
+  bytes: &[u8], ihdr: &IHDR, zlib_buffer: &mut [u8], bitmap: &mut crate::BorrowedBitmap<'_, P>,
+) where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  png_unfilter_for_each(bytes, ihdr, zlib_buffer, |x, y, p| {
+    if let Some(dst) = bitmap.get_mut(x, y) {
+      *dst = p;
+    }
+  });
+}
+
+/// Converts the `PLTE` (and the `tRNS` alphas for it) into linear,
+/// pre-multiplied colors, the same as all the decoders output.
+///
+/// Entries past the end of the `PLTE` are transparent black, so the output can
+/// be indexed with any `u8`.
+#[cfg(feature = "miniz_oxide")]
+fn png_resolve_palette<P>(bytes: &[u8]) -> [P; 256]
 where
   P: Copy + From<r32g32b32a32_Sfloat>,
 {
-  #[allow(unused)]
-  use alloc::vec::Vec;
-  use bitfrob::{U8_SCALE_1_TO_8, U8_SCALE_2_TO_8, U8_SCALE_4_TO_8};
   use bytemuck::cast_slice;
   use pixel_formats::{r8g8b8_Srgb, r8g8b8a8_Srgb};
 
-  let ihdr = png_get_header(bytes).ok_or(ImagineError::Parse)?;
-  if ihdr.width > 17_000 || ihdr.height > 17_000 {
-    return Err(ImagineError::DimensionsTooLarge);
-  }
-  if ihdr.width == 0 {
-    return Err(ImagineError::WidthOrHeightZero);
-  }
-  if ihdr.height == 0 {
-    return Err(ImagineError::WidthOrHeightZero);
+  let is_srgb = png_get_srgb(bytes).is_some();
+  let gamma_exp = png_gamma_exponent(bytes);
+  let trns: Option<tRNS<'_>> = png_get_transparency(bytes);
+
+  let mut palette: [P; 256] = [r32g32b32a32_Sfloat::TRANSPARENT_BLACK.into(); 256];
+  if is_srgb {
+    let plte: &[r8g8b8_Srgb] = cast_slice(png_get_palette(bytes).unwrap_or(&[]));
+    let trns: &[u8] = trns.map(|trns| trns.to_alphas()).unwrap_or(&[]);
+    palette.iter_mut().zip(plte.iter().copied()).enumerate().for_each(
+      |(i, (palette, r8g8b8_Srgb { r, g, b }))| {
+        let a: u8 = trns.get(i).copied().unwrap_or(u8::MAX);
+        let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a });
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        *palette = P::from(pre_multiplied_alpha);
+      },
+    );
+  } else {
+    let plte: &[r8g8b8_Unorm] = cast_slice(png_get_palette(bytes).unwrap_or(&[]));
+    let trns: &[u8] = trns.map(|trns| trns.to_alphas()).unwrap_or(&[]);
+    palette.iter_mut().zip(plte.iter().copied()).enumerate().for_each(
+      |(i, (palette, r8g8b8_Unorm { r, g, b }))| {
+        let a: u8 = trns.get(i).copied().unwrap_or(u8::MAX);
+        let unorm = r8g8b8a8_Unorm { r, g, b, a };
+        let sfloat = r32g32b32a32_Sfloat::from(unorm);
+        let gamma_corrected = r32g32b32a32_Sfloat {
+          r: sfloat.r.powf(gamma_exp),
+          g: sfloat.g.powf(gamma_exp),
+          b: sfloat.b.powf(gamma_exp),
+          a: sfloat.a,
+        };
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        *palette = P::from(pre_multiplied_alpha);
+      },
+    );
   }
+  palette
+}
 
-  let transparent_black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
-  let target_pixel_count: usize =
-    ihdr.width.checked_mul(ihdr.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
-  let mut bitmap: crate::Bitmap<P> = {
-    let mut pixels = Vec::new();
-    pixels.try_reserve(target_pixel_count)?;
-    pixels.resize(target_pixel_count, transparent_black);
-    crate::Bitmap { width: ihdr.width, height: ihdr.height, pixels }
-  };
+/// Unfilters the decompressed image data and passes each pixel to `put` as
+/// `(x, y, pixel)`, with the origin in the top left.
+///
+/// This does all the work of converting the PNG's pixel data into linear,
+/// pre-multiplied color, using the palette, transparency, and gamma info from
+/// the PNG's other chunks. Pixels are visited in the same order as
+/// [IHDR::unfilter_decompressed_data] visits them.
+#[cfg(feature = "miniz_oxide")]
+fn png_unfilter_for_each<P>(
+  bytes: &[u8], ihdr: &IHDR, zlib_buffer: &mut [u8], mut put: impl FnMut(u32, u32, P),
+) where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  use bitfrob::{U8_SCALE_1_TO_8, U8_SCALE_2_TO_8, U8_SCALE_4_TO_8};
+  use pixel_formats::r8g8b8a8_Srgb;
 
-  let mut zlib_buffer: Vec<u8> = {
-    let zlib_len = ihdr.get_zlib_decompression_requirement();
-    let mut zlib_buffer: Vec<u8> = Vec::new();
-    zlib_buffer.try_reserve(zlib_len)?;
-    zlib_buffer.resize(zlib_len, 0);
-    zlib_buffer
-  };
-  let _who_cares = miniz_oxide::inflate::decompress_slice_iter_to_slice(
-    &mut zlib_buffer,
-    png_get_idat(bytes),
-    true,
-    true,
-  );
+  let transparent_black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
 
   let is_srgb = png_get_srgb(bytes).is_some();
 
-  let gamma = png_get_gamma(bytes).unwrap_or(100_000_u32) as f32 / 100_000.0_f32;
-  let gamma_exp = 1.0 / gamma;
+  let gamma_exp = png_gamma_exponent(bytes);
 
   let trns: Option<tRNS<'_>> = png_get_transparency(bytes);
   let trns_y = trns.and_then(|trns| trns.try_to_grayscale());
   let trns_rgb = trns.and_then(|trns| trns.try_to_rgb());
 
-  let mut palette: [P; 256] = [r32g32b32a32_Sfloat::TRANSPARENT_BLACK.into(); 256];
-  if ihdr.color_type == PngColorType::Index {
-    if is_srgb {
-      let plte: &[r8g8b8_Srgb] = cast_slice(png_get_palette(bytes).unwrap_or(&[]));
-      let trns: &[u8] = trns.map(|trns| trns.to_alphas()).unwrap_or(&[]);
-      palette.iter_mut().zip(plte.iter().copied()).enumerate().for_each(
-        |(i, (palette, r8g8b8_Srgb { r, g, b }))| {
-          let a: u8 = trns.get(i).copied().unwrap_or(u8::MAX);
-          let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a });
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *palette = P::from(pre_multiplied_alpha);
-        },
-      );
-    } else {
-      let plte: &[r8g8b8_Unorm] = cast_slice(png_get_palette(bytes).unwrap_or(&[]));
-      let trns: &[u8] = trns.map(|trns| trns.to_alphas()).unwrap_or(&[]);
-      palette.iter_mut().zip(plte.iter().copied()).enumerate().for_each(
-        |(i, (palette, r8g8b8_Unorm { r, g, b }))| {
-          let a: u8 = trns.get(i).copied().unwrap_or(u8::MAX);
-          let unorm = r8g8b8a8_Unorm { r, g, b, a };
-          let sfloat = r32g32b32a32_Sfloat::from(unorm);
+  let palette: [P; 256] = if ihdr.color_type == PngColorType::Index {
+    png_resolve_palette(bytes)
+  } else {
+    [transparent_black; 256]
+  };
+
+  match ihdr.color_type {
+    PngColorType::Index => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let p: P = palette[usize::from(data[0])];
+        put(px, py, p);
+      };
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+    }
+    PngColorType::Y if ihdr.bit_depth == 16 => {
+      // depth 16 needs separate handling from 8 or less.
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let y = u16::from_be_bytes([data[0], data[1]]);
+        let p: P = if Some(y) == trns_y {
+          transparent_black
+        } else {
+          let y = (y as f32) / (u16::MAX as f32);
+          let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a: 1.0 };
           let gamma_corrected = r32g32b32a32_Sfloat {
             r: sfloat.r.powf(gamma_exp),
             g: sfloat.g.powf(gamma_exp),
@@ -284,48 +664,11 @@ where
             b: gamma_corrected.b * gamma_corrected.a,
             a: gamma_corrected.a,
           };
-          *palette = P::from(pre_multiplied_alpha);
-        },
-      );
-    }
-  };
-
-  match ihdr.color_type {
-    PngColorType::Index => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          *p = palette[usize::from(data[0])];
-        }
-      };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
-    }
-    PngColorType::Y if ihdr.bit_depth == 16 => {
-      // depth 16 needs separate handling from 8 or less.
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let y = u16::from_be_bytes([data[0], data[1]]);
-          *p = if Some(y) == trns_y {
-            transparent_black
-          } else {
-            let y = (y as f32) / (u16::MAX as f32);
-            let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a: 1.0 };
-            let gamma_corrected = r32g32b32a32_Sfloat {
-              r: sfloat.r.powf(gamma_exp),
-              g: sfloat.g.powf(gamma_exp),
-              b: sfloat.b.powf(gamma_exp),
-              a: sfloat.a,
-            };
-            let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-              r: gamma_corrected.r * gamma_corrected.a,
-              g: gamma_corrected.g * gamma_corrected.a,
-              b: gamma_corrected.b * gamma_corrected.a,
-              a: gamma_corrected.a,
-            };
-            P::from(pre_multiplied_alpha)
-          };
-        }
+          P::from(pre_multiplied_alpha)
+        };
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
     PngColorType::Y if is_srgb => {
       let mult = match ihdr.bit_depth {
@@ -334,17 +677,16 @@ where
         4 => U8_SCALE_4_TO_8,
         _ => 1,
       };
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          *p = if Some(u16::from(data[0])) == trns_y {
-            transparent_black
-          } else {
-            let y = data[0] * mult;
-            P::from(r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r: y, g: y, b: y, a: u8::MAX }))
-          };
-        }
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let p: P = if Some(u16::from(data[0])) == trns_y {
+          transparent_black
+        } else {
+          let y = data[0] * mult;
+          P::from(r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r: y, g: y, b: y, a: u8::MAX }))
+        };
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
     PngColorType::Y => {
       let mult = match ihdr.bit_depth {
@@ -353,233 +695,1238 @@ where
         4 => U8_SCALE_4_TO_8,
         _ => 1,
       };
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          *p = if Some(u16::from(data[0])) == trns_y {
-            transparent_black
-          } else {
-            let y = data[0] * mult;
-            let y = (y as f32) / (u8::MAX as f32);
-            let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a: 1.0 };
-            let gamma_corrected = r32g32b32a32_Sfloat {
-              r: sfloat.r.powf(gamma_exp),
-              g: sfloat.g.powf(gamma_exp),
-              b: sfloat.b.powf(gamma_exp),
-              a: sfloat.a,
-            };
-            // no alpha multiply, alpha is known 1.0
-            P::from(gamma_corrected)
-          };
-        }
-      };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
-    }
-    PngColorType::YA if ihdr.bit_depth == 16 => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let y = (u16::from_be_bytes([data[0], data[1]]) as f32) / (u16::MAX as f32);
-          let a = (u16::from_be_bytes([data[2], data[3]]) as f32) / (u16::MAX as f32);
-          let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a };
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let p: P = if Some(u16::from(data[0])) == trns_y {
+          transparent_black
+        } else {
+          let y = data[0] * mult;
+          let y = (y as f32) / (u8::MAX as f32);
+          let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a: 1.0 };
           let gamma_corrected = r32g32b32a32_Sfloat {
             r: sfloat.r.powf(gamma_exp),
             g: sfloat.g.powf(gamma_exp),
             b: sfloat.b.powf(gamma_exp),
             a: sfloat.a,
           };
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *p = P::from(pre_multiplied_alpha);
-        }
+          // no alpha multiply, alpha is known 1.0
+          P::from(gamma_corrected)
+        };
+        put(px, py, p);
+      };
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+    }
+    PngColorType::YA if ihdr.bit_depth == 16 => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let y = (u16::from_be_bytes([data[0], data[1]]) as f32) / (u16::MAX as f32);
+        let a = (u16::from_be_bytes([data[2], data[3]]) as f32) / (u16::MAX as f32);
+        let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a };
+        let gamma_corrected = r32g32b32a32_Sfloat {
+          r: sfloat.r.powf(gamma_exp),
+          g: sfloat.g.powf(gamma_exp),
+          b: sfloat.b.powf(gamma_exp),
+          a: sfloat.a,
+        };
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        let p: P = P::from(pre_multiplied_alpha);
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
     PngColorType::YA if is_srgb => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let y = data[0];
-          let a = data[1];
-          let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r: y, g: y, b: y, a });
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *p = P::from(pre_multiplied_alpha);
-        }
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let y = data[0];
+        let a = data[1];
+        let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r: y, g: y, b: y, a });
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        let p: P = P::from(pre_multiplied_alpha);
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
     PngColorType::YA => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let y = (data[0] as f32) / (u8::MAX as f32);
-          let a = (data[1] as f32) / (u8::MAX as f32);
-          let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a };
-          let gamma_corrected = r32g32b32a32_Sfloat {
-            r: sfloat.r.powf(gamma_exp),
-            g: sfloat.g.powf(gamma_exp),
-            b: sfloat.b.powf(gamma_exp),
-            a: sfloat.a,
-          };
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *p = P::from(pre_multiplied_alpha);
-        }
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let y = (data[0] as f32) / (u8::MAX as f32);
+        let a = (data[1] as f32) / (u8::MAX as f32);
+        let sfloat = r32g32b32a32_Sfloat { r: y, g: y, b: y, a };
+        let gamma_corrected = r32g32b32a32_Sfloat {
+          r: sfloat.r.powf(gamma_exp),
+          g: sfloat.g.powf(gamma_exp),
+          b: sfloat.b.powf(gamma_exp),
+          a: sfloat.a,
+        };
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        let p: P = P::from(pre_multiplied_alpha);
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
     PngColorType::RGB if ihdr.bit_depth == 16 => {
       // depth 16 needs separate handling from 8 or less.
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let r = u16::from_be_bytes([data[0], data[1]]);
-          let g = u16::from_be_bytes([data[2], data[3]]);
-          let b = u16::from_be_bytes([data[4], data[5]]);
-          *p = if Some([r, g, b]) == trns_rgb {
-            transparent_black
-          } else {
-            let r = (u16::from_be_bytes([data[0], data[1]]) as f32) / (u16::MAX as f32);
-            let g = (u16::from_be_bytes([data[2], data[3]]) as f32) / (u16::MAX as f32);
-            let b = (u16::from_be_bytes([data[4], data[5]]) as f32) / (u16::MAX as f32);
-            let sfloat = r32g32b32a32_Sfloat { r, g, b, a: 1.0 };
-            let gamma_corrected = r32g32b32a32_Sfloat {
-              r: sfloat.r.powf(gamma_exp),
-              g: sfloat.g.powf(gamma_exp),
-              b: sfloat.b.powf(gamma_exp),
-              a: sfloat.a,
-            };
-            // no alpha multiply, alpha is known 1.0
-            P::from(gamma_corrected)
-          };
-        }
-      };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
-    }
-    PngColorType::RGB if is_srgb => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let r = data[0];
-          let g = data[1];
-          let b = data[2];
-          *p = if Some([u16::from(r), u16::from(g), u16::from(b)]) == trns_rgb {
-            transparent_black
-          } else {
-            let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a: u8::MAX });
-            // no alpha multiply, alpha is known 1.0
-            P::from(gamma_corrected)
-          };
-        }
-      };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
-    }
-    PngColorType::RGB => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let r = data[0];
-          let g = data[1];
-          let b = data[2];
-          *p = if Some([u16::from(r), u16::from(g), u16::from(b)]) == trns_rgb {
-            transparent_black
-          } else {
-            let r = (data[0] as f32) / (u8::MAX as f32);
-            let g = (data[1] as f32) / (u8::MAX as f32);
-            let b = (data[2] as f32) / (u8::MAX as f32);
-            let sfloat = r32g32b32a32_Sfloat { r, g, b, a: 1.0 };
-            let gamma_corrected = r32g32b32a32_Sfloat {
-              r: sfloat.r.powf(gamma_exp),
-              g: sfloat.g.powf(gamma_exp),
-              b: sfloat.b.powf(gamma_exp),
-              a: sfloat.a,
-            };
-            // no alpha multiply, alpha is known 1.0
-            P::from(gamma_corrected)
-          };
-        }
-      };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
-    }
-    PngColorType::RGBA if ihdr.bit_depth == 16 => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let r = u16::from_be_bytes([data[0], data[1]]);
+        let g = u16::from_be_bytes([data[2], data[3]]);
+        let b = u16::from_be_bytes([data[4], data[5]]);
+        let p: P = if Some([r, g, b]) == trns_rgb {
+          transparent_black
+        } else {
           let r = (u16::from_be_bytes([data[0], data[1]]) as f32) / (u16::MAX as f32);
           let g = (u16::from_be_bytes([data[2], data[3]]) as f32) / (u16::MAX as f32);
           let b = (u16::from_be_bytes([data[4], data[5]]) as f32) / (u16::MAX as f32);
-          let a = (u16::from_be_bytes([data[6], data[7]]) as f32) / (u16::MAX as f32);
-          let sfloat = r32g32b32a32_Sfloat { r, g, b, a };
+          let sfloat = r32g32b32a32_Sfloat { r, g, b, a: 1.0 };
           let gamma_corrected = r32g32b32a32_Sfloat {
             r: sfloat.r.powf(gamma_exp),
             g: sfloat.g.powf(gamma_exp),
             b: sfloat.b.powf(gamma_exp),
             a: sfloat.a,
           };
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *p = P::from(pre_multiplied_alpha);
-        }
+          // no alpha multiply, alpha is known 1.0
+          P::from(gamma_corrected)
+        };
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
-    PngColorType::RGBA if is_srgb => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
-          let r = data[0];
-          let g = data[1];
-          let b = data[2];
-          let a = data[3];
-          let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a });
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *p = P::from(pre_multiplied_alpha);
-        }
+    PngColorType::RGB if is_srgb => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let r = data[0];
+        let g = data[1];
+        let b = data[2];
+        let p: P = if Some([u16::from(r), u16::from(g), u16::from(b)]) == trns_rgb {
+          transparent_black
+        } else {
+          let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a: u8::MAX });
+          // no alpha multiply, alpha is known 1.0
+          P::from(gamma_corrected)
+        };
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
     }
-    PngColorType::RGBA => {
-      let unfilter_op = |x: u32, y: u32, data: &[u8]| {
-        if let Some(p) = bitmap.get_mut(x, y) {
+    PngColorType::RGB => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let r = data[0];
+        let g = data[1];
+        let b = data[2];
+        let p: P = if Some([u16::from(r), u16::from(g), u16::from(b)]) == trns_rgb {
+          transparent_black
+        } else {
           let r = (data[0] as f32) / (u8::MAX as f32);
           let g = (data[1] as f32) / (u8::MAX as f32);
           let b = (data[2] as f32) / (u8::MAX as f32);
-          let a = (data[3] as f32) / (u8::MAX as f32);
-          let sfloat = r32g32b32a32_Sfloat { r, g, b, a };
+          let sfloat = r32g32b32a32_Sfloat { r, g, b, a: 1.0 };
           let gamma_corrected = r32g32b32a32_Sfloat {
             r: sfloat.r.powf(gamma_exp),
             g: sfloat.g.powf(gamma_exp),
             b: sfloat.b.powf(gamma_exp),
             a: sfloat.a,
           };
-          let pre_multiplied_alpha = r32g32b32a32_Sfloat {
-            r: gamma_corrected.r * gamma_corrected.a,
-            g: gamma_corrected.g * gamma_corrected.a,
-            b: gamma_corrected.b * gamma_corrected.a,
-            a: gamma_corrected.a,
-          };
-          *p = P::from(pre_multiplied_alpha);
-        }
+          // no alpha multiply, alpha is known 1.0
+          P::from(gamma_corrected)
+        };
+        put(px, py, p);
+      };
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+    }
+    PngColorType::RGBA if ihdr.bit_depth == 16 => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let r = (u16::from_be_bytes([data[0], data[1]]) as f32) / (u16::MAX as f32);
+        let g = (u16::from_be_bytes([data[2], data[3]]) as f32) / (u16::MAX as f32);
+        let b = (u16::from_be_bytes([data[4], data[5]]) as f32) / (u16::MAX as f32);
+        let a = (u16::from_be_bytes([data[6], data[7]]) as f32) / (u16::MAX as f32);
+        let sfloat = r32g32b32a32_Sfloat { r, g, b, a };
+        let gamma_corrected = r32g32b32a32_Sfloat {
+          r: sfloat.r.powf(gamma_exp),
+          g: sfloat.g.powf(gamma_exp),
+          b: sfloat.b.powf(gamma_exp),
+          a: sfloat.a,
+        };
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        let p: P = P::from(pre_multiplied_alpha);
+        put(px, py, p);
+      };
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+    }
+    PngColorType::RGBA if is_srgb => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let r = data[0];
+        let g = data[1];
+        let b = data[2];
+        let a = data[3];
+        let gamma_corrected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r, g, b, a });
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        let p: P = P::from(pre_multiplied_alpha);
+        put(px, py, p);
       };
-      ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+    }
+    PngColorType::RGBA => {
+      let unfilter_op = |px: u32, py: u32, data: &[u8]| {
+        let r = (data[0] as f32) / (u8::MAX as f32);
+        let g = (data[1] as f32) / (u8::MAX as f32);
+        let b = (data[2] as f32) / (u8::MAX as f32);
+        let a = (data[3] as f32) / (u8::MAX as f32);
+        let sfloat = r32g32b32a32_Sfloat { r, g, b, a };
+        let gamma_corrected = r32g32b32a32_Sfloat {
+          r: sfloat.r.powf(gamma_exp),
+          g: sfloat.g.powf(gamma_exp),
+          b: sfloat.b.powf(gamma_exp),
+          a: sfloat.a,
+        };
+        let pre_multiplied_alpha = r32g32b32a32_Sfloat {
+          r: gamma_corrected.r * gamma_corrected.a,
+          g: gamma_corrected.g * gamma_corrected.a,
+          b: gamma_corrected.b * gamma_corrected.a,
+          a: gamma_corrected.a,
+        };
+        let p: P = P::from(pre_multiplied_alpha);
+        put(px, py, p);
+      };
+      ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+    }
+  }
+}
+
+/// Decodes just the top rows of a PNG, such as for a quick preview.
+///
+/// The output bitmap always has the full size of the image, but only the
+/// first `max_rows` rows are decoded. The rest of the image is left as
+/// transparent black. You also get the number of rows that were decoded,
+/// which is `max_rows` or the image's height, whichever is less.
+///
+/// For non-interlaced images only the compressed data needed for those rows is
+/// decompressed, so this is much faster than a full decode of a large image.
+/// Interlaced images spread every row across the whole file, so they're fully
+/// decoded and then the extra rows are cleared.
+///
+/// The output is oriented with the origin in the top left if
+/// `origin_top_left` is set. Otherwise it's flipped, so the decoded rows are
+/// at the end of the pixel data.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba_partial<P>(
+  bytes: &[u8], origin_top_left: bool, max_rows: u32,
+) -> Result<(crate::Bitmap<P>, u32), ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  use alloc::vec::Vec;
+
+  let transparent_black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
+  let ihdr = png_get_decode_header(bytes)?;
+  let rows = max_rows.min(ihdr.height);
+  if ihdr.is_interlaced || png_signature_is_mng_or_jng(bytes) {
+    let mut bitmap: crate::Bitmap<P> = png_try_bitmap_rgba(bytes, true)?;
+    let decoded_len = (rows as usize) * (bitmap.width as usize);
+    bitmap.pixels[decoded_len..].fill(transparent_black);
+    if !origin_top_left {
+      bitmap.vertical_flip();
+    }
+    return Ok((bitmap, rows));
+  }
+
+  let (width, height, pixels) = png_alloc_decode_pixels(&ihdr, transparent_black)?;
+  let mut bitmap = crate::Bitmap { width, height, pixels };
+  if rows > 0 {
+    // A header for just the top rows makes both the decompression and the
+    // unfiltering stop at the end of those rows.
+    let top_ihdr = IHDR { height: rows, ..ihdr };
+    let mut zlib_buffer: Vec<u8> = png_decompress_idat_to_vec(bytes, &top_ihdr)?;
+    png_unfilter_to_pixels(
+      bytes,
+      &top_ihdr,
+      &mut zlib_buffer,
+      &mut crate::BorrowedBitmap { width, height, pixels: &mut bitmap.pixels },
+    );
+  }
+  if !origin_top_left {
+    bitmap.vertical_flip();
+  }
+  Ok((bitmap, rows))
+}
+
+/// Information about how a PNG's image data is stored.
+///
+/// This is for finding out why an image doesn't look right, or for seeing what
+/// an encoder did. See [png_try_bitmap_rgba_diagnostic].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PngDiagnostics {
+  /// How many lines use each filter type, in the order None, Sub, Up,
+  /// Average, Paeth.
+  ///
+  /// With an interlaced image the lines of every reduced image are counted.
+  pub filter_counts: [u32; 5],
+  /// How many lines have a filter type that isn't valid.
+  pub bad_filter_count: u32,
+  /// The total length of all the `IDAT` chunks.
+  pub idat_len: usize,
+  /// How many bytes the image data should decompress to, based on the header.
+  pub expected_data_len: usize,
+  /// How many bytes the image data actually decompressed to.
+  ///
+  /// If there's far more data than expected the decompression stops early,
+  /// so then this is only a lower bound.
+  pub data_len: usize,
+  /// If the `IDAT` chunks have more bytes after the end of the zlib stream.
+  pub zlib_trailing_bytes: bool,
+  /// If the zlib stream is cut off or broken, including when its checksum
+  /// doesn't match.
+  pub zlib_error: bool,
+  /// If any chunk's CRC doesn't match the chunk.
+  pub crc_mismatch: bool,
+}
+
+/// Like [png_try_bitmap_rgba], but also gives [PngDiagnostics] about the
+/// file.
+///
+/// The image decodes the same as with [png_try_bitmap_rgba]. Problems that
+/// the decoder normally lets slide (such as a bad CRC, or a zlib stream that's
+/// too short) are reported in the diagnostics instead of making the decode
+/// fail.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba_diagnostic<P>(
+  bytes: &[u8], origin_top_left: bool,
+) -> Result<(crate::Bitmap<P>, PngDiagnostics), ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  use alloc::vec::Vec;
+  use miniz_oxide::inflate::{
+    core::{decompress, inflate_flags::*, DecompressorOxide},
+    TINFLStatus,
+  };
+
+  if png_signature_is_mng_or_jng(bytes) {
+    return Err(ImagineError::Unsupported(crate::UnsupportedFormat::MngOrJng));
+  }
+  let ihdr = png_get_decode_header(bytes)?;
+  let (width, height, pixels) =
+    png_alloc_decode_pixels(&ihdr, P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK))?;
+  let mut bitmap = crate::Bitmap { width, height, pixels };
+  let mut diagnostics = PngDiagnostics {
+    crc_mismatch: PngRawChunkIter::new(bytes)
+      .any(|raw_chunk| png_chunk_crc(raw_chunk.type_, raw_chunk.data) != raw_chunk.declared_crc),
+    expected_data_len: ihdr.get_zlib_decompression_requirement(),
+    ..PngDiagnostics::default()
+  };
+
+  let mut idat: Vec<u8> = Vec::new();
+  for slice in png_get_idat(bytes) {
+    idat.try_reserve(slice.len())?;
+    idat.extend_from_slice(slice);
+  }
+  diagnostics.idat_len = idat.len();
+
+  // Inflate into a buffer that can grow past the expected size, so that we
+  // can tell how much data there really is, up to a limit.
+  let limit = diagnostics.expected_data_len.saturating_mul(2).max(1 << 16);
+  let mut data: Vec<u8> = Vec::new();
+  data.try_reserve(diagnostics.expected_data_len)?;
+  data.resize(diagnostics.expected_data_len, 0);
+  let flags = TINFL_FLAG_PARSE_ZLIB_HEADER
+    | TINFL_FLAG_COMPUTE_ADLER32
+    | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+  let mut decompressor = DecompressorOxide::new();
+  let (mut in_pos, mut out_pos) = (0, 0);
+  let status = loop {
+    let (status, in_used, out_used) =
+      decompress(&mut decompressor, &idat[in_pos..], &mut data, out_pos, flags);
+    in_pos += in_used;
+    out_pos += out_used;
+    match status {
+      TINFLStatus::HasMoreOutput if data.len() < limit => {
+        let new_len = data.len().saturating_mul(2).clamp(1, limit);
+        data.try_reserve(new_len - data.len())?;
+        data.resize(new_len, 0);
+      }
+      status => break status,
+    }
+  };
+  diagnostics.data_len = out_pos;
+  diagnostics.zlib_trailing_bytes = status == TINFLStatus::Done && in_pos < idat.len();
+  diagnostics.zlib_error = !matches!(status, TINFLStatus::Done | TINFLStatus::HasMoreOutput);
+  data.truncate(out_pos);
+  data.resize(diagnostics.expected_data_len, 0);
+
+  // each filter line starts with its filter type.
+  let mut rest: &[u8] = &data;
+  for (width, height) in reduced_image_dimensions(ihdr.width, ihdr.height)
+    .into_iter()
+    .skip(usize::from(ihdr.is_interlaced))
+    .take(if ihdr.is_interlaced { 7 } else { 1 })
+  {
+    if width == 0 || height == 0 {
+      continue;
+    }
+    let bytes_per_filterline = ihdr.bytes_per_filterline(width);
+    let (these_bytes, more_bytes) =
+      rest.split_at(bytes_per_filterline.saturating_mul(height as usize).min(rest.len()));
+    rest = more_bytes;
+    for line in these_bytes.chunks_exact(bytes_per_filterline) {
+      match diagnostics.filter_counts.get_mut(usize::from(line[0])) {
+        Some(count) => *count += 1,
+        None => diagnostics.bad_filter_count += 1,
+      }
+    }
+  }
+
+  png_unfilter_to_pixels(
+    bytes,
+    &ihdr,
+    &mut data,
+    &mut crate::BorrowedBitmap { width, height, pixels: &mut bitmap.pixels },
+  );
+  if !origin_top_left {
+    bitmap.vertical_flip();
+  }
+  Ok((bitmap, diagnostics))
+}
+
+/// Decodes a greyscale PNG without expanding the pixels.
+///
+/// The output is `(scanlines, width, height, bit_depth)`, where `scanlines` is
+/// the unfiltered greyscale data with the origin in the top left. Each scanline
+/// is `(width * bit_depth + 7) / 8` bytes, with pixels packed from the high
+/// bits to the low bits of each byte (the same as PNG itself). 16-bit data is
+/// stored big-endian. Interlaced images are de-interlaced.
+///
+/// This is intended for 1, 2, and 4 bit documents (eg: scanned pages), where
+/// expanding every pixel into a full color value would take many times the
+/// memory. Any transparency or gamma info is ignored.
+///
+/// ## Failure
+/// * Images that aren't [PngColorType::Y] give a parse error.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_gray_packed(
+  bytes: &[u8],
+) -> Result<(alloc::vec::Vec<u8>, u32, u32, u8), ImagineError> {
+  use alloc::vec::Vec;
+
+  let ihdr = png_get_header(bytes).ok_or(ImagineError::Parse)?;
+  if ihdr.color_type != PngColorType::Y {
+    return Err(ImagineError::Parse);
+  }
+  if ihdr.width > 17_000 || ihdr.height > 17_000 {
+    return Err(ImagineError::DimensionsTooLarge);
+  }
+
+  let bit_depth = ihdr.bit_depth;
+  // every line has a filter byte that we don't keep in the output.
+  let bytes_per_line = ihdr.bytes_per_filterline(ihdr.width) - 1;
+  let total_bytes =
+    bytes_per_line.checked_mul(ihdr.height.try_into()?).ok_or(ImagineError::CheckedMath)?;
+  let mut packed: Vec<u8> = Vec::new();
+  packed.try_reserve(total_bytes)?;
+  packed.resize(total_bytes, 0);
+
+  let mut zlib_buffer: Vec<u8> = png_decompress_idat_to_vec(bytes, &ihdr)?;
+  let unfilter_op = |x: u32, y: u32, data: &[u8]| {
+    let line_start = (y as usize) * bytes_per_line;
+    match bit_depth {
+      1 | 2 | 4 => {
+        let bit_pos = (x as usize) * usize::from(bit_depth);
+        let shift = 8 - usize::from(bit_depth) - (bit_pos % 8);
+        if let Some(b) = packed.get_mut(line_start + bit_pos / 8) {
+          *b |= data[0] << shift;
+        }
+      }
+      _ => {
+        let start = line_start + (x as usize) * data.len();
+        if let Some(dest) = packed.get_mut(start..(start + data.len())) {
+          dest.copy_from_slice(data);
+        }
+      }
+    }
+  };
+  ihdr.unfilter_decompressed_data(&mut zlib_buffer, unfilter_op).ok();
+
+  Ok((packed, ihdr.width, ihdr.height, bit_depth))
+}
+
+/// Options that control how strictly a PNG is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PngDecodeOptions {
+  /// If ancillary chunks that fail to parse should be ignored.
+  ///
+  /// When this is `true` (the default), a broken ancillary chunk is treated
+  /// as if it wasn't there at all, which is what you want when displaying
+  /// images. When this is `false`, any broken ancillary chunk that the decoder
+  /// knows about (`tRNS`, `bKGD`, `sRGB`, `gAMA`) makes the decode fail with a
+  /// parse error, which is what you want when validating files.
+  pub ignore_ancillary_errors: bool,
+
+  /// The most scratch memory the decoder will use to decompress the image.
+  ///
+  /// The scratch space needed is known from the header alone (see
+  /// [IHDR::get_zlib_decompression_requirement]), so an image that needs more
+  /// than this is refused with [ImagineError::DimensionsTooLarge] before any
+  /// allocation is attempted. The default is `usize::MAX`, which leaves only
+  /// the usual 17,000 pixel width and height limit. If you're decoding
+  /// untrusted images you should set this lower.
+  pub max_scratch_bytes: usize,
+
+  /// If the decode should fail when the `IDAT` chunks aren't all next to each
+  /// other.
+  ///
+  /// The PNG spec requires one unbroken run of `IDAT` chunks, but the image
+  /// data can be put back together just fine either way. By default (`false`)
+  /// other chunks between the `IDAT` chunks are allowed. When this is `true`
+  /// such a file makes the decode fail with a parse error (see
+  /// [png_idat_is_contiguous]).
+  pub require_contiguous_idat: bool,
+}
+impl Default for PngDecodeOptions {
+  #[inline]
+  fn default() -> Self {
+    Self {
+      ignore_ancillary_errors: true,
+      max_scratch_bytes: usize::MAX,
+      require_contiguous_idat: false,
+    }
+  }
+}
+
+/// Checks that every known ancillary chunk parses and fits the image header.
+///
+/// * `tRNS` must have the right length for the color type, and can't be used
+///   with color types that already have alpha.
+/// * `bKGD` must have the right form for the color type.
+/// * `sRGB` and `gAMA` must parse, and `gAMA` can't be 0.
+///
+/// Unknown chunks are skipped. Chunk order isn't checked: the palette is found
+/// wherever it is in the file, so a `tRNS` that's (incorrectly) placed before
+/// the `PLTE` is still accepted, the same as the decoders accept it.
+#[inline]
+pub fn png_check_ancillary_chunks(bytes: &[u8]) -> Result<(), ImagineError> {
+  let ihdr = png_get_header(bytes).ok_or(ImagineError::Parse)?;
+  let palette_len = png_get_palette(bytes).map(|p| p.len()).unwrap_or(0);
+  for raw_chunk in PngRawChunkIter::new(bytes) {
+    let is_ancillary = matches!(
+      raw_chunk.type_,
+      PngRawChunkType::tRNS | PngRawChunkType::bKGD | PngRawChunkType::sRGB | PngRawChunkType::gAMA
+    );
+    if !is_ancillary {
+      continue;
     }
+    let ok = match PngChunk::try_from(raw_chunk) {
+      Ok(PngChunk::tRNS(trns)) => match ihdr.color_type {
+        PngColorType::Y => trns.try_to_grayscale().is_some(),
+        PngColorType::RGB => trns.try_to_rgb().is_some(),
+        PngColorType::Index => trns.to_alphas().len() <= palette_len,
+        PngColorType::YA | PngColorType::RGBA => false,
+      },
+      Ok(PngChunk::bKGD(bkgd)) => match (ihdr.color_type, bkgd) {
+        (PngColorType::Y | PngColorType::YA, bKGD::Greyscale { .. }) => true,
+        (PngColorType::RGB | PngColorType::RGBA, bKGD::RGB { .. }) => true,
+        (PngColorType::Index, bKGD::Index { i }) => usize::from(i) < palette_len,
+        _ => false,
+      },
+      Ok(PngChunk::gAMA(g)) => g != 0,
+      Ok(_) => true,
+      Err(_) => false,
+    };
+    if !ok {
+      return Err(ImagineError::Parse);
+    }
+  }
+  Ok(())
+}
+
+/// Automatically allocate and fill in a [Bitmap](crate::Bitmap).
+///
+/// The output is automatically flipped as necessary so that the output will be
+/// oriented with the origin in the top left.
+///
+/// This uses the default [PngDecodeOptions].
+///
+/// Every sample is converted to `f32` before going into `P`, so any `P` that
+/// implements `From<r32g32b32a32_Sfloat>` works as the output. With a pixel
+/// type that has more than 8 bits per channel, 16-bit images keep their full
+/// precision.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba<P>(
+  bytes: &[u8], origin_top_left: bool,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  png_try_bitmap_rgba_with_options(bytes, origin_top_left, PngDecodeOptions::default())
+}
+
+/// Like [png_try_bitmap_rgba], but never flips the image.
+///
+/// PNG always stores the image with the origin in the top left, so the
+/// [Orientation](crate::Orientation) you get back is always `TopLeft`.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba_oriented<P>(
+  bytes: &[u8],
+) -> Result<(crate::Bitmap<P>, crate::Orientation), ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  png_try_bitmap_rgba(bytes, true).map(|bitmap| (bitmap, crate::Orientation::TopLeft))
+}
+
+/// Like [png_try_bitmap_rgba], but with explicit [PngDecodeOptions].
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba_with_options<P>(
+  bytes: &[u8], origin_top_left: bool, options: PngDecodeOptions,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  #[allow(unused)]
+  use alloc::vec::Vec;
+
+  if png_signature_is_mng_or_jng(bytes) {
+    return Err(ImagineError::Unsupported(crate::UnsupportedFormat::MngOrJng));
+  }
+  let ihdr = png_get_decode_header(bytes)?;
+  if !options.ignore_ancillary_errors {
+    png_check_ancillary_chunks(bytes)?;
+  }
+  if options.require_contiguous_idat && !png_idat_is_contiguous(bytes) {
+    return Err(ImagineError::Parse);
+  }
+  if ihdr.get_zlib_decompression_requirement() > options.max_scratch_bytes {
+    return Err(ImagineError::DimensionsTooLarge);
+  }
+
+  let (width, height, pixels) =
+    png_alloc_decode_pixels(&ihdr, P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK))?;
+  let mut bitmap = crate::Bitmap { width, height, pixels };
+
+  let mut zlib_buffer: Vec<u8> = png_decompress_idat_to_vec(bytes, &ihdr)?;
+
+  png_unfilter_to_pixels(
+    bytes,
+    &ihdr,
+    &mut zlib_buffer,
+    &mut crate::BorrowedBitmap { width, height, pixels: &mut bitmap.pixels },
+  );
+
+  if !origin_top_left {
+    bitmap.vertical_flip();
   }
+  Ok(bitmap)
+}
+
+/// Automatically allocate and fill in an opaque [Bitmap](crate::Bitmap).
+///
+/// The image is decoded just like with [png_try_bitmap_rgba], and then every
+/// pixel is composited over the `background` color you give. This gives a
+/// defined color to any pixel that's not fully opaque, including pixels that
+/// match a `tRNS` transparency key (for 8-bit and 16-bit grayscale as well as
+/// RGB images), which are fully transparent and so become the background.
+///
+/// The `background` should be linear, the same as the decoded pixels.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgb<P>(
+  bytes: &[u8], origin_top_left: bool, background: r32g32b32_Sfloat,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32_Sfloat>,
+{
+  use alloc::vec::Vec;
+
+  let rgba: crate::Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(bytes, origin_top_left)?;
+  let mut pixels: Vec<P> = Vec::new();
+  pixels.try_reserve(rgba.pixels.len())?;
+  // the decoded pixels are premultiplied, so "over" is just `c + bg * (1-a)`.
+  pixels.extend(rgba.pixels.iter().map(|p| {
+    let inv_a = 1.0 - p.a;
+    P::from(r32g32b32_Sfloat {
+      r: p.r + background.r * inv_a,
+      g: p.g + background.g * inv_a,
+      b: p.b + background.b * inv_a,
+    })
+  }));
+  Ok(crate::Bitmap { width: rgba.width, height: rgba.height, pixels })
+}
+
+/// Like [png_try_bitmap_rgba], but using a [ColorTransform](crate::ColorTransform)
+/// when the PNG has an embedded ICC profile.
+///
+/// * If there's no `iCCP` chunk this is the same as [png_try_bitmap_rgba].
+/// * If there is one, the built-in `sRGB` and `gAMA` handling is skipped, and
+///   every pixel's stored values are passed through `transform` instead. The
+///   palette and `tRNS` chunks are still used to get those stored values.
+///
+/// Use [png_get_icc_profile] to get the profile for building the transform.
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba_with_transform<P, T>(
+  bytes: &[u8], origin_top_left: bool, transform: &T,
+) -> Result<crate::Bitmap<P>, ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+  T: crate::ColorTransform + ?Sized,
+{
+  use alloc::vec::Vec;
+
+  if png_signature_is_mng_or_jng(bytes) {
+    return Err(ImagineError::Unsupported(crate::UnsupportedFormat::MngOrJng));
+  }
+  if png_get_icc_profile(bytes).is_none() {
+    return png_try_bitmap_rgba(bytes, origin_top_left);
+  }
+  let ihdr = png_get_decode_header(bytes)?;
+  let (width, height, pixels) =
+    png_alloc_decode_pixels(&ihdr, P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK))?;
+  let mut bitmap = crate::Bitmap { width, height, pixels };
+  let mut scratch: Vec<u8> = Vec::new();
+  scratch.try_reserve(ihdr.get_zlib_decompression_requirement())?;
+  scratch.resize(ihdr.get_zlib_decompression_requirement(), 0);
+
+  let trns = png_get_transparency(bytes);
+  let trns_y = trns.and_then(|trns| trns.try_to_grayscale());
+  let trns_rgb = trns.and_then(|trns| trns.try_to_rgb());
+  let alphas: &[u8] = trns.map(|trns| trns.to_alphas()).unwrap_or(&[]);
+  let palette: &[[u8; 3]] = png_get_palette(bytes).unwrap_or(&[]);
+  let y_max = ((1_u32 << ihdr.bit_depth) - 1) as f32;
+  let u8f = |u: u8| (u as f32) / (u8::MAX as f32);
+  let u16f = |u: u16| (u as f32) / (u16::MAX as f32);
+  let key_alpha = |is_key: bool| if is_key { 0.0 } else { 1.0 };
+  png_for_each_pixel(bytes, &mut scratch, |x, y, sample| {
+    let rgba: [f32; 4] = match sample {
+      PngSample::Y8(v) => {
+        let y = (v as f32) / y_max;
+        [y, y, y, key_alpha(Some(u16::from(v)) == trns_y)]
+      }
+      PngSample::Y16(v) => {
+        let y = u16f(v);
+        [y, y, y, key_alpha(Some(v) == trns_y)]
+      }
+      PngSample::YA8([y, a]) => [u8f(y), u8f(y), u8f(y), u8f(a)],
+      PngSample::YA16([y, a]) => [u16f(y), u16f(y), u16f(y), u16f(a)],
+      PngSample::Rgb8([r, g, b]) => {
+        let is_key = Some([u16::from(r), u16::from(g), u16::from(b)]) == trns_rgb;
+        [u8f(r), u8f(g), u8f(b), key_alpha(is_key)]
+      }
+      PngSample::Rgb16([r, g, b]) => {
+        [u16f(r), u16f(g), u16f(b), key_alpha(Some([r, g, b]) == trns_rgb)]
+      }
+      PngSample::Rgba8([r, g, b, a]) => [u8f(r), u8f(g), u8f(b), u8f(a)],
+      PngSample::Rgba16([r, g, b, a]) => [u16f(r), u16f(g), u16f(b), u16f(a)],
+      PngSample::Index(i) => match palette.get(usize::from(i)) {
+        Some([r, g, b]) => {
+          let a = alphas.get(usize::from(i)).copied().unwrap_or(u8::MAX);
+          [u8f(*r), u8f(*g), u8f(*b), u8f(a)]
+        }
+        None => return,
+      },
+    };
+    let c = transform.apply(rgba);
+    if let Some(p) = bitmap.get_mut(x, y) {
+      *p = P::from(r32g32b32a32_Sfloat { r: c.r * c.a, g: c.g * c.a, b: c.b * c.a, a: c.a });
+    }
+  })?;
 
   if !origin_top_left {
     bitmap.vertical_flip();
   }
   Ok(bitmap)
 }
+
+/// A single pixel of PNG data, at the precision the image stores it.
+///
+/// Samples are exactly as they appear in the image data, with no palette
+/// lookup, transparency, gamma, or alpha handling. When the bit depth is less
+/// than 8 the value is *not* rescaled, so a 1-bit image gives `Y8(0)` or
+/// `Y8(1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum PngSample {
+  Y8(u8),
+  Y16(u16),
+  YA8([u8; 2]),
+  YA16([u16; 2]),
+  Rgb8([u8; 3]),
+  Rgb16([u16; 3]),
+  Rgba8([u8; 4]),
+  Rgba16([u16; 4]),
+  Index(u8),
+}
+
+/// Decodes a PNG and runs `f` on every pixel, at the image's own precision.
+///
+/// The closure gets `(x, y, sample)` for each pixel, with the origin in the
+/// top left. For interlaced images the pixels aren't visited in order, but
+/// each pixel is visited once. How to convert a [PngSample] into a color is up
+/// to you, so this is the way to keep 16-bit data or indexed data as is.
+///
+/// * `scratch` is used to decompress the image data, and it must be at least
+///   [IHDR::get_zlib_decompression_requirement] bytes.
+///
+/// ## Failure
+/// * If `scratch` is too small you get [ImagineError::BufferTooSmall].
+#[inline]
+#[cfg(feature = "miniz_oxide")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "miniz_oxide")))]
+pub fn png_for_each_pixel<F>(bytes: &[u8], scratch: &mut [u8], mut f: F) -> Result<(), ImagineError>
+where
+  F: FnMut(u32, u32, PngSample),
+{
+  let ihdr = png_get_header(bytes).ok_or(ImagineError::Parse)?;
+  let zlib_buffer = scratch
+    .get_mut(..ihdr.get_zlib_decompression_requirement())
+    .ok_or(ImagineError::BufferTooSmall)?;
+  zlib_buffer.fill(0);
+  let _who_cares = miniz_oxide::inflate::decompress_slice_iter_to_slice(
+    zlib_buffer,
+    png_get_idat(bytes),
+    true,
+    true,
+  );
+
+  let sixteen = ihdr.bit_depth == 16;
+  let u16_at = |data: &[u8], i: usize| u16::from_be_bytes([data[i * 2], data[i * 2 + 1]]);
+  let unfilter_op = |x: u32, y: u32, data: &[u8]| {
+    let sample = match ihdr.color_type {
+      PngColorType::Y if sixteen => PngSample::Y16(u16_at(data, 0)),
+      PngColorType::Y => PngSample::Y8(data[0]),
+      PngColorType::YA if sixteen => PngSample::YA16([u16_at(data, 0), u16_at(data, 1)]),
+      PngColorType::YA => PngSample::YA8([data[0], data[1]]),
+      PngColorType::RGB if sixteen => {
+        PngSample::Rgb16([u16_at(data, 0), u16_at(data, 1), u16_at(data, 2)])
+      }
+      PngColorType::RGB => PngSample::Rgb8([data[0], data[1], data[2]]),
+      PngColorType::RGBA if sixteen => {
+        PngSample::Rgba16([u16_at(data, 0), u16_at(data, 1), u16_at(data, 2), u16_at(data, 3)])
+      }
+      PngColorType::RGBA => PngSample::Rgba8([data[0], data[1], data[2], data[3]]),
+      PngColorType::Index => PngSample::Index(data[0]),
+    };
+    f(x, y, sample)
+  };
+  ihdr.unfilter_decompressed_data(zlib_buffer, unfilter_op).ok();
+  Ok(())
+}
+
+/// Decodes a PNG into a [BorrowedBitmap](crate::BorrowedBitmap), without allocating.
+///
+/// This is the same decoding as [png_try_bitmap_rgba], but the pixels are
+/// written into memory that you already own. The output has the origin in the
+/// top left.
+///
+/// * `scratch` is used to decompress the image data, and it must be at least
+///   [IHDR::get_zlib_decompression_requirement] bytes.
+///
+/// ## Failure
+/// * If the bitmap's width and height don't match the PNG you get
+///   [ImagineError::DimensionsMismatch].
+/// * If the bitmap has fewer than `width * height` pixels, or `scratch` is too
+///   small, you get [ImagineError::BufferTooSmall].
+/// * MNG and JNG files give [ImagineError::Unsupported], the same as with
+///   [png_try_bitmap_rgba].
+#[inline]
+#[cfg(feature = "miniz_oxide")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "miniz_oxide")))]
+pub fn png_decode_into_borrowed<P>(
+  bytes: &[u8], bitmap: &mut crate::BorrowedBitmap<'_, P>, scratch: &mut [u8],
+) -> Result<(), ImagineError>
+where
+  P: Copy + From<r32g32b32a32_Sfloat>,
+{
+  if png_signature_is_mng_or_jng(bytes) {
+    return Err(ImagineError::Unsupported(crate::UnsupportedFormat::MngOrJng));
+  }
+  let ihdr = png_get_decode_header(bytes)?;
+  if ihdr.width != bitmap.width || ihdr.height != bitmap.height {
+    return Err(ImagineError::DimensionsMismatch);
+  }
+  let target_pixel_count: usize =
+    ihdr.width.checked_mul(ihdr.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  let pixels = bitmap.pixels.get_mut(..target_pixel_count).ok_or(ImagineError::BufferTooSmall)?;
+  let zlib_buffer = scratch
+    .get_mut(..ihdr.get_zlib_decompression_requirement())
+    .ok_or(ImagineError::BufferTooSmall)?;
+  zlib_buffer.fill(0);
+  let _who_cares = miniz_oxide::inflate::decompress_slice_iter_to_slice(
+    zlib_buffer,
+    png_get_idat(bytes),
+    true,
+    true,
+  );
+
+  let transparent_black: P = P::from(r32g32b32a32_Sfloat::TRANSPARENT_BLACK);
+  pixels.fill(transparent_black);
+  png_unfilter_to_pixels(
+    bytes,
+    &ihdr,
+    zlib_buffer,
+    &mut crate::BorrowedBitmap { width: ihdr.width, height: ihdr.height, pixels },
+  );
+  Ok(())
+}
+
+/// Decodes a PNG one scanline at a time, without allocating.
+///
+/// Each row is written into `row_buf` and then passed to `on_row` as
+/// `(y, row)`, from the top row down. The row's pixels are linear and straight
+/// alpha (the same as [decode_rgba8](crate::decode_rgba8)), and fully
+/// transparent pixels are transparent black. This is the same decoding as
+/// [png_try_bitmap_rgba], so it's suited to sending pixels directly to a
+/// display without having room for the whole image.
+///
+/// * `scratch` is used to decompress the image data, and it must be at least
+///   [IHDR::get_zlib_decompression_requirement] bytes.
+/// * `row_buf` must be at least the image's width in pixels. The row passed to
+///   `on_row` is exactly the image's width.
+///
+/// ## Failure
+/// * Interlaced images can't be given out row by row without holding the
+///   whole image, so they give a parse error.
+/// * If `scratch` or `row_buf` is too small you get
+///   [ImagineError::BufferTooSmall].
+#[inline]
+#[cfg(feature = "miniz_oxide")]
+#[cfg_attr(docs_rs, doc(cfg(feature = "miniz_oxide")))]
+pub fn png_decode_scanlines_no_alloc<F>(
+  bytes: &[u8], scratch: &mut [u8], row_buf: &mut [r8g8b8a8_Unorm], mut on_row: F,
+) -> Result<(), ImagineError>
+where
+  F: FnMut(u32, &[r8g8b8a8_Unorm]),
+{
+  let ihdr = png_get_decode_header(bytes)?;
+  if ihdr.is_interlaced {
+    return Err(ImagineError::Parse);
+  }
+  let row = row_buf.get_mut(..usize::try_from(ihdr.width)?).ok_or(ImagineError::BufferTooSmall)?;
+  let zlib_buffer = scratch
+    .get_mut(..ihdr.get_zlib_decompression_requirement())
+    .ok_or(ImagineError::BufferTooSmall)?;
+  zlib_buffer.fill(0);
+  let _who_cares = miniz_oxide::inflate::decompress_slice_iter_to_slice(
+    zlib_buffer,
+    png_get_idat(bytes),
+    true,
+    true,
+  );
+
+  let u8f = |c: f32| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+  let last_x = ihdr.width - 1;
+  png_unfilter_for_each(bytes, &ihdr, zlib_buffer, |x, y, p: r32g32b32a32_Sfloat| {
+    row[x as usize] = if p.a > 0.0 {
+      r8g8b8a8_Unorm { r: u8f(p.r / p.a), g: u8f(p.g / p.a), b: u8f(p.b / p.a), a: u8f(p.a) }
+    } else {
+      r8g8b8a8_Unorm::TRANSPARENT_BLACK
+    };
+    if x == last_x {
+      on_row(y, row);
+    }
+  });
+  Ok(())
+}
+
+/// Decodes a PNG into 8-bit RGBA, keeping the file's own sample values.
+///
+/// The normal decoders convert to linear, pre-multiplied color, which can't
+/// keep the exact color of partly transparent pixels. This instead copies the
+/// stored samples directly, with straight alpha, so an 8-bit image round
+/// trips exactly. The output has the origin in the top left.
+///
+/// * 8-bit RGBA, RGB, greyscale, and indexed data are copied as is. Greyscale
+///   fills in all three color channels, and a palette fills in the colors (and
+///   alphas, from `tRNS`) of indexed data.
+/// * Greyscale with fewer than 8 bits is scaled up to the full `u8` range.
+/// * With a `tRNS` color key, matching RGB or greyscale pixels get an alpha of
+///   0, but they keep their color.
+/// * 16-bit samples are rounded to the nearest 8-bit value, so those images
+///   aren't lossless.
+/// * Gamma info (`sRGB`, `gAMA`, `iCCP`) is *not* applied. The values are
+///   the encoded values from the file, so they're often not actually linear,
+///   even though the output type is usually for linear data.
+///
+/// ## Failure
+/// * The image's width or height must be 17,000 or less, and not 0, the same
+///   as with [png_try_bitmap_rgba].
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_bitmap_rgba8_lossless(
+  bytes: &[u8],
+) -> Result<crate::Bitmap<r8g8b8a8_Unorm>, ImagineError> {
+  use alloc::vec::Vec;
+  use bitfrob::{U8_SCALE_1_TO_8, U8_SCALE_2_TO_8, U8_SCALE_4_TO_8};
+
+  let ihdr = png_get_decode_header(bytes)?;
+  let (width, height, mut pixels) =
+    png_alloc_decode_pixels(&ihdr, r8g8b8a8_Unorm::TRANSPARENT_BLACK)?;
+  let mut scratch: Vec<u8> = Vec::new();
+  scratch.try_reserve(ihdr.get_zlib_decompression_requirement())?;
+  scratch.resize(ihdr.get_zlib_decompression_requirement(), 0);
+
+  let trns = png_get_transparency(bytes);
+  let trns_y = trns.and_then(|trns| trns.try_to_grayscale());
+  let trns_rgb = trns.and_then(|trns| trns.try_to_rgb());
+  let mut palette = [r8g8b8a8_Unorm::TRANSPARENT_BLACK; 256];
+  if ihdr.color_type == PngColorType::Index {
+    let alphas: &[u8] = trns.map(|trns| trns.to_alphas()).unwrap_or(&[]);
+    let plte = png_get_palette(bytes).unwrap_or(&[]);
+    palette.iter_mut().zip(plte.iter()).enumerate().for_each(|(i, (p, &[r, g, b]))| {
+      let a = alphas.get(i).copied().unwrap_or(u8::MAX);
+      *p = r8g8b8a8_Unorm { r, g, b, a };
+    });
+  }
+  let y_mult = match ihdr.bit_depth {
+    1 => U8_SCALE_1_TO_8,
+    2 => U8_SCALE_2_TO_8,
+    4 => U8_SCALE_4_TO_8,
+    _ => 1,
+  };
+  let to_u8 = |v: u16| ((u32::from(v) * 255 + 32767) / 65535) as u8;
+  let key_alpha = |keyed: bool| if keyed { 0 } else { u8::MAX };
+
+  png_for_each_pixel(bytes, &mut scratch, |x, y, sample| {
+    let p = match sample {
+      PngSample::Y8(v) => {
+        let y = v * y_mult;
+        r8g8b8a8_Unorm { r: y, g: y, b: y, a: key_alpha(Some(u16::from(v)) == trns_y) }
+      }
+      PngSample::Y16(v) => {
+        let y = to_u8(v);
+        r8g8b8a8_Unorm { r: y, g: y, b: y, a: key_alpha(Some(v) == trns_y) }
+      }
+      PngSample::YA8([y, a]) => r8g8b8a8_Unorm { r: y, g: y, b: y, a },
+      PngSample::YA16([y, a]) => {
+        let y = to_u8(y);
+        r8g8b8a8_Unorm { r: y, g: y, b: y, a: to_u8(a) }
+      }
+      PngSample::Rgb8([r, g, b]) => {
+        let keyed = Some([u16::from(r), u16::from(g), u16::from(b)]) == trns_rgb;
+        r8g8b8a8_Unorm { r, g, b, a: key_alpha(keyed) }
+      }
+      PngSample::Rgb16(rgb) => {
+        let [r, g, b] = rgb.map(to_u8);
+        r8g8b8a8_Unorm { r, g, b, a: key_alpha(Some(rgb) == trns_rgb) }
+      }
+      PngSample::Rgba8([r, g, b, a]) => r8g8b8a8_Unorm { r, g, b, a },
+      PngSample::Rgba16(rgba) => {
+        let [r, g, b, a] = rgba.map(to_u8);
+        r8g8b8a8_Unorm { r, g, b, a }
+      }
+      PngSample::Index(i) => palette[usize::from(i)],
+    };
+    if let Some(dst) = pixels.get_mut((y as usize) * (width as usize) + (x as usize)) {
+      *dst = p;
+    }
+  })?;
+  Ok(crate::Bitmap { width, height, pixels })
+}
+
+/// Decodes an indexed PNG as one index per pixel, plus the resolved palette.
+///
+/// The output is `(indexes, palette, width, height)`. There's one `u8` index
+/// per pixel, with the origin in the top left, even if the image uses fewer
+/// bits per index. The palette has the linear, pre-multiplied colors that
+/// [png_try_bitmap_rgba] would use for each index (including the gamma info
+/// and `tRNS` alphas), and any entries past the end of the `PLTE` are
+/// transparent black.
+///
+/// Keeping the indexes separate lets you change the palette later, such as for
+/// palette cycling effects, without decoding the image again.
+///
+/// ## Failure
+/// * Images that aren't indexed color give [ImagineError::Parse].
+/// * The image's width or height must be 17,000 or less, and not 0, the same
+///   as with [png_try_bitmap_rgba].
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_try_indexed_rgba(
+  bytes: &[u8],
+) -> Result<(alloc::vec::Vec<u8>, [r32g32b32a32_Sfloat; 256], u32, u32), ImagineError> {
+  use alloc::vec::Vec;
+
+  let ihdr = png_get_decode_header(bytes)?;
+  if ihdr.color_type != PngColorType::Index {
+    return Err(ImagineError::Parse);
+  }
+  let (width, height, mut indexes) = png_alloc_decode_pixels(&ihdr, 0_u8)?;
+  let mut scratch: Vec<u8> = Vec::new();
+  scratch.try_reserve(ihdr.get_zlib_decompression_requirement())?;
+  scratch.resize(ihdr.get_zlib_decompression_requirement(), 0);
+
+  png_for_each_pixel(bytes, &mut scratch, |x, y, sample| {
+    if let (PngSample::Index(i), Some(dst)) =
+      (sample, indexes.get_mut((y as usize) * (width as usize) + (x as usize)))
+    {
+      *dst = i;
+    }
+  })?;
+  Ok((indexes, png_resolve_palette(bytes), width, height))
+}
+
+/// The pixel layout that [png_write_bitmap] uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PngWriteFormat {
+  /// 8-bit RGBA, stored as sRGB with straight alpha (and an `sRGB` chunk).
+  #[default]
+  Rgba8,
+  /// 8-bit RGB, stored as sRGB (and an `sRGB` chunk).
+  ///
+  /// Like with the greyscale formats there's no alpha, so the pixels are
+  /// effectively composited over black.
+  Rgb8,
+  /// 8-bit greyscale, stored as sRGB (and an `sRGB` chunk).
+  Y8,
+  /// 16-bit greyscale, stored as linear (and a `gAMA` chunk of 1.0).
+  ///
+  /// 16 bits is enough precision that linear storage doesn't band.
+  Y16,
+}
+
+/// Encodes a [Bitmap](crate::Bitmap) as the bytes of a PNG file.
+///
+/// The pixels are expected to be linear and pre-multiplied, which is what the
+/// decoders output, and the file uses the layout picked by `format`. The image
+/// data isn't filtered (every line uses filter type 0), and it's compressed
+/// with `miniz_oxide` at its default level.
+///
+/// The greyscale formats are a third the size of RGB (before compression),
+/// which is good for scanned documents and the like. Colors that aren't
+/// already grey are converted with the Rec. 709 luma weights, and since
+/// there's no alpha the pixels are effectively composited over black.
+///
+/// ## Failure
+/// * If the bitmap doesn't have exactly `width * height` pixels you get
+///   [ImagineError::DimensionsMismatch].
+/// * A width or height of 0 can't be stored in a PNG, and gives
+///   [ImagineError::WidthOrHeightZero].
+/// * Images too large for the IHDR fields give
+///   [ImagineError::DimensionsTooLarge].
+#[inline]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+#[cfg_attr(docs_rs, doc(cfg(all(feature = "alloc", feature = "miniz_oxide"))))]
+pub fn png_write_bitmap<P>(
+  bitmap: &crate::Bitmap<P>, format: PngWriteFormat,
+) -> Result<alloc::vec::Vec<u8>, ImagineError>
+where
+  P: Copy,
+  r32g32b32a32_Sfloat: From<P>,
+{
+  use alloc::vec::Vec;
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let pixel_count: usize =
+    bitmap.width.checked_mul(bitmap.height).ok_or(ImagineError::CheckedMath)?.try_into()?;
+  if bitmap.pixels.len() != pixel_count {
+    return Err(ImagineError::DimensionsMismatch);
+  }
+  if bitmap.width == 0 || bitmap.height == 0 {
+    return Err(ImagineError::WidthOrHeightZero);
+  }
+  if bitmap.width > i32::MAX as u32 || bitmap.height > i32::MAX as u32 {
+    return Err(ImagineError::DimensionsTooLarge);
+  }
+  let (color_type, bit_depth, bytes_per_pixel) = match format {
+    PngWriteFormat::Rgba8 => (PngColorType::RGBA, 8, 4),
+    PngWriteFormat::Rgb8 => (PngColorType::RGB, 8, 3),
+    PngWriteFormat::Y8 => (PngColorType::Y, 8, 1),
+    PngWriteFormat::Y16 => (PngColorType::Y, 16, 2),
+  };
+
+  // filter byte, then the pixel data, for each line.
+  let line_len =
+    (bitmap.width as usize).checked_mul(bytes_per_pixel).ok_or(ImagineError::CheckedMath)? + 1;
+  let mut filtered: Vec<u8> = Vec::new();
+  filtered
+    .try_reserve(line_len.checked_mul(bitmap.height as usize).ok_or(ImagineError::CheckedMath)?)?;
+  let luma = |c: r32g32b32a32_Sfloat| 0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b;
+  for row in bitmap.pixels.chunks_exact(bitmap.width as usize) {
+    filtered.push(0);
+    for p in row {
+      let sfloat = r32g32b32a32_Sfloat::from(*p);
+      match format {
+        PngWriteFormat::Rgba8 => {
+          let r8g8b8a8_Srgb { r, g, b, a } =
+            r8g8b8a8_Srgb::from(crate::alloc_bitmap::unpremultiply(sfloat));
+          filtered.extend_from_slice(&[r, g, b, a]);
+        }
+        PngWriteFormat::Rgb8 => {
+          let r8g8b8a8_Srgb { r, g, b, .. } =
+            r8g8b8a8_Srgb::from(r32g32b32a32_Sfloat { a: 1.0, ..sfloat });
+          filtered.extend_from_slice(&[r, g, b]);
+        }
+        PngWriteFormat::Y8 => {
+          let y = luma(sfloat);
+          let grey = r32g32b32a32_Sfloat { r: y, g: y, b: y, a: 1.0 };
+          filtered.push(r8g8b8a8_Srgb::from(grey).r);
+        }
+        PngWriteFormat::Y16 => {
+          let y = (luma(sfloat).clamp(0.0, 1.0) * (u16::MAX as f32) + 0.5) as u16;
+          filtered.extend_from_slice(&y.to_be_bytes());
+        }
+      }
+    }
+  }
+  let zlib = miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 6);
+
+  let mut ihdr = [0_u8; 13];
+  ihdr[0..4].copy_from_slice(&bitmap.width.to_be_bytes());
+  ihdr[4..8].copy_from_slice(&bitmap.height.to_be_bytes());
+  ihdr[8] = bit_depth;
+  ihdr[9] = color_type as u8;
+
+  let mut out: Vec<u8> = Vec::new();
+  out.try_reserve(8 + 25 + 13 + 16 + zlib.len() + 12)?;
+  out.extend_from_slice(&[137, 80, 78, 71, 13, 10, 26, 10]);
+  let mut write_chunk = |type_: PngRawChunkType, data: &[u8]| {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&type_.0);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&png_chunk_crc(type_, data).to_be_bytes());
+  };
+  write_chunk(PngRawChunkType::IHDR, &ihdr);
+  if bit_depth == 16 {
+    write_chunk(PngRawChunkType::gAMA, &100_000_u32.to_be_bytes());
+  } else {
+    // rendering intent: perceptual
+    write_chunk(PngRawChunkType::sRGB, &[0]);
+  }
+  // IDAT chunks can't be over 2^31-1 bytes, so large data is split up.
+  for idat in zlib.chunks(i32::MAX as usize) {
+    write_chunk(PngRawChunkType::IDAT, idat);
+  }
+  write_chunk(PngRawChunkType::IEND, &[]);
+  Ok(out)
+}
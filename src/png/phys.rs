@@ -0,0 +1,53 @@
+use super::*;
+
+/// Physical pixel dimensions.
+///
+/// When the unit isn't known the values only give the pixel aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(nonstandard_style)]
+pub struct pHYs {
+  /// Pixels per unit, horizontally.
+  pub x: u32,
+  /// Pixels per unit, vertically.
+  pub y: u32,
+  /// If the unit is the meter (otherwise the unit isn't known).
+  pub unit_is_meter: bool,
+}
+impl TryFrom<&[u8]> for pHYs {
+  type Error = ();
+  #[inline]
+  fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+    match *slice {
+      [x0, x1, x2, x3, y0, y1, y2, y3, unit @ (0 | 1)] => Ok(pHYs {
+        x: u32::from_be_bytes([x0, x1, x2, x3]),
+        y: u32::from_be_bytes([y0, y1, y2, y3]),
+        unit_is_meter: unit == 1,
+      }),
+      _ => Err(()),
+    }
+  }
+}
+impl TryFrom<PngChunk<'_>> for pHYs {
+  type Error = ();
+  #[inline]
+  fn try_from(value: PngChunk<'_>) -> Result<Self, Self::Error> {
+    match value {
+      PngChunk::pHYs(phys) => Ok(phys),
+      _ => Err(()),
+    }
+  }
+}
+impl pHYs {
+  /// The horizontal and vertical resolution in dots per inch.
+  ///
+  /// This is `None` if the unit isn't known, or either value is 0.
+  #[inline]
+  #[must_use]
+  pub fn dpi(&self) -> Option<(f32, f32)> {
+    if self.unit_is_meter && self.x > 0 && self.y > 0 {
+      Some((self.x as f32 * 0.0254, self.y as f32 * 0.0254))
+    } else {
+      None
+    }
+  }
+}
@@ -16,12 +16,23 @@ pub enum PngChunk<'b> {
   tRNS(tRNS<'b>),
   /// Background color
   bKGD(bKGD),
+  /// Embedded ICC profile
+  iCCP(iCCP<'b>),
+  /// Suggested palette
+  sPLT(sPLT<'b>),
+  /// Primary chromaticities and white point
+  cHRM(cHRM),
+  /// Physical pixel dimensions
+  pHYs(pHYs),
+  /// Last modification time
+  tIME(tIME),
+  /// Latin-1 text
+  tEXt(tEXt<'b>),
   /// Image Data
   IDAT(IDAT<'b>),
   /// Image End
   IEND,
 }
-// * TODO: cHRM
 // * TODO: sBIT
 impl<'b> TryFrom<PngRawChunk<'b>> for PngChunk<'b> {
   type Error = PngRawChunk<'b>;
@@ -41,6 +52,30 @@ impl<'b> TryFrom<PngRawChunk<'b>> for PngChunk<'b> {
         // this can fail, so use `return` to avoid the outer Ok()
         return bKGD::try_from(raw.data).map(PngChunk::bKGD).map_err(|_| raw);
       }
+      PngRawChunkType::iCCP => {
+        // this can fail, so use `return` to avoid the outer Ok()
+        return iCCP::try_from(raw.data).map(PngChunk::iCCP).map_err(|_| raw);
+      }
+      PngRawChunkType::sPLT => {
+        // this can fail, so use `return` to avoid the outer Ok()
+        return sPLT::try_from(raw.data).map(PngChunk::sPLT).map_err(|_| raw);
+      }
+      PngRawChunkType::cHRM => {
+        // this can fail, so use `return` to avoid the outer Ok()
+        return cHRM::try_from(raw.data).map(PngChunk::cHRM).map_err(|_| raw);
+      }
+      PngRawChunkType::pHYs => {
+        // this can fail, so use `return` to avoid the outer Ok()
+        return pHYs::try_from(raw.data).map(PngChunk::pHYs).map_err(|_| raw);
+      }
+      PngRawChunkType::tIME => {
+        // this can fail, so use `return` to avoid the outer Ok()
+        return tIME::try_from(raw.data).map(PngChunk::tIME).map_err(|_| raw);
+      }
+      PngRawChunkType::tEXt => {
+        // this can fail, so use `return` to avoid the outer Ok()
+        return tEXt::try_from(raw.data).map(PngChunk::tEXt).map_err(|_| raw);
+      }
       PngRawChunkType::sRGB => PngChunk::sRGB(match raw.data.get(0) {
         Some(0) => sRGBIntent::Perceptual,
         Some(1) => sRGBIntent::RelativeColorimetric,
@@ -13,6 +13,12 @@ impl PngRawChunkType {
   pub const bKGD: Self = Self(*b"bKGD");
   pub const sRGB: Self = Self(*b"sRGB");
   pub const gAMA: Self = Self(*b"gAMA");
+  pub const iCCP: Self = Self(*b"iCCP");
+  pub const sPLT: Self = Self(*b"sPLT");
+  pub const cHRM: Self = Self(*b"cHRM");
+  pub const pHYs: Self = Self(*b"pHYs");
+  pub const tIME: Self = Self(*b"tIME");
+  pub const tEXt: Self = Self(*b"tEXt");
 }
 impl Debug for PngRawChunkType {
   fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -91,3 +97,18 @@ impl<'b> Iterator for PngRawChunkIter<'b> {
     Some(PngRawChunk { type_, data, declared_crc })
   }
 }
+
+/// Computes the CRC-32 of a chunk's type and data, as stored after the data.
+#[inline]
+#[must_use]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+pub(crate) fn png_chunk_crc(type_: PngRawChunkType, data: &[u8]) -> u32 {
+  let mut crc = u32::MAX;
+  for &byte in type_.0.iter().chain(data.iter()) {
+    crc ^= u32::from(byte);
+    for _ in 0..8 {
+      crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
@@ -0,0 +1,117 @@
+use super::*;
+
+/// Suggested palette.
+///
+/// The chunk holds a palette name, the sample depth (8 or 16), and then the
+/// palette entries. A PNG can have more than one of these, each with a
+/// different name.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(nonstandard_style)]
+pub struct sPLT<'b> {
+  name: &'b [u8],
+  sample_depth: u8,
+  entries: &'b [u8],
+}
+impl<'b> TryFrom<&'b [u8]> for sPLT<'b> {
+  type Error = ();
+  #[inline]
+  fn try_from(data: &'b [u8]) -> Result<Self, Self::Error> {
+    let name_len = data.iter().position(|&u| u == 0).ok_or(())?;
+    if !(1..=79).contains(&name_len) {
+      return Err(());
+    }
+    let (name, rest) = data.split_at(name_len);
+    match rest {
+      // null separator, then the sample depth
+      [0, sample_depth @ (8 | 16), entries @ ..] => {
+        Ok(Self { name, sample_depth: *sample_depth, entries })
+      }
+      _ => Err(()),
+    }
+  }
+}
+impl<'b> TryFrom<PngChunk<'b>> for sPLT<'b> {
+  type Error = ();
+  #[inline]
+  fn try_from(value: PngChunk<'b>) -> Result<Self, Self::Error> {
+    match value {
+      PngChunk::sPLT(splt) => Ok(splt),
+      _ => Err(()),
+    }
+  }
+}
+impl Debug for sPLT<'_> {
+  #[inline]
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("sPLT")
+      .field("name", &self.name)
+      .field("sample_depth", &self.sample_depth)
+      .field("entry_count", &self.entries().count())
+      .finish()
+  }
+}
+impl<'b> sPLT<'b> {
+  /// The palette's name (Latin-1 text, 1 to 79 bytes).
+  #[inline]
+  #[must_use]
+  pub const fn name(&self) -> &'b [u8] {
+    self.name
+  }
+
+  /// The bit depth of the entry samples, either 8 or 16.
+  #[inline]
+  #[must_use]
+  pub const fn sample_depth(&self) -> u8 {
+    self.sample_depth
+  }
+
+  /// Iterates the entries of the palette.
+  ///
+  /// With a sample depth of 8 each entry is 6 bytes, and with a sample depth
+  /// of 16 each entry is 10 bytes. If the data isn't a whole number of entries
+  /// then the partial entry at the end is skipped.
+  #[inline]
+  pub fn entries(&self) -> impl Iterator<Item = SuggestedPaletteEntry> + 'b {
+    let sixteen = self.sample_depth == 16;
+    self.entries.chunks_exact(if sixteen { 10 } else { 6 }).map(move |chunk| {
+      let u16_at = |i: usize| u16::from_be_bytes([chunk[i], chunk[i + 1]]);
+      if sixteen {
+        SuggestedPaletteEntry {
+          r: u16_at(0),
+          g: u16_at(2),
+          b: u16_at(4),
+          a: u16_at(6),
+          frequency: u16_at(8),
+        }
+      } else {
+        SuggestedPaletteEntry {
+          r: u16::from(chunk[0]),
+          g: u16::from(chunk[1]),
+          b: u16::from(chunk[2]),
+          a: u16::from(chunk[3]),
+          frequency: u16_at(4),
+        }
+      }
+    })
+  }
+}
+
+/// An entry of a [sPLT] suggested palette.
+///
+/// The samples are straight alpha, and they're in the range of the palette's
+/// sample depth (so with a depth of 8 the max value is 255). The samples are
+/// not gamma corrected, they use the same scale as the image's samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SuggestedPaletteEntry {
+  /// Red
+  pub r: u16,
+  /// Green
+  pub g: u16,
+  /// Blue
+  pub b: u16,
+  /// Alpha
+  pub a: u16,
+  /// How often this color appears in the image, relative to the other
+  /// entries. Entries with a frequency of 0 are best ignored.
+  pub frequency: u16,
+}
@@ -67,3 +67,18 @@ fn test_reduced_image_dimensions() {
     ]
   );
 }
+
+#[test]
+fn test_ihdr_indexed_bit_depth() {
+  use super::*;
+
+  let ihdr_bytes = |bit_depth: u8| [0, 0, 0, 1, 0, 0, 0, 1, bit_depth, 3, 0, 0, 0];
+  for bit_depth in [1, 2, 4, 8] {
+    let ihdr = IHDR::try_from(&ihdr_bytes(bit_depth)[..]).unwrap();
+    assert_eq!(ihdr.color_type, PngColorType::Index);
+    assert_eq!(ihdr.bit_depth, bit_depth);
+  }
+  for bit_depth in [0, 3, 16] {
+    assert!(IHDR::try_from(&ihdr_bytes(bit_depth)[..]).is_err(), "bit_depth: {bit_depth}");
+  }
+}
@@ -0,0 +1,56 @@
+use super::*;
+
+/// Uncompressed Latin-1 text.
+///
+/// The chunk holds a keyword (such as `Title` or `Author`) and then the text
+/// for that keyword. A PNG can have any number of these.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(nonstandard_style)]
+pub struct tEXt<'b> {
+  keyword: &'b [u8],
+  text: &'b [u8],
+}
+impl<'b> TryFrom<&'b [u8]> for tEXt<'b> {
+  type Error = ();
+  #[inline]
+  fn try_from(data: &'b [u8]) -> Result<Self, Self::Error> {
+    let keyword_len = data.iter().position(|&u| u == 0).ok_or(())?;
+    if !(1..=79).contains(&keyword_len) {
+      return Err(());
+    }
+    Ok(Self { keyword: &data[..keyword_len], text: &data[keyword_len + 1..] })
+  }
+}
+impl<'b> TryFrom<PngChunk<'b>> for tEXt<'b> {
+  type Error = ();
+  #[inline]
+  fn try_from(value: PngChunk<'b>) -> Result<Self, Self::Error> {
+    match value {
+      PngChunk::tEXt(text) => Ok(text),
+      _ => Err(()),
+    }
+  }
+}
+impl Debug for tEXt<'_> {
+  #[inline]
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    f.debug_struct("tEXt")
+      .field("keyword", &self.keyword)
+      .field("text_len", &self.text.len())
+      .finish()
+  }
+}
+impl<'b> tEXt<'b> {
+  /// The keyword (Latin-1 text, 1 to 79 bytes).
+  #[inline]
+  #[must_use]
+  pub const fn keyword(&self) -> &'b [u8] {
+    self.keyword
+  }
+  /// The text (Latin-1, with `'\n'` line breaks).
+  #[inline]
+  #[must_use]
+  pub const fn text(&self) -> &'b [u8] {
+    self.text
+  }
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+/// The time of the last image modification, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(nonstandard_style)]
+pub struct tIME {
+  /// The full year, such as 2024.
+  pub year: u16,
+  /// 1 to 12
+  pub month: u8,
+  /// 1 to 31
+  pub day: u8,
+  /// 0 to 23
+  pub hour: u8,
+  /// 0 to 59
+  pub minute: u8,
+  /// 0 to 60 (to allow for a leap second)
+  pub second: u8,
+}
+impl TryFrom<&[u8]> for tIME {
+  type Error = ();
+  #[inline]
+  fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+    match *slice {
+      [y0, y1, month @ 1..=12, day @ 1..=31, hour @ 0..=23, minute @ 0..=59, second @ 0..=60] => {
+        Ok(tIME { year: u16::from_be_bytes([y0, y1]), month, day, hour, minute, second })
+      }
+      _ => Err(()),
+    }
+  }
+}
+impl TryFrom<PngChunk<'_>> for tIME {
+  type Error = ();
+  #[inline]
+  fn try_from(value: PngChunk<'_>) -> Result<Self, Self::Error> {
+    match value {
+      PngChunk::tIME(time) => Ok(time),
+      _ => Err(()),
+    }
+  }
+}
@@ -68,3 +68,25 @@ pub(crate) fn onz_u16_le(bytes: &[u8]) -> Option<NonZeroU16> {
 pub(crate) fn onz_u32_le(bytes: &[u8]) -> Option<NonZeroU32> {
   NonZeroU32::new(u32_le(bytes))
 }
+
+/// The sRGB decoding curve, for one channel.
+#[inline]
+#[must_use]
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+  if c <= 0.04045 {
+    c / 12.92
+  } else {
+    ((c + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+/// The sRGB encoding curve, for one channel.
+#[inline]
+#[must_use]
+pub(crate) fn linear_to_srgb(c: f32) -> f32 {
+  if c <= 0.0031308 {
+    c * 12.92
+  } else {
+    1.055 * c.powf(1.0 / 2.4) - 0.055
+  }
+}
@@ -2,7 +2,7 @@
 #![allow(unused_imports)]
 
 #[cfg(feature = "alloc")]
-use imagine::Bitmap;
+use imagine::{AlphaHandling, Bitmap};
 
 #[cfg(feature = "png")]
 mod png;
@@ -10,6 +10,9 @@ mod png;
 #[cfg(feature = "bmp")]
 mod bmp;
 
+#[cfg(feature = "netpbm")]
+mod netpbm;
+
 #[allow(dead_code)]
 fn rand_bytes(count: usize) -> Vec<u8> {
   let mut buffer = vec![0; count];
@@ -17,6 +20,15 @@ fn rand_bytes(count: usize) -> Vec<u8> {
   buffer
 }
 
+/// Passes the stored values through as if they were linear.
+#[allow(dead_code)]
+struct AsLinear;
+impl imagine::ColorTransform for AsLinear {
+  fn apply(&self, [r, g, b, a]: [f32; 4]) -> pixel_formats::r32g32b32a32_Sfloat {
+    pixel_formats::r32g32b32a32_Sfloat { r, g, b, a }
+  }
+}
+
 #[test]
 #[cfg(feature = "alloc")]
 fn test_image_vertical_flip() {
@@ -28,3 +40,1179 @@ fn test_image_vertical_flip() {
   i.vertical_flip();
   assert_eq!(i.pixels, vec![5, 6, 7, 8, 1, 2, 3, 4]);
 }
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_draw_corner_markers() {
+  let mut i = Bitmap { width: 6, height: 5, pixels: vec![0; 30] };
+  i.draw_corner_markers(1);
+  #[rustfmt::skip]
+  assert_eq!(i.pixels, vec![
+    1, 1, 1, 1, 1, 1,
+    1, 1, 1, 0, 0, 0,
+    1, 1, 1, 0, 0, 0,
+    1, 1, 0, 0, 0, 0,
+    1, 1, 0, 0, 0, 0,
+  ]);
+
+  // all 8 ways to flip and rotate a square image give different results.
+  let mut i = Bitmap { width: 7, height: 7, pixels: vec![0; 49] };
+  i.draw_corner_markers(1);
+  let mut seen = Vec::new();
+  for transpose in [false, true] {
+    for h_flip in [false, true] {
+      for v_flip in [false, true] {
+        let mut o = i.clone();
+        if transpose {
+          o.transpose();
+        }
+        if h_flip {
+          o.horizontal_flip();
+        }
+        if v_flip {
+          o.vertical_flip();
+        }
+        assert!(!seen.contains(&o), "{transpose} {h_flip} {v_flip}");
+        seen.push(o);
+      }
+    }
+  }
+  let mut t = i.clone();
+  t.transpose();
+  assert_ne!(t, i);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_rows_rev_and_column() {
+  let i = Bitmap { width: 3, height: 2, pixels: vec![1, 2, 3, 4, 5, 6] };
+  let rows: Vec<&[i32]> = i.rows_rev().collect();
+  assert_eq!(rows, vec![&[4, 5, 6][..], &[1, 2, 3][..]]);
+  assert_eq!(i.column(1).unwrap().copied().collect::<Vec<_>>(), vec![2, 5]);
+  assert_eq!(i.column(2).unwrap().copied().collect::<Vec<_>>(), vec![3, 6]);
+  assert!(i.column(3).is_none());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_tiled() {
+  use imagine::ImagineError;
+  let i = Bitmap { width: 2, height: 2, pixels: vec![1, 2, 3, 4] };
+  let out = i.tiled(5, 3).unwrap();
+  assert_eq!((out.width, out.height), (5, 3));
+  assert_eq!(out.pixels, vec![1, 2, 1, 2, 1, 3, 4, 3, 4, 3, 1, 2, 1, 2, 1]);
+  assert_eq!(i.tiled(1, 1).unwrap().pixels, vec![1]);
+  assert_eq!(i.tiled(0, 4).unwrap().pixels, Vec::<i32>::new());
+  let empty = Bitmap { width: 0, height: 2, pixels: Vec::<i32>::new() };
+  assert_eq!(empty.tiled(4, 4), Err(ImagineError::WidthOrHeightZero));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_row_bands() {
+  let mut i = Bitmap { width: 2, height: 5, pixels: (0..10).collect::<Vec<i32>>() };
+  let bands: Vec<(u32, &[i32])> = i.row_bands(2).collect();
+  assert_eq!(bands, vec![(0, &[0, 1, 2, 3][..]), (2, &[4, 5, 6, 7][..]), (4, &[8, 9][..])]);
+  assert_eq!(i.row_bands(0).count(), 5);
+  assert_eq!(i.row_bands(9).count(), 1);
+
+  for (start_row, band) in i.row_bands_mut(3) {
+    band.iter_mut().for_each(|p| *p = start_row as i32);
+  }
+  assert_eq!(i.pixels, vec![0, 0, 0, 0, 0, 0, 3, 3, 3, 3]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_composite_over_checker() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  let clear = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let red = r32g32b32a32_Sfloat { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+  let light = r32g32b32a32_Sfloat::OPAQUE_WHITE;
+  let dark = r32g32b32a32_Sfloat::OPAQUE_BLACK;
+  let mut i = Bitmap { width: 4, height: 2, pixels: vec![clear; 8] };
+  i.pixels[3] = red;
+  let out = i.composite_over_checker(2, light, dark);
+  assert_eq!(out.pixels, vec![light, light, dark, red, light, light, dark, dark]);
+  assert!(out.pixels.iter().all(|p| p.a == 1.0));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_unique_colors_and_palmap() {
+  let i = Bitmap { width: 3, height: 2, pixels: vec![7, 5, 7, 9, 5, 5] };
+  assert_eq!(i.unique_colors(3), Some(vec![7, 5, 9]));
+  assert_eq!(i.unique_colors(2), None);
+
+  let palmap = i.to_palmap_if_indexable(256).unwrap();
+  assert_eq!(palmap.palette, vec![7, 5, 9]);
+  assert_eq!(palmap.indexes, vec![0, 1, 0, 2, 1, 1]);
+  assert_eq!(Bitmap::<i32>::from(&palmap), i);
+
+  let big = Bitmap { width: 300, height: 1, pixels: (0..300).collect::<Vec<i32>>() };
+  // a u8 index can't go past 256 colors, whatever `max` is.
+  assert!(big.to_palmap_if_indexable(1000).is_none());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_linearize_and_encode_srgb() {
+  use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Srgb, r8g8b8a8_Unorm};
+
+  let bytes: Vec<u8> = (0..=255).collect();
+  let unorm = Bitmap {
+    width: 256,
+    height: 1,
+    pixels: bytes.iter().map(|&u| r8g8b8a8_Unorm { r: u, g: u, b: u, a: 255 }).collect(),
+  };
+  // the Srgb `From` impl applies the curve, so it should agree with linearize.
+  let linear: Bitmap<r32g32b32a32_Sfloat> = unorm.linearize();
+  for (l, &u) in linear.pixels.iter().zip(bytes.iter()) {
+    let expected = r32g32b32a32_Sfloat::from(r8g8b8a8_Srgb { r: u, g: u, b: u, a: 255 });
+    assert!((l.r - expected.r).abs() < 0.0001, "{u}: {l:?} vs {expected:?}");
+    assert_eq!(l.a, 1.0);
+  }
+  // and going back gives the original sRGB bytes.
+  let encoded: Bitmap<r32g32b32a32_Sfloat> = linear.encode_srgb();
+  for (e, &u) in encoded.pixels.iter().zip(bytes.iter()) {
+    assert_eq!((e.g * 255.0).round() as u8, u);
+  }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_draw_line_and_rect_outline() {
+  let mut i = Bitmap { width: 5, height: 4, pixels: vec![0; 20] };
+  i.draw_line(0, 0, 4, 2, 1);
+  // this line is mostly outside the image
+  i.draw_line(-10, 3, 1, 3, 2);
+  #[rustfmt::skip]
+  assert_eq!(i.pixels, vec![
+    1, 0, 0, 0, 0,
+    0, 1, 1, 0, 0,
+    0, 0, 0, 1, 1,
+    2, 2, 0, 0, 0,
+  ]);
+
+  let mut i = Bitmap { width: 5, height: 4, pixels: vec![0; 20] };
+  i.draw_rect_outline(1, 1, 3, 5, 1);
+  #[rustfmt::skip]
+  assert_eq!(i.pixels, vec![
+    0, 0, 0, 0, 0,
+    0, 1, 1, 1, 0,
+    0, 1, 0, 1, 0,
+    0, 1, 0, 1, 0,
+  ]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_rotate() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let px = |v: f32| r32g32b32a32_Sfloat { r: v, g: v, b: v, a: 1.0 };
+  let fill = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let close = |a: &Bitmap<r32g32b32a32_Sfloat>, b: &[f32]| {
+    assert_eq!(a.pixels.len(), b.len());
+    for (p, v) in a.pixels.iter().zip(b.iter()) {
+      assert!((p.r - v).abs() < 0.001, "{p:?} vs {v}");
+    }
+  };
+  let i = Bitmap {
+    width: 3,
+    height: 2,
+    pixels: vec![px(0.0), px(0.2), px(0.4), px(0.6), px(0.8), px(1.0)],
+  };
+
+  let r = i.rotate(0.0, fill);
+  assert_eq!(r, i);
+
+  let r = i.rotate(core::f32::consts::PI, fill);
+  assert_eq!((r.width, r.height), (3, 2));
+  close(&r, &[1.0, 0.8, 0.6, 0.4, 0.2, 0.0]);
+
+  let r = i.rotate(core::f32::consts::FRAC_PI_2, fill);
+  assert_eq!((r.width, r.height), (2, 3));
+  close(&r, &[0.6, 0.0, 0.8, 0.2, 1.0, 0.4]);
+
+  // at 45 degrees the canvas grows, and the corners are filled.
+  let r = i.rotate(core::f32::consts::FRAC_PI_4, fill);
+  assert_eq!((r.width, r.height), (4, 4));
+  assert_eq!(r.pixels[0], fill);
+  assert_eq!(r.pixels[15], fill);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_empty_and_truncated_input_errors() {
+  use imagine::{try_bitmap_rgba, ImagineError};
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let signatures: [&[u8]; 3] = [b"\x89PNG\r\n\x1a\n", b"BM\x46\x00\x00\x00\x00\x00", b"P6 1 1 2"];
+  for signature in signatures {
+    for len in 0..signature.len() {
+      let bytes = &signature[..len];
+      #[cfg(all(feature = "png", feature = "miniz_oxide"))]
+      assert!(imagine::png::png_try_bitmap_rgba::<r8g8b8a8_Srgb>(bytes, true).is_err());
+      #[cfg(feature = "bmp")]
+      assert!(imagine::bmp::bmp_try_bitmap_rgba::<r8g8b8a8_Srgb>(bytes, true).is_err());
+      #[cfg(feature = "netpbm")]
+      assert!(imagine::netpbm::netpbm_try_bitmap_rgba::<r8g8b8a8_Srgb>(bytes, true).is_err());
+      assert_eq!(try_bitmap_rgba::<r8g8b8a8_Srgb>(bytes, true), Err(ImagineError::Parse));
+    }
+  }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_crop_and_tiles() {
+  let i = Bitmap { width: 5, height: 3, pixels: (0..15).collect::<Vec<u8>>() };
+  assert_eq!(i.crop(1, 1, 2, 2), Bitmap { width: 2, height: 2, pixels: vec![6, 7, 11, 12] });
+  assert_eq!(i.crop(4, 2, 9, 9), Bitmap { width: 1, height: 1, pixels: vec![14] });
+  assert_eq!(i.crop(9, 0, 1, 1).pixels.len(), 0);
+
+  let tiles: Vec<_> = i.tiles(2, 2, false).collect();
+  assert_eq!(tiles.len(), 2);
+  assert_eq!(tiles[0].pixels, vec![0, 1, 5, 6]);
+  assert_eq!(tiles[1].pixels, vec![2, 3, 7, 8]);
+
+  let tiles: Vec<_> = i.tiles(2, 2, true).collect();
+  assert_eq!(tiles.len(), 6);
+  assert_eq!((tiles[2].width, tiles[2].height), (1, 2));
+  assert_eq!(tiles[2].pixels, vec![4, 9]);
+  assert_eq!(tiles[5].pixels, vec![14]);
+
+  assert_eq!(i.tiles(0, 2, true).count(), 0);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_compose_layers() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let black = r32g32b32a32_Sfloat::OPAQUE_BLACK;
+  let white = r32g32b32a32_Sfloat::OPAQUE_WHITE;
+  let clear = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let half_white = r32g32b32a32_Sfloat { r: 0.5, g: 0.5, b: 0.5, a: 0.5 };
+  let base = Bitmap { width: 3, height: 2, pixels: vec![black; 6] };
+  let opaque = Bitmap { width: 2, height: 2, pixels: vec![white, clear, white, white] };
+  let half = Bitmap { width: 1, height: 1, pixels: vec![half_white] };
+
+  // the second layer hangs off the right edge and gets clipped.
+  let out = base.compose_layers(&[(&half, 0, 0), (&opaque, 2, 0), (&half, 3, 0)]);
+  let grey = r32g32b32a32_Sfloat { r: 0.5, g: 0.5, b: 0.5, a: 1.0 };
+  assert_eq!(out.pixels, vec![grey, black, white, black, black, white]);
+
+  // later layers go on top.
+  let out = base.compose_layers(&[(&opaque, 0, 0), (&half, 1, 0)]);
+  assert_eq!(out.pixels[1], grey);
+  assert_eq!(base.compose_layers(&[]), base);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_average_and_dominant_color() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let black = r32g32b32a32_Sfloat::OPAQUE_BLACK;
+  let white = r32g32b32a32_Sfloat::OPAQUE_WHITE;
+  let clear = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let red = r32g32b32a32_Sfloat { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+
+  let i = Bitmap { width: 2, height: 2, pixels: vec![black, white, white, clear] };
+  assert_eq!(i.average_color(), r32g32b32a32_Sfloat { r: 0.5, g: 0.5, b: 0.5, a: 0.75 });
+  assert_eq!(i.dominant_color(), Some(white));
+
+  let i = Bitmap { width: 5, height: 1, pixels: vec![red, clear, clear, clear, white] };
+  // transparent pixels don't count as a color.
+  assert_eq!(i.dominant_color(), Some(red));
+
+  let empty: Bitmap<r32g32b32a32_Sfloat> = Bitmap { width: 0, height: 0, pixels: vec![] };
+  assert_eq!(empty.average_color(), clear);
+  assert_eq!(empty.dominant_color(), None);
+  let i = Bitmap { width: 1, height: 1, pixels: vec![clear] };
+  assert_eq!(i.dominant_color(), None);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "png", feature = "miniz_oxide"))]
+fn test_try_bitmap_rgba_uses_signature() {
+  use imagine::{try_bitmap_rgba, ImagineError};
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let mut v = std::fs::read("tests/png/PngSuite-2017jul19/basn0g08.png").unwrap();
+  assert!(try_bitmap_rgba::<r8g8b8a8_Srgb>(&v, true).is_ok());
+  // once the signature says PNG, the PNG decoder's own error comes back.
+  v[16..20].copy_from_slice(&20_000_u32.to_be_bytes());
+  assert_eq!(try_bitmap_rgba::<r8g8b8a8_Srgb>(&v, true), Err(ImagineError::DimensionsTooLarge));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_downsample_box() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let px = |v: f32| r32g32b32a32_Sfloat { r: v, g: v, b: v, a: 1.0 };
+  let close = |a: &Bitmap<r32g32b32a32_Sfloat>, b: &[f32]| {
+    assert_eq!(a.pixels.len(), b.len());
+    for (p, v) in a.pixels.iter().zip(b.iter()) {
+      assert!((p.r - v).abs() < 0.0001 && (p.a - 1.0).abs() < 0.0001, "{p:?} vs {v}");
+    }
+  };
+  // a 4x2 checkerboard averages out to flat grey.
+  let i = Bitmap {
+    width: 4,
+    height: 2,
+    pixels: vec![px(0.0), px(1.0), px(0.0), px(1.0), px(1.0), px(0.0), px(1.0), px(0.0)],
+  };
+  let d = i.downsample_box(2, 1, AlphaHandling::Premultiplied);
+  assert_eq!((d.width, d.height), (2, 1));
+  close(&d, &[0.5, 0.5]);
+
+  // 3 -> 2 splits the middle pixel between both outputs.
+  let i = Bitmap { width: 3, height: 1, pixels: vec![px(0.0), px(0.3), px(0.9)] };
+  close(&i.downsample_box(2, 1, AlphaHandling::Premultiplied), &[0.1, 0.7]);
+
+  // the same size is the same image.
+  close(&i.downsample_box(3, 1, AlphaHandling::Premultiplied), &[0.0, 0.3, 0.9]);
+  assert_eq!(i.downsample_box(0, 0, AlphaHandling::Premultiplied).pixels.len(), 0);
+
+  // a straight alpha red pixel next to a transparent pixel that has a black
+  // color. Averaging them straight would darken the red.
+  let i = Bitmap {
+    width: 2,
+    height: 1,
+    pixels: vec![
+      r32g32b32a32_Sfloat { r: 1.0, g: 0.0, b: 0.0, a: 1.0 },
+      r32g32b32a32_Sfloat { r: 0.0, g: 0.0, b: 0.0, a: 0.0 },
+    ],
+  };
+  let d = i.downsample_box(1, 1, AlphaHandling::Straight);
+  assert_eq!(d.pixels, vec![r32g32b32a32_Sfloat { r: 1.0, g: 0.0, b: 0.0, a: 0.5 }]);
+  let d = i.downsample_box(1, 1, AlphaHandling::Premultiplied);
+  assert_eq!(d.pixels, vec![r32g32b32a32_Sfloat { r: 0.5, g: 0.0, b: 0.0, a: 0.5 }]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_flip_axis() {
+  use imagine::Axis;
+  let original = Bitmap { width: 3, height: 2, pixels: vec![1, 2, 3, 4, 5, 6] };
+
+  let mut i = original.clone();
+  i.flip(Axis::Horizontal);
+  assert_eq!(i.pixels, vec![3, 2, 1, 6, 5, 4]);
+
+  let mut i = original.clone();
+  i.flip(Axis::Vertical);
+  assert_eq!(i.pixels, vec![4, 5, 6, 1, 2, 3]);
+
+  let mut i = original.clone();
+  i.flip(Axis::Both);
+  assert_eq!(i.pixels, vec![6, 5, 4, 3, 2, 1]);
+  i.horizontal_flip();
+  i.vertical_flip();
+  assert_eq!(i, original);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_quantize_median_cut() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let px = |v: f32| r32g32b32a32_Sfloat { r: v, g: v * 0.5, b: 1.0 - v, a: 1.0 };
+  // a 64 step gradient down to 16 colors.
+  let i = Bitmap { width: 64, height: 1, pixels: (0..64).map(|x| px(x as f32 / 63.0)).collect() };
+  let q = i.quantize_median_cut(16);
+  assert_eq!((q.width, q.height), (64, 1));
+  assert_eq!(q.palette.len(), 16);
+  assert_eq!(q.indexes.len(), 64);
+  // every pixel is still close to where it started, and the gradient is still
+  // in order from one end to the other.
+  let back: Bitmap<r32g32b32a32_Sfloat> = Bitmap::from(&q);
+  for (a, b) in i.pixels.iter().zip(back.pixels.iter()) {
+    assert!((a.r - b.r).abs() < 0.04, "{a:?} {b:?}");
+  }
+  assert!(back.pixels.windows(2).all(|w| w[0].r <= w[1].r));
+
+  // fewer colors than asked for gives a smaller palette.
+  let two = Bitmap { width: 3, height: 1, pixels: vec![px(0.0), px(1.0), px(0.0)] };
+  let q = two.quantize_median_cut(256);
+  assert_eq!(q.palette.len(), 2);
+  assert_eq!(q.indexes[0], q.indexes[2]);
+  assert_ne!(q.indexes[0], q.indexes[1]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_sobel() {
+  use pixel_formats::{r32g32b32a32_Sfloat, r8_Unorm};
+  let b = r32g32b32a32_Sfloat::OPAQUE_BLACK;
+  let w = r32g32b32a32_Sfloat::OPAQUE_WHITE;
+  // black on the left, white on the right.
+  let i = Bitmap { width: 4, height: 3, pixels: [b, b, w, w].repeat(3) };
+  let edges = i.sobel();
+  assert_eq!((edges.width, edges.height), (4, 3));
+  // clamped borders mean the flat edges of the image don't count as edges.
+  let expected: Vec<r8_Unorm> =
+    [0, 255, 255, 0].repeat(3).into_iter().map(|r| r8_Unorm { r }).collect();
+  assert_eq!(edges.pixels, expected);
+
+  // a flat image has no edges at all.
+  let flat = Bitmap { width: 3, height: 3, pixels: vec![w; 9] };
+  assert!(flat.sobel().pixels.iter().all(|p| p.r == 0));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_gaussian_blur() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let b = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let w = r32g32b32a32_Sfloat::OPAQUE_WHITE;
+  // a single opaque white pixel in the middle of a transparent image.
+  let mut pixels = vec![b; 81];
+  pixels[40] = w;
+  let i = Bitmap { width: 9, height: 9, pixels };
+  let blurred = i.gaussian_blur(1.0);
+  assert_eq!((blurred.width, blurred.height), (9, 9));
+  // the blur spreads the pixel out evenly, and the total is kept.
+  let total: f32 = blurred.pixels.iter().map(|p| p.a).sum();
+  assert!((total - 1.0).abs() < 0.01, "{total}");
+  let c = blurred.pixels[40];
+  assert!(c.a < 1.0 && c.a > blurred.pixels[39].a);
+  assert_eq!(blurred.pixels[39], blurred.pixels[41]);
+  assert_eq!(blurred.pixels[31], blurred.pixels[49]);
+  // pre-multiplied, so the color is still white once un-multiplied.
+  assert!(blurred.pixels.iter().all(|p| (p.r - p.a).abs() < 0.0001));
+
+  // a flat image stays flat, even at the clamped edges.
+  let flat = Bitmap { width: 3, height: 2, pixels: vec![w; 6] };
+  assert!(flat.gaussian_blur(2.0).pixels.iter().all(|p| (p.g - 1.0).abs() < 0.0001));
+  // a zero sigma does nothing.
+  assert_eq!(i.gaussian_blur(0.0), i);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_convolve3x3() {
+  use imagine::BorderMode;
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let c = |r, g, b, a| r32g32b32a32_Sfloat { r, g, b, a };
+  let pixels: Vec<_> = (0..12).map(|i| c(i as f32 / 12.0, 0.5, 0.25, 1.0)).collect();
+  let i = Bitmap { width: 4, height: 3, pixels };
+  // the identity kernel does nothing, with any border.
+  let identity = [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]];
+  for border in [BorderMode::Clamp, BorderMode::Mirror, BorderMode::Zero] {
+    assert_eq!(i.convolve3x3(identity, 1.0, 0.0, border), i);
+  }
+  // a box blur of a flat image stays flat, unless the edges see zeroes.
+  let flat = Bitmap { width: 3, height: 3, pixels: vec![c(0.5, 0.5, 0.5, 1.0); 9] };
+  let ones = [[1.0; 3]; 3];
+  for border in [BorderMode::Clamp, BorderMode::Mirror] {
+    let out = flat.convolve3x3(ones, 9.0, 0.0, border);
+    assert!(out.pixels.iter().all(|p| (p.g - 0.5).abs() < 0.0001), "{border:?}");
+  }
+  let zero = flat.convolve3x3(ones, 9.0, 0.0, BorderMode::Zero);
+  assert!((zero.pixels[4].g - 0.5).abs() < 0.0001);
+  assert!((zero.pixels[0].g - 0.5 * 4.0 / 9.0).abs() < 0.0001);
+  assert!((zero.pixels[1].g - 0.5 * 6.0 / 9.0).abs() < 0.0001);
+  assert!(zero.pixels.iter().all(|p| p.a == 1.0));
+  // mirror and clamp differ at the edges of a gradient.
+  let left = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 0.0]];
+  assert_eq!(i.convolve3x3(left, 1.0, 0.0, BorderMode::Clamp).pixels[0], i.pixels[0]);
+  assert_eq!(i.convolve3x3(left, 1.0, 0.0, BorderMode::Mirror).pixels[0], i.pixels[1]);
+  // an edge detect kernel with a bias, and a zero divisor counts as 1.0
+  let edge = [[0.0, -1.0, 0.0], [-1.0, 4.0, -1.0], [0.0, -1.0, 0.0]];
+  let out = flat.convolve3x3(edge, 0.0, 0.25, BorderMode::Clamp);
+  assert!(out.pixels.iter().all(|p| (p.r - 0.25).abs() < 0.0001));
+  // alpha is kept, and the output is pre-multiplied.
+  let half = Bitmap { width: 2, height: 1, pixels: vec![c(0.4, 0.4, 0.4, 0.5); 2] };
+  let out = half.convolve3x3(ones, 9.0, 0.0, BorderMode::Clamp);
+  assert!(out.pixels.iter().all(|p| p.a == 0.5 && (p.r - 0.4).abs() < 0.0001));
+  // an empty image is returned as is.
+  let empty: Bitmap<r32g32b32a32_Sfloat> = Bitmap { width: 0, height: 0, pixels: vec![] };
+  assert_eq!(empty.convolve3x3(ones, 9.0, 0.0, BorderMode::Zero), empty);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_apply_lut() {
+  use pixel_formats::{r32g32b32_Sfloat, r32g32b32a32_Sfloat};
+  // red is inverted, green is squared, blue is left alone.
+  let lut: [r32g32b32_Sfloat; 256] = core::array::from_fn(|i| {
+    let v = i as f32 / 255.0;
+    r32g32b32_Sfloat { r: 1.0 - v, g: v * v, b: v }
+  });
+  let mut i = Bitmap {
+    width: 2,
+    height: 1,
+    pixels: vec![
+      r32g32b32a32_Sfloat { r: 0.0, g: 1.0, b: 0.25, a: 1.0 },
+      // half transparent, so the straight color is twice this.
+      r32g32b32a32_Sfloat { r: 0.1, g: 0.25, b: 0.5, a: 0.5 },
+    ],
+  };
+  i.apply_lut(&lut);
+  let [p0, p1] = [i.pixels[0], i.pixels[1]];
+  assert!((p0.r - 1.0).abs() < 0.0001 && (p0.g - 1.0).abs() < 0.0001, "{p0:?}");
+  assert!((p0.b - 0.25).abs() < 0.0001, "{p0:?}");
+  assert!((p1.r - 0.4).abs() < 0.0001 && (p1.g - 0.125).abs() < 0.001, "{p1:?}");
+  assert!((p1.b - 0.5).abs() < 0.0001 && p1.a == 0.5, "{p1:?}");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_detect_format_from_reader() {
+  use imagine::{detect_format, detect_format_from_reader, ImageFormat};
+  use std::io::{Cursor, Read};
+
+  let png = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  let mut r = Cursor::new(&png);
+  assert_eq!(detect_format_from_reader(&mut r).unwrap(), Some(ImageFormat::Png));
+  // only the start was read, the rest is left for the caller.
+  let mut rest = Vec::new();
+  r.read_to_end(&mut rest).unwrap();
+  assert_eq!(rest, png[16..]);
+
+  let mut r = Cursor::new(b"P6 1 1 255\n\0\0\0");
+  assert_eq!(detect_format_from_reader(&mut r).unwrap(), Some(ImageFormat::Netpbm));
+  // short data is fine.
+  let mut r = Cursor::new(b"BM");
+  assert_eq!(detect_format_from_reader(&mut r).unwrap(), Some(ImageFormat::Bmp));
+  let mut r = Cursor::new(b"GIF89a");
+  assert_eq!(detect_format_from_reader(&mut r).unwrap(), None);
+  assert_eq!(detect_format(&[]), None);
+}
+
+#[test]
+fn test_image_format_names() {
+  use imagine::ImageFormat;
+  assert_eq!(ImageFormat::Png.mime_type(), "image/png");
+  assert_eq!(ImageFormat::Bmp.mime_type(), "image/bmp");
+  assert_eq!(ImageFormat::Netpbm.mime_type(), "image/x-portable-anymap");
+  assert_eq!(ImageFormat::Png.preferred_extension(), "png");
+  assert_eq!(ImageFormat::Bmp.preferred_extension(), "bmp");
+  assert_eq!(ImageFormat::Netpbm.preferred_extension(), "pnm");
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_opaque_bounds() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let t = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let half = r32g32b32a32_Sfloat { r: 0.0, g: 0.0, b: 0.0, a: 0.5 };
+  let mut i = Bitmap { width: 5, height: 4, pixels: vec![t; 20] };
+  assert_eq!(i.opaque_bounds(), None);
+  i.pixels[5 + 3] = half;
+  assert_eq!(i.opaque_bounds(), Some((3, 1, 1, 1)));
+  i.pixels[2 * 5 + 1] = half;
+  assert_eq!(i.opaque_bounds(), Some((1, 1, 3, 2)));
+  i.pixels[19] = half;
+  assert_eq!(i.opaque_bounds(), Some((1, 1, 4, 3)));
+  let empty: Bitmap<r32g32b32a32_Sfloat> = Bitmap { width: 0, height: 0, pixels: vec![] };
+  assert_eq!(empty.opaque_bounds(), None);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_to_ascii() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let b = r32g32b32a32_Sfloat::OPAQUE_BLACK;
+  let w = r32g32b32a32_Sfloat::OPAQUE_WHITE;
+  let t = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  // 4x8, white and black on top, transparent and white on the bottom.
+  let mut pixels = Vec::new();
+  for y in 0..8 {
+    for x in 0..4 {
+      pixels.push(if y < 4 {
+        if x < 2 {
+          w
+        } else {
+          b
+        }
+      } else if x < 2 {
+        t
+      } else {
+        w
+      });
+    }
+  }
+  let i = Bitmap { width: 4, height: 8, pixels };
+  // each line of text covers two lines of pixels.
+  assert_eq!(i.to_ascii(4), "@@  \n@@  \n  @@\n  @@\n");
+  assert_eq!(i.to_ascii(2), "@ \n @\n");
+  assert_eq!(i.to_ascii(0), "");
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_sample_nearest_and_bilinear() {
+  use imagine::WrapMode;
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let px = |v: f32| r32g32b32a32_Sfloat { r: v, g: v, b: v, a: 1.0 };
+  let i = Bitmap { width: 2, height: 1, pixels: vec![px(0.0), px(1.0)] };
+
+  assert_eq!(i.sample_nearest(0.1, 0.5, WrapMode::Clamp), Some(px(0.0)));
+  assert_eq!(i.sample_nearest(0.6, 0.5, WrapMode::Clamp), Some(px(1.0)));
+  assert_eq!(i.sample_nearest(1.6, 0.5, WrapMode::Clamp), Some(px(1.0)));
+  assert_eq!(i.sample_nearest(1.1, 0.5, WrapMode::Repeat), Some(px(0.0)));
+  assert_eq!(i.sample_nearest(-0.1, 0.5, WrapMode::Clamp), Some(px(0.0)));
+  assert_eq!(i.sample_nearest(-0.1, 0.5, WrapMode::Repeat), Some(px(1.0)));
+
+  // pixel centers give exact pixels, halfway between gives the average.
+  assert_eq!(i.sample_bilinear(0.25, 0.5, WrapMode::Clamp), Some(px(0.0)));
+  assert_eq!(i.sample_bilinear(0.75, 0.5, WrapMode::Clamp), Some(px(1.0)));
+  assert_eq!(i.sample_bilinear(0.5, 0.5, WrapMode::Clamp), Some(px(0.5)));
+  // at the right edge, clamp stays on the last pixel but repeat blends back
+  // toward the first pixel.
+  assert_eq!(i.sample_bilinear(1.0, 0.5, WrapMode::Clamp), Some(px(1.0)));
+  assert_eq!(i.sample_bilinear(1.0, 0.5, WrapMode::Repeat), Some(px(0.5)));
+
+  let empty: Bitmap<r32g32b32a32_Sfloat> = Bitmap::default();
+  assert_eq!(empty.sample_nearest(0.5, 0.5, WrapMode::Clamp), None);
+  assert_eq!(empty.sample_bilinear(0.5, 0.5, WrapMode::Repeat), None);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_raw_dump_round_trip() {
+  use imagine::ImagineError;
+  use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Srgb};
+  let i = Bitmap {
+    width: 3,
+    height: 2,
+    pixels: (0..6_u8).map(|x| r8g8b8a8_Srgb { r: x, g: x * 2, b: x * 3, a: 255 }).collect(),
+  };
+  let dump = i.to_raw_dump();
+  assert_eq!(&dump[..8], b"imagine1");
+  assert_eq!(dump.len(), 8 + 12 + 6 * 4);
+  assert_eq!(Bitmap::<r8g8b8a8_Srgb>::from_raw_dump(&dump), Ok(i.clone()));
+  // the pixel bytes don't need to be aligned.
+  let mut shifted = vec![0_u8];
+  shifted.extend_from_slice(&dump);
+  assert_eq!(Bitmap::<r8g8b8a8_Srgb>::from_raw_dump(&shifted[1..]), Ok(i));
+
+  // the wrong pixel type, wrong lengths, and bad magic are all errors.
+  let r = Bitmap::<r32g32b32a32_Sfloat>::from_raw_dump(&dump);
+  assert_eq!(r, Err(ImagineError::Parse));
+  let r = Bitmap::<r8g8b8a8_Srgb>::from_raw_dump(&dump[..dump.len() - 1]);
+  assert_eq!(r, Err(ImagineError::Parse));
+  let mut bad = dump.clone();
+  bad[7] = b'2';
+  assert_eq!(Bitmap::<r8g8b8a8_Srgb>::from_raw_dump(&bad), Err(ImagineError::Parse));
+  assert_eq!(Bitmap::<r8g8b8a8_Srgb>::from_raw_dump(&[]), Err(ImagineError::Parse));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_set_channel_from() {
+  use imagine::{Channel, ImagineError};
+  use pixel_formats::{r32g32b32a32_Sfloat, r8_Unorm};
+  let c = r32g32b32a32_Sfloat { r: 1.0, g: 0.5, b: 0.0, a: 1.0 };
+  let mut i = Bitmap { width: 2, height: 1, pixels: vec![c; 2] };
+  let mask = Bitmap { width: 2, height: 1, pixels: vec![r8_Unorm { r: 255 }, r8_Unorm { r: 51 }] };
+  i.set_channel_from(Channel::A, &mask).unwrap();
+  // the alpha comes from the mask, and the color is pre-multiplied by it.
+  assert_eq!(i.pixels[0], c);
+  let p = i.pixels[1];
+  assert!((p.a - 0.2).abs() < 0.0001 && (p.r - 0.2).abs() < 0.0001, "{p:?}");
+  assert!((p.g - 0.1).abs() < 0.0001 && p.b == 0.0, "{p:?}");
+
+  // color channels are set in straight alpha form too.
+  i.set_channel_from(Channel::B, &mask).unwrap();
+  assert_eq!(i.pixels[0].b, 1.0);
+  assert!((i.pixels[1].b - 0.04).abs() < 0.0001, "{:?}", i.pixels[1]);
+
+  let wrong = Bitmap { width: 1, height: 2, pixels: vec![r8_Unorm { r: 0 }; 2] };
+  assert_eq!(i.set_channel_from(Channel::R, &wrong), Err(ImagineError::DimensionsMismatch));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_palmap_index_checks() {
+  use imagine::Palmap;
+  use pixel_formats::r8g8b8a8_Srgb;
+  let mut p: Palmap<u8, r8g8b8a8_Srgb> = Palmap {
+    width: 2,
+    height: 2,
+    indexes: vec![0, 3, 1, 2],
+    palette: vec![r8g8b8a8_Srgb::default(); 4],
+  };
+  assert_eq!(p.max_index_used(), Some(3));
+  assert!(p.is_valid());
+  p.indexes[0] = 12;
+  assert_eq!(p.max_index_used(), Some(12));
+  assert!(!p.is_valid());
+
+  let empty: Palmap<u8, r8g8b8a8_Srgb> =
+    Palmap { width: 0, height: 0, indexes: vec![], palette: vec![] };
+  assert_eq!(empty.max_index_used(), None);
+  assert!(empty.is_valid());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_invert() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let c = r32g32b32a32_Sfloat { r: 0.5, g: 0.25, b: 0.0, a: 0.5 };
+  let t = r32g32b32a32_Sfloat::TRANSPARENT_BLACK;
+  let mut i = Bitmap { width: 2, height: 1, pixels: vec![c, t] };
+  i.invert();
+  // straight color is (1.0, 0.5, 0.0), so it inverts to (0.0, 0.5, 1.0).
+  assert_eq!(i.pixels[0], r32g32b32a32_Sfloat { r: 0.0, g: 0.25, b: 0.5, a: 0.5 });
+  assert_eq!(i.pixels[1].a, 0.0);
+  i.invert();
+  assert_eq!(i.pixels[0], c);
+
+  i.invert_including_alpha();
+  assert_eq!(i.pixels[0], r32g32b32a32_Sfloat { r: 0.0, g: 0.25, b: 0.5, a: 0.5 });
+  assert_eq!(i.pixels[1], r32g32b32a32_Sfloat::OPAQUE_WHITE);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bitmap_find_pixel() {
+  use pixel_formats::r8g8b8a8_Srgb;
+  let bg = r8g8b8a8_Srgb { r: 0, g: 0, b: 0, a: 255 };
+  let red = r8g8b8a8_Srgb { r: 255, g: 0, b: 0, a: 255 };
+  let near_red = r8g8b8a8_Srgb { r: 250, g: 0, b: 0, a: 255 };
+  let mut pixels = vec![bg; 4 * 3];
+  pixels[6] = near_red;
+  pixels[9] = red;
+  let i = Bitmap { width: 4, height: 3, pixels };
+  assert_eq!(i.find_pixel(red, 0.0), Some((1, 2)));
+  assert_eq!(i.find_pixel(red, 0.05), Some((2, 1)));
+  assert_eq!(i.find_all_pixels(red, 0.05), vec![(2, 1), (1, 2)]);
+  assert_eq!(i.find_pixel(r8g8b8a8_Srgb { r: 0, g: 255, b: 0, a: 255 }, 0.5), None);
+  assert_eq!(i.find_all_pixels(bg, 0.0).len(), 10);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bitmap_is_valid_premultiplied() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let good = r32g32b32a32_Sfloat { r: 0.5, g: 0.25, b: 0.0, a: 0.5 };
+  let bad = r32g32b32a32_Sfloat { r: 0.6, g: 0.25, b: 0.0, a: 0.5 };
+  let mut i = Bitmap { width: 2, height: 2, pixels: vec![good; 4] };
+  assert!(i.is_valid_premultiplied(0.0));
+  assert!(i.downsample_box(1, 1, AlphaHandling::Premultiplied).is_valid_premultiplied(1e-6));
+  i.pixels[3] = bad;
+  assert!(!i.is_valid_premultiplied(0.0));
+  assert!(i.is_valid_premultiplied(0.1));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_posterize() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let c = r32g32b32a32_Sfloat { r: 0.4, g: 0.2, b: 0.05, a: 0.5 };
+  let mut i = Bitmap { width: 1, height: 1, pixels: vec![c] };
+  i.posterize(2);
+  // straight color is (0.8, 0.4, 0.1), which rounds to (1.0, 0.0, 0.0).
+  assert_eq!(i.pixels[0], r32g32b32a32_Sfloat { r: 0.5, g: 0.0, b: 0.0, a: 0.5 });
+
+  let mut i = Bitmap { width: 1, height: 1, pixels: vec![c] };
+  i.posterize(3);
+  // with 3 levels the steps are 0.0, 0.5, and 1.0.
+  assert_eq!(i.pixels[0], r32g32b32a32_Sfloat { r: 0.5, g: 0.25, b: 0.0, a: 0.5 });
+
+  let mut zero = Bitmap { width: 1, height: 1, pixels: vec![c] };
+  zero.posterize(0);
+  let mut two = Bitmap { width: 1, height: 1, pixels: vec![c] };
+  two.posterize(2);
+  assert_eq!(zero.pixels, two.pixels);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "png", feature = "miniz_oxide"))]
+fn test_try_animation_rgba_still_image() {
+  use imagine::{try_animation_rgba, try_bitmap_rgba, Animation, ImagineError};
+  use pixel_formats::r8g8b8a8_Srgb;
+  use std::time::Duration;
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  let animation: Animation<r8g8b8a8_Srgb> = try_animation_rgba(&v, true).unwrap();
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = try_bitmap_rgba(&v, true).unwrap();
+  assert_eq!(animation.frames.len(), 1);
+  assert_eq!(animation.frames[0].bitmap, bitmap);
+  assert_eq!(animation.frames[0].delay, Duration::ZERO);
+  assert_eq!(animation.loop_count, 1);
+
+  let r: Result<Animation<r8g8b8a8_Srgb>, _> = try_animation_rgba(&[], true);
+  assert_eq!(r.unwrap_err(), ImagineError::Parse);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_pad_to_pow2() {
+  let i = Bitmap { width: 3, height: 2, pixels: vec![1_u8, 2, 3, 4, 5, 6] };
+  let (padded, u_scale, v_scale) = i.pad_to_pow2(0);
+  assert_eq!((padded.width, padded.height), (4, 2));
+  assert_eq!(padded.pixels, vec![1, 2, 3, 0, 4, 5, 6, 0]);
+  assert_eq!((u_scale, v_scale), (0.75, 1.0));
+
+  let i = Bitmap { width: 5, height: 3, pixels: vec![9_u8; 15] };
+  let (padded, u_scale, v_scale) = i.pad_to_pow2(0);
+  assert_eq!((padded.width, padded.height), (8, 4));
+  assert_eq!(padded.pixels.iter().filter(|&&p| p == 9).count(), 15);
+  assert_eq!(padded.pixels[..8], [9, 9, 9, 9, 9, 0, 0, 0]);
+  assert_eq!(padded.pixels[24..], [0; 8]);
+  assert_eq!((u_scale, v_scale), (0.625, 0.75));
+
+  let empty: Bitmap<u8> = Bitmap::default();
+  let (padded, ..) = empty.pad_to_pow2(7);
+  assert_eq!((padded.width, padded.height, padded.pixels), (1, 1, vec![7]));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_mse_and_psnr() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let black = r32g32b32a32_Sfloat::OPAQUE_BLACK;
+  let a = Bitmap { width: 2, height: 1, pixels: vec![black; 2] };
+  assert_eq!(a.mse(&a), Some(0.0));
+  assert_eq!(a.psnr(&a), Some(f64::INFINITY));
+
+  let mut b = a.clone();
+  b.pixels[0] = r32g32b32a32_Sfloat { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+  // one channel of eight is off by 1.0
+  assert_eq!(b.mse(&a), Some(0.125));
+  let psnr = b.psnr(&a).unwrap();
+  assert!((psnr - 9.0309).abs() < 0.001, "{psnr}");
+
+  let c = Bitmap { width: 1, height: 2, pixels: vec![black; 2] };
+  assert_eq!(a.mse(&c), None);
+  assert_eq!(a.psnr(&c), None);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_concat() {
+  let a = Bitmap { width: 2, height: 2, pixels: vec![1_u8, 2, 3, 4] };
+  let b = Bitmap { width: 1, height: 3, pixels: vec![5_u8, 6, 7] };
+
+  let h = Bitmap::concat_h(&[a.clone(), b.clone()], 0).unwrap();
+  assert_eq!((h.width, h.height), (3, 3));
+  assert_eq!(h.pixels, vec![1, 2, 5, 3, 4, 6, 0, 0, 7]);
+
+  let v = Bitmap::concat_v(&[a.clone(), b.clone()], 0).unwrap();
+  assert_eq!((v.width, v.height), (2, 5));
+  assert_eq!(v.pixels, vec![1, 2, 3, 4, 5, 0, 6, 0, 7, 0]);
+
+  assert_eq!(Bitmap::concat_h(std::slice::from_ref(&a), 0).unwrap(), a);
+  assert_eq!(Bitmap::concat_v(std::slice::from_ref(&a), 0).unwrap(), a);
+  let empty: Bitmap<u8> = Bitmap::concat_h(&[], 0).unwrap();
+  assert_eq!(empty, Bitmap::default());
+  let empty: Bitmap<u8> = Bitmap::concat_v(&[], 0).unwrap();
+  assert_eq!(empty, Bitmap::default());
+
+  // missing pixels are filled in, rather than panicking.
+  let short = Bitmap { width: 2, height: 2, pixels: vec![8_u8, 9, 10] };
+  let h = Bitmap::concat_h(&[short.clone(), b.clone()], 0).unwrap();
+  assert_eq!(h.pixels, vec![8, 9, 5, 10, 0, 6, 0, 0, 7]);
+  let v = Bitmap::concat_v(&[b.clone(), short], 0).unwrap();
+  assert_eq!(v.pixels, vec![5, 0, 6, 0, 7, 0, 8, 9, 10, 0]);
+
+  // sizes that don't fit are an error, rather than wrapping around.
+  let wide = Bitmap { width: u32::MAX, height: 0, pixels: vec![] };
+  let tall = Bitmap { width: 0, height: u32::MAX, pixels: vec![] };
+  assert_eq!(
+    Bitmap::concat_h(&[wide.clone(), b.clone()], 0),
+    Err(imagine::ImagineError::CheckedMath)
+  );
+  assert_eq!(Bitmap::concat_v(&[tall, b], 0), Err(imagine::ImagineError::CheckedMath));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_apply_exif_orientation() {
+  // 1 2 3
+  // 4 5 6
+  let upright = Bitmap { width: 3, height: 2, pixels: vec![1_u8, 2, 3, 4, 5, 6] };
+  let mut t = upright.clone();
+  t.transpose();
+  assert_eq!((t.width, t.height, t.pixels), (2, 3, vec![1, 4, 2, 5, 3, 6]));
+
+  // each value has the image as the camera stored it, which the orientation
+  // should turn back into the upright image.
+  let stored: [(u8, u32, u32, Vec<u8>); 8] = [
+    (1, 3, 2, vec![1, 2, 3, 4, 5, 6]),
+    (2, 3, 2, vec![3, 2, 1, 6, 5, 4]),
+    (3, 3, 2, vec![6, 5, 4, 3, 2, 1]),
+    (4, 3, 2, vec![4, 5, 6, 1, 2, 3]),
+    (5, 2, 3, vec![1, 4, 2, 5, 3, 6]),
+    (6, 2, 3, vec![3, 6, 2, 5, 1, 4]),
+    (7, 2, 3, vec![6, 3, 5, 2, 4, 1]),
+    (8, 2, 3, vec![4, 1, 5, 2, 6, 3]),
+  ];
+  for (orientation, width, height, pixels) in stored {
+    let mut i = Bitmap { width, height, pixels };
+    i.apply_exif_orientation(orientation);
+    assert_eq!(i, upright, "{orientation}");
+  }
+  let mut i = upright.clone();
+  i.apply_exif_orientation(9);
+  assert_eq!(i, upright);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_apply_mask() {
+  use imagine::ImagineError;
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let red = r32g32b32a32_Sfloat { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+  let grey = r32g32b32a32_Sfloat { r: 0.5, g: 0.5, b: 0.5, a: 1.0 };
+  let mut i = Bitmap { width: 3, height: 1, pixels: vec![red; 3] };
+  let mask = Bitmap {
+    width: 3,
+    height: 1,
+    pixels: vec![r32g32b32a32_Sfloat::OPAQUE_WHITE, grey, r32g32b32a32_Sfloat::OPAQUE_BLACK],
+  };
+  i.apply_mask(&mask).unwrap();
+  assert_eq!(
+    i.pixels,
+    vec![
+      red,
+      r32g32b32a32_Sfloat { r: 0.5, g: 0.0, b: 0.0, a: 0.5 },
+      r32g32b32a32_Sfloat::TRANSPARENT_BLACK
+    ]
+  );
+
+  let small = Bitmap { width: 1, height: 1, pixels: vec![grey] };
+  assert_eq!(i.apply_mask(&small), Err(ImagineError::DimensionsMismatch));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_swizzle() {
+  use imagine::ChannelOrder;
+  use pixel_formats::r8g8b8a8_Srgb;
+  let px = |r, g, b, a| r8g8b8a8_Srgb { r, g, b, a };
+  let original = Bitmap { width: 2, height: 1, pixels: vec![px(1, 2, 3, 4), px(10, 20, 30, 40)] };
+
+  for (order, expected) in [
+    (ChannelOrder::RGBA, px(1, 2, 3, 4)),
+    (ChannelOrder::BGRA, px(3, 2, 1, 4)),
+    (ChannelOrder::ARGB, px(4, 1, 2, 3)),
+    (ChannelOrder::ABGR, px(4, 3, 2, 1)),
+  ] {
+    let mut b = original.clone();
+    b.swizzle(order);
+    assert_eq!(b.pixels[0], expected, "{order:?}");
+    b.unswizzle(order);
+    assert_eq!(b, original, "{order:?}");
+  }
+
+  for order in [ChannelOrder::BGRA, ChannelOrder::ABGR] {
+    let mut b = original.clone();
+    b.swizzle(order);
+    b.swizzle(order);
+    assert_eq!(b, original, "{order:?}");
+  }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_blend_pixel() {
+  use pixel_formats::r32g32b32a32_Sfloat;
+  let px = |r, g, b, a| r32g32b32a32_Sfloat { r, g, b, a };
+  let mut b = Bitmap { width: 2, height: 2, pixels: vec![px(0.0, 0.0, 1.0, 1.0); 4] };
+
+  // half opaque red, pre-multiplied
+  assert!(b.blend_pixel(1, 0, px(0.5, 0.0, 0.0, 0.5)));
+  assert_eq!(b.pixels[1], px(0.5, 0.0, 0.5, 1.0));
+  assert_eq!(b.pixels[0], px(0.0, 0.0, 1.0, 1.0));
+
+  // fully transparent does nothing, fully opaque replaces
+  assert!(b.blend_pixel(0, 1, px(0.0, 0.0, 0.0, 0.0)));
+  assert_eq!(b.pixels[2], px(0.0, 0.0, 1.0, 1.0));
+  assert!(b.blend_pixel(1, 1, px(0.0, 1.0, 0.0, 1.0)));
+  assert_eq!(b.pixels[3], px(0.0, 1.0, 0.0, 1.0));
+
+  let before = b.clone();
+  assert!(!b.blend_pixel(2, 0, px(1.0, 1.0, 1.0, 1.0)));
+  assert!(!b.blend_pixel(0, 2, px(1.0, 1.0, 1.0, 1.0)));
+  assert_eq!(b, before);
+}
+
+#[test]
+#[cfg(all(
+  feature = "alloc",
+  feature = "png",
+  feature = "bmp",
+  feature = "netpbm",
+  feature = "miniz_oxide"
+))]
+fn test_transcode_to_png() {
+  use imagine::{png::png_get_header, png::PngColorType, transcode_to_png, try_bitmap_rgba};
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  for (path, color_type) in [
+    ("tests/png/PngSuite-2017jul19/basn0g08.png", PngColorType::Y),
+    ("tests/png/PngSuite-2017jul19/basn2c08.png", PngColorType::RGB),
+    ("tests/png/PngSuite-2017jul19/tbbn3p08.png", PngColorType::RGBA),
+    ("tests/bmp/valid/24bpp-320x240.bmp", PngColorType::RGB),
+    ("tests/netpbm/p2.pgm", PngColorType::Y),
+    ("tests/netpbm/p3.ppm", PngColorType::RGB),
+  ] {
+    let bytes = std::fs::read(path).unwrap();
+    let png = transcode_to_png(&bytes).unwrap();
+    assert_eq!(png_get_header(&png).unwrap().color_type, color_type, "{path}");
+
+    let before: Bitmap<r8g8b8a8_Srgb> = try_bitmap_rgba(&bytes, true).unwrap();
+    let after: Bitmap<r8g8b8a8_Srgb> = try_bitmap_rgba(&png, true).unwrap();
+    assert_eq!((before.width, before.height), (after.width, after.height), "{path}");
+    for (b, a) in before.pixels.iter().zip(after.pixels.iter()) {
+      let close = |x: u8, y: u8| x.abs_diff(y) <= 1;
+      assert!(
+        close(b.r, a.r) && close(b.g, a.g) && close(b.b, a.b) && close(b.a, a.a),
+        "{path}: {b:?} vs {a:?}"
+      );
+    }
+  }
+
+  // an 8-bit sRGB source (color P6 data is sRGB) keeps every level exactly,
+  // both as grey and as color.
+  let mut ppm = b"P6 256 2 255\n".to_vec();
+  ppm.extend((0..=255_u8).flat_map(|v| [v, v, v]));
+  ppm.extend((0..=255_u8).flat_map(|v| [v, 255 - v, v / 2]));
+  for (rows, color_type) in [(1, PngColorType::Y), (2, PngColorType::RGB)] {
+    let mut src = ppm.clone();
+    src[7] = b'0' + rows;
+    src.truncate(src.len() - (2 - rows as usize) * 256 * 3);
+    let png = transcode_to_png(&src).unwrap();
+    assert_eq!(png_get_header(&png).unwrap().color_type, color_type);
+    let after: Bitmap<r8g8b8a8_Srgb> = try_bitmap_rgba(&png, true).unwrap();
+    let bytes: Vec<u8> = after.pixels.iter().flat_map(|p| [p.r, p.g, p.b]).collect();
+    assert_eq!(bytes[..], src[src.len() - bytes.len()..]);
+  }
+
+  assert!(transcode_to_png(b"not an image").is_err());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_crop_centered() {
+  // 5x4, each pixel is its own index
+  let b = Bitmap { width: 5, height: 4, pixels: (0_u8..20).collect::<Vec<_>>() };
+
+  let c = b.crop_centered(3, 2);
+  assert_eq!((c.width, c.height), (3, 2));
+  assert_eq!(c.pixels, vec![6, 7, 8, 11, 12, 13]);
+
+  // uneven leftover space cuts the extra from the right and bottom.
+  let c = b.crop_centered(2, 1);
+  assert_eq!((c.width, c.height), (2, 1));
+  assert_eq!(c.pixels, vec![6, 7]);
+
+  // square thumbnail crop of a wide image
+  let c = b.crop_centered(4, 4);
+  assert_eq!((c.width, c.height), (4, 4));
+  assert_eq!(c.pixels[..4], [0, 1, 2, 3]);
+
+  assert_eq!(b.crop_centered(100, 100), b);
+  let c = b.crop_centered(100, 2);
+  assert_eq!((c.width, c.height), (5, 2));
+  assert_eq!(c.pixels[..5], [5, 6, 7, 8, 9]);
+  assert_eq!(b.crop_centered(0, 0).pixels.len(), 0);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_split_at_row() {
+  let mut b = Bitmap { width: 3, height: 4, pixels: (0_u8..12).collect::<Vec<_>>() };
+
+  let (top, bottom) = b.split_at_row(1);
+  assert_eq!((top.width, top.height), (3, 1));
+  assert_eq!((bottom.width, bottom.height), (3, 3));
+  assert_eq!(top.pixels, &[0, 1, 2]);
+  assert_eq!(bottom.pixels[0], 3);
+
+  // the two halves can be worked on at the same time.
+  let (top, bottom) = b.split_at_row(2);
+  std::thread::scope(|s| {
+    s.spawn(|| top.pixels.iter_mut().for_each(|p| *p += 100));
+    s.spawn(move || {
+      let (mut third, mut fourth) = bottom.split_at_row(1);
+      *third.get_mut(0, 0).unwrap() = 0;
+      fourth.horizontal_flip();
+    });
+  });
+  assert_eq!(b.pixels, vec![100, 101, 102, 103, 104, 105, 0, 7, 8, 11, 10, 9]);
+
+  let (top, bottom) = b.split_at_row(99);
+  assert_eq!((top.height, top.pixels.len()), (4, 12));
+  assert_eq!((bottom.height, bottom.pixels.len()), (0, 0));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_with_border() {
+  use imagine::WrapMode;
+  // 3x2
+  // 0 1 2
+  // 3 4 5
+  let b = Bitmap { width: 3, height: 2, pixels: (0_u8..6).collect::<Vec<_>>() };
+
+  let m = b.with_mirror_border(1);
+  assert_eq!((m.width, m.height), (5, 4));
+  #[rustfmt::skip]
+  assert_eq!(m.pixels, vec![
+    4, 3, 4, 5, 4,
+    1, 0, 1, 2, 1,
+    4, 3, 4, 5, 4,
+    1, 0, 1, 2, 1,
+  ]);
+  assert_eq!(m.crop(1, 1, 3, 2), b);
+
+  let c = b.with_clamp_border(1);
+  #[rustfmt::skip]
+  assert_eq!(c.pixels, vec![
+    0, 0, 1, 2, 2,
+    0, 0, 1, 2, 2,
+    3, 3, 4, 5, 5,
+    3, 3, 4, 5, 5,
+  ]);
+
+  // padding wider than the image keeps reflecting back and forth.
+  let wide = b.with_mirror_border(4);
+  assert_eq!((wide.width, wide.height), (11, 10));
+  assert_eq!(wide.pixels[4 * 11..5 * 11], [0, 1, 2, 1, 0, 1, 2, 1, 0, 1, 2]);
+
+  assert_eq!(b.with_mirror_border(0), b);
+  let empty: Bitmap<u8> = Bitmap { width: 0, height: 0, pixels: vec![] };
+  assert_eq!(empty.with_clamp_border(3), empty);
+
+  let i = Bitmap { width: 3, height: 1, pixels: vec![0.0_f32, 1.0, 2.0] };
+  assert_eq!(i.sample_nearest(-0.1, 0.5, WrapMode::Mirror), Some(1.0));
+  assert_eq!(i.sample_nearest(1.1, 0.5, WrapMode::Mirror), Some(1.0));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_image_rgb565() {
+  use imagine::ImagineError;
+  use pixel_formats::r8g8b8a8_Srgb;
+  let px = |r, g, b| r8g8b8a8_Srgb { r, g, b, a: 255 };
+  let b = Bitmap {
+    width: 2,
+    height: 2,
+    pixels: vec![px(255, 0, 0), px(0, 255, 0), px(0, 0, 255), px(255, 255, 255)],
+  };
+  let packed = b.to_rgb565();
+  assert_eq!(packed, vec![0xF800, 0x07E0, 0x001F, 0xFFFF]);
+  assert_eq!(packed[0].to_le_bytes(), [0x00, 0xF8]);
+  let unpacked: Bitmap<r8g8b8a8_Srgb> = Bitmap::from_rgb565(&packed, 2, 2).unwrap();
+  assert_eq!(unpacked, b);
+
+  // every 565 value round trips exactly.
+  let all: Vec<u16> = (0..=u16::MAX).collect();
+  let big: Bitmap<r8g8b8a8_Srgb> = Bitmap::from_rgb565(&all, 256, 256).unwrap();
+  assert_eq!(big.to_rgb565(), all);
+
+  // a 5-bit step is about 8.2 and a 6-bit step is about 4.0, so these round
+  // to the nearest step below and above the halfway points.
+  let near = Bitmap { width: 2, height: 1, pixels: vec![px(4, 2, 4), px(5, 3, 5)] };
+  assert_eq!(near.to_rgb565(), vec![0x0000, (1 << 11) | (1 << 5) | 1]);
+
+  assert_eq!(
+    Bitmap::<r8g8b8a8_Srgb>::from_rgb565(&packed, 3, 2),
+    Err(ImagineError::DimensionsMismatch)
+  );
+}
@@ -1,11 +1,41 @@
 use walkdir::WalkDir;
 
+/// Makes the first 16 bytes of an info header, and zeroes the rest.
+///
+/// `size` is both the length of the output and the size written into it.
+fn info_header(size: u32, width: i32, height: i32, bits_per_pixel: u16) -> Vec<u8> {
+  let mut info = vec![0_u8; size as usize];
+  info[0..4].copy_from_slice(&size.to_le_bytes());
+  info[4..8].copy_from_slice(&width.to_le_bytes());
+  info[8..12].copy_from_slice(&height.to_le_bytes());
+  info[12..14].copy_from_slice(&1_u16.to_le_bytes());
+  info[14..16].copy_from_slice(&bits_per_pixel.to_le_bytes());
+  info
+}
+
+/// Puts a file header in front of an info header, palette, and pixel data.
+fn make_bmp(info: &[u8], palette: &[u8], data: &[u8]) -> Vec<u8> {
+  let offset = 14 + info.len() + palette.len();
+  let mut bmp = Vec::new();
+  bmp.extend_from_slice(b"BM");
+  bmp.extend_from_slice(&((offset + data.len()) as u32).to_le_bytes());
+  bmp.extend_from_slice(&[0; 4]);
+  bmp.extend_from_slice(&(offset as u32).to_le_bytes());
+  bmp.extend_from_slice(info);
+  bmp.extend_from_slice(palette);
+  bmp.extend_from_slice(data);
+  bmp
+}
+
 #[test]
 #[cfg(all(feature = "alloc"))]
 fn test_files_do_not_panic_decoder() {
   // iter ALL files in the test folder, even non-png files shouldn't panic it.
 
-  use imagine::{bmp::bmp_try_bitmap_rgba, Bitmap};
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, bmp_try_bitmap_rgba8},
+    Bitmap,
+  };
   use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Unorm};
   use std::ffi::OsStr;
 
@@ -22,7 +52,685 @@ fn test_files_do_not_panic_decoder() {
       }
     };
     let _: Option<Bitmap> = bmp_try_bitmap_rgba(&v, true).ok();
+    let _: Option<Bitmap<r8g8b8a8_Unorm>> = bmp_try_bitmap_rgba8(&v, true).ok();
     // Most test images are "hostile" so they naturally fail to parse.
     // However, the library shouldn't panic even with a hostile image.
   }
 }
+
+#[test]
+fn test_bmp_nice_header_summary() {
+  use imagine::bmp::nice_header::{bmp_get_nice_header, BmpCompression};
+
+  let v = std::fs::read("tests/bmp/valid/1bpp-topdown-320x240.bmp").unwrap();
+  let h = bmp_get_nice_header(&v).unwrap();
+  assert_eq!((h.width, h.height, h.bits_per_pixel), (320, 240, 1));
+  assert!(h.origin_top_left);
+  assert_eq!(h.compression, BmpCompression::RgbNoCompression);
+  assert_eq!(h.palette_len, 2);
+
+  let v = std::fs::read("tests/bmp/valid/24bpp-320x240.bmp").unwrap();
+  let h = bmp_get_nice_header(&v).unwrap();
+  assert_eq!((h.bits_per_pixel, h.origin_top_left, h.palette_len), (24, false, 0));
+
+  // when the header doesn't say, an indexed image gets a full palette.
+  let v = std::fs::read("tests/bmp/valid/8bpp-colorsused-zero.bmp").unwrap();
+  assert_eq!(bmp_get_nice_header(&v).unwrap().palette_len, 256);
+
+  // a direct color image only has a color table if it declares one.
+  let mut info = info_header(40, 1, 1, 24);
+  info[32..36].copy_from_slice(&3_u32.to_le_bytes());
+  let h = bmp_get_nice_header(&make_bmp(&info, &[0; 12], &[0; 4])).unwrap();
+  assert_eq!((h.bits_per_pixel, h.palette_len), (24, 3));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_os2_huffman1d() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, nice_header::*},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // lines are stored bottom up, and each bit string is one Modified Huffman code.
+  let lines: [&[&str]; 3] = [
+    // white 3, black 64 + 13
+    &["1000", "0000001111", "00000100"],
+    // fill and EOL, white 64 + 16
+    &["0000", "000000000001", "11011", "101010"],
+    // white 10, then EOL fills the rest of the line with white
+    &["00111", "000000000001"],
+  ];
+  let bits: String = lines.iter().flat_map(|line| line.iter().copied()).collect();
+  let data: Vec<u8> = bits
+    .as_bytes()
+    .chunks(8)
+    .map(|chunk| {
+      chunk.iter().enumerate().fold(0, |acc, (i, b)| acc | (u8::from(*b == b'1') << (7 - i)))
+    })
+    .collect();
+  let mut info = info_header(64, 80, 3, 1);
+  info[16..20].copy_from_slice(&3_u32.to_le_bytes());
+  let palette = [255_u8, 255, 255, 0, 0, 0, 0, 0];
+  let bmp = make_bmp(&info, &palette, &data);
+
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert_eq!(h.compression, BmpCompression::Huffman1D);
+  assert!(matches!(h.data_format, BmpDataFormat::Indexed1Huffman { .. }));
+
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bmp, true).unwrap();
+  let white = r8g8b8a8_Srgb { r: 255, g: 255, b: 255, a: 255 };
+  let black = r8g8b8a8_Srgb { r: 0, g: 0, b: 0, a: 255 };
+  let rows: Vec<&[r8g8b8a8_Srgb]> = bitmap.pixels.chunks(80).collect();
+  assert!(rows[0].iter().all(|p| *p == white));
+  assert!(rows[1].iter().all(|p| *p == white));
+  assert!(rows[2][..3].iter().all(|p| *p == white));
+  assert!(rows[2][3..].iter().all(|p| *p == black));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_cmyk_rle8() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, nice_header::*},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let mut info = info_header(40, 4, 1, 8);
+  info[16..20].copy_from_slice(&12_u32.to_le_bytes());
+  info[32..36].copy_from_slice(&3_u32.to_le_bytes());
+  // entries are [k, y, m, c]: white, cyan, and 50% black.
+  let palette = [0_u8, 0, 0, 0, 0, 0, 0, 255, 128, 0, 0, 0];
+  // run of 2 white, run of 1 cyan, run of 1 grey, end of line, end of bitmap.
+  let data = [2_u8, 0, 1, 1, 1, 2, 0, 0, 0, 1];
+  let bmp = make_bmp(&info, &palette, &data);
+
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert_eq!(h.compression, BmpCompression::CmykRLE8);
+  assert!(h.data_format.is_rle() && h.data_format.is_cmyk());
+
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bmp, true).unwrap();
+  let white = r8g8b8a8_Srgb { r: 255, g: 255, b: 255, a: 255 };
+  let cyan = r8g8b8a8_Srgb { r: 0, g: 255, b: 255, a: 255 };
+  let grey = r8g8b8a8_Srgb { r: 127, g: 127, b: 127, a: 255 };
+  assert_eq!(bitmap.pixels, vec![white, white, cyan, grey]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_to_bytes_round_trip() {
+  use imagine::{
+    bmp::{bmp_signature_is_correct, bmp_to_bytes, bmp_try_bitmap_rgba, BmpWriteOptions},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let px = |r, g, b| r8g8b8a8_Srgb { r, g, b, a: 255 };
+  // 3 wide so that each line needs padding.
+  let original: Bitmap<r8g8b8a8_Srgb> = Bitmap {
+    width: 3,
+    height: 2,
+    pixels: vec![
+      px(255, 0, 0),
+      px(0, 255, 0),
+      px(0, 0, 255),
+      px(10, 20, 30),
+      px(128, 128, 128),
+      px(255, 255, 255),
+    ],
+  };
+  for top_down in [false, true] {
+    let bytes = bmp_to_bytes(&original, BmpWriteOptions { top_down }).unwrap();
+    assert_eq!(original.to_bmp_bytes(BmpWriteOptions { top_down }).unwrap(), bytes);
+    assert!(bmp_signature_is_correct(&bytes));
+    assert_eq!(bytes.len(), 54 + 12 * 2);
+    let height = i32::from_le_bytes(bytes[22..26].try_into().unwrap());
+    assert_eq!(height, if top_down { -2 } else { 2 });
+    // the first stored row is the bottom row unless writing top down.
+    let first = if top_down { [0, 0, 255] } else { [30, 20, 10] };
+    assert_eq!(bytes[54..57], first);
+    for origin_top_left in [false, true] {
+      let mut decoded: Bitmap<r8g8b8a8_Srgb> =
+        bmp_try_bitmap_rgba(&bytes, origin_top_left).unwrap();
+      if !origin_top_left {
+        decoded.vertical_flip();
+      }
+      assert_eq!(decoded, original);
+    }
+  }
+
+  let bad = Bitmap { width: 2, height: 2, pixels: vec![px(0, 0, 0)] };
+  assert_eq!(
+    bmp_to_bytes(&bad, BmpWriteOptions::default()),
+    Err(imagine::ImagineError::DimensionsMismatch)
+  );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_v3_zeroed_masks_use_defaults() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, nice_header::*},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // a V3 header (56 bytes), 2x1, BI_BITFIELDS, all masks zero.
+  let bitfields_bmp = |bits_per_pixel: u16, data: &[u8]| {
+    let mut info = info_header(56, 2, 1, bits_per_pixel);
+    info[16..20].copy_from_slice(&3_u32.to_le_bytes());
+    make_bmp(&info, &[], data)
+  };
+
+  // pixels are stored as `[b, g, r, x]`.
+  let bmp = bitfields_bmp(32, &[30, 20, 10, 0, 255, 128, 0, 0]);
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert!(matches!(
+    h.data_format,
+    BmpDataFormat::Bitmask32RGB { r_mask: 0xFF0000, g_mask: 0xFF00, b_mask: 0xFF }
+  ));
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bmp, true).unwrap();
+  let expected = vec![
+    r8g8b8a8_Srgb { r: 10, g: 20, b: 30, a: 255 },
+    r8g8b8a8_Srgb { r: 0, g: 128, b: 255, a: 255 },
+  ];
+  assert_eq!(bitmap.pixels, expected);
+
+  // the same at 16bpp gives 555.
+  let bmp = bitfields_bmp(16, &[0, 0, 0, 0]);
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert!(matches!(
+    h.data_format,
+    BmpDataFormat::Bitmask16RGB {
+      r_mask: 0b0111_1100_0000_0000,
+      g_mask: 0b11111_00000,
+      b_mask: 0b11111
+    }
+  ));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_icc_profile_and_transform() {
+  use imagine::{
+    bmp::{bmp_get_icc_profile, bmp_try_bitmap_rgba_with_transform},
+    Bitmap,
+  };
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  // a V5 header (124 bytes), 1x1, 24bpp, with an embedded profile after the
+  // pixel data.
+  let profile = b"not a real profile";
+  let data = [0_u8, 51, 102, 0];
+  let mut info = info_header(124, 1, 1, 24);
+  info[56..60].copy_from_slice(&0x4D42_4544_u32.to_le_bytes());
+  info[112..116].copy_from_slice(&((124 + data.len()) as u32).to_le_bytes());
+  info[116..120].copy_from_slice(&(profile.len() as u32).to_le_bytes());
+  let bmp = make_bmp(&info, &[], &[&data[..], profile].concat());
+
+  assert_eq!(bmp_get_icc_profile(&bmp), Some(&profile[..]));
+  let bitmap: Bitmap<r32g32b32a32_Sfloat> =
+    bmp_try_bitmap_rgba_with_transform(&bmp, true, &super::AsLinear).unwrap();
+  // the transform sees exactly the stored values, with no round trip.
+  let p = bitmap.pixels[0];
+  assert_eq!(p, r32g32b32a32_Sfloat { r: 102.0 / 255.0, g: 51.0 / 255.0, b: 0.0, a: 1.0 });
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_huge_palette_len_errors() {
+  use imagine::{bmp::bmp_try_bitmap_rgba, Bitmap, ImagineError};
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // a 40 byte info header, 1x1, 8bpp, declaring `u32::MAX` palette entries.
+  let mut info = info_header(40, 1, 1, 8);
+  info[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+  let bmp = make_bmp(&info, &[0, 0, 255, 0], &[0; 4]);
+
+  // the palette's byte size doesn't fit in `u32`, on every target.
+  let result: Result<Bitmap<r8g8b8a8_Srgb>, _> = bmp_try_bitmap_rgba(&bmp, true);
+  assert_eq!(result.map(|b| b.pixels), Err(ImagineError::CheckedMath));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_indexed2() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, nice_header::*},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // a 40 byte info header, 5x2, 2bpp, with a 4 color palette.
+  let info = info_header(40, 5, 2, 2);
+  // palette entries are `[b, g, r, x]`: black, red, green, blue.
+  let palette = [0, 0, 0, 0, 0, 0, 255, 0, 0, 255, 0, 0, 255, 0, 0, 0];
+  // bottom line first, each line padded to 4 bytes.
+  let data = [0b0001_1011, 0b0000_0000, 0, 0, 0b1110_0100, 0b1100_0000, 0, 0];
+  let bmp = make_bmp(&info, &palette, &data);
+
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert!(matches!(h.data_format, BmpDataFormat::Indexed2 { .. }));
+  assert_eq!(h.palette_len, 4);
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bmp, true).unwrap();
+  let k = r8g8b8a8_Srgb { r: 0, g: 0, b: 0, a: 255 };
+  let r = r8g8b8a8_Srgb { r: 255, g: 0, b: 0, a: 255 };
+  let g = r8g8b8a8_Srgb { r: 0, g: 255, b: 0, a: 255 };
+  let b = r8g8b8a8_Srgb { r: 0, g: 0, b: 255, a: 255 };
+  assert_eq!(bitmap.pixels, vec![b, g, r, k, b, k, r, g, b, k]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_try_bitmap_rgba_oriented() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, bmp_try_bitmap_rgba_oriented},
+    Bitmap, Orientation,
+  };
+
+  for (name, expected) in [
+    ("24bpp-320x240.bmp", Orientation::BottomLeft),
+    ("24bpp-topdown-320x240.bmp", Orientation::TopLeft),
+  ] {
+    let v = std::fs::read(format!("tests/bmp/valid/{name}")).unwrap();
+    let (mut oriented, orientation): (Bitmap, _) = bmp_try_bitmap_rgba_oriented(&v).unwrap();
+    assert_eq!(orientation, expected, "{name}");
+    // flipping it ourselves gives the usual top left output.
+    if !orientation.is_top_left() {
+      oriented.vertical_flip();
+    }
+    let top_left: Bitmap = bmp_try_bitmap_rgba(&v, true).unwrap();
+    assert_eq!(oriented, top_left, "{name}");
+  }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_os2_short_header() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, nice_header::*},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // a 16 byte OS/2 2.x header, 2x1, 8bpp, which implies a 256 entry palette.
+  let info = info_header(16, 2, 1, 8);
+  // palette entries are `[b, g, r, x]`, with entry `i` being `(i, 0, 255-i)`.
+  let palette: Vec<u8> = (0..=255_u8).flat_map(|i| [255 - i, 0, i, 0]).collect();
+  let bmp = make_bmp(&info, &palette, &[1, 255, 0, 0]);
+
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert_eq!(h.compression, BmpCompression::RgbNoCompression);
+  assert_eq!(h.palette_len, 256);
+  assert!(matches!(h.data_format, BmpDataFormat::Indexed8 { palette_span: (30, 1054) }));
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bmp, true).unwrap();
+  let expected = vec![
+    r8g8b8a8_Srgb { r: 1, g: 0, b: 254, a: 255 },
+    r8g8b8a8_Srgb { r: 255, g: 0, b: 0, a: 255 },
+  ];
+  assert_eq!(bitmap.pixels, expected);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_extra_trailing_rows() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, nice_header::*},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // a 40 byte info header, 1x2, 24bpp, but with an `image_size` and pixel
+  // data that's 4 lines long instead of 2.
+  let mut info = info_header(40, 1, 2, 24);
+  info[20..24].copy_from_slice(&16_u32.to_le_bytes());
+  // lines are `[b, g, r]` plus 1 byte of padding, bottom line first.
+  let data = [0, 0, 255, 0, 255, 0, 0, 0, 0, 255, 0, 0, 9, 9, 9, 0];
+  let bmp = make_bmp(&info, &[], &data);
+  let offset = 14 + info.len();
+
+  let h = bmp_get_nice_header(&bmp).unwrap();
+  assert_eq!(h.data_span, (offset, offset + 8));
+  let bitmap: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bmp, true).unwrap();
+  let expected = vec![
+    r8g8b8a8_Srgb { r: 0, g: 0, b: 255, a: 255 },
+    r8g8b8a8_Srgb { r: 255, g: 0, b: 0, a: 255 },
+  ];
+  assert_eq!(bitmap.pixels, expected);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_palmap_to_bytes_round_trip() {
+  use imagine::{
+    bmp::{
+      bmp_palmap_to_bytes, bmp_try_bitmap_rgba, nice_header::bmp_get_nice_header, BmpWriteOptions,
+    },
+    Bitmap, Palmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let px = |r, g, b| r8g8b8a8_Srgb { r, g, b, a: 255 };
+  let colors = [px(255, 0, 0), px(0, 255, 0), px(0, 0, 255), px(10, 20, 30)];
+  // each palette size picks a different bit depth.
+  for (palette_len, bits_per_pixel) in [(2, 1), (4, 2), (5, 4), (20, 8)] {
+    let palette: Vec<r8g8b8a8_Srgb> = colors.iter().copied().cycle().take(palette_len).collect();
+    // 5 wide so that each line needs padding at every bit depth.
+    let palmap: Palmap<u8, r8g8b8a8_Srgb> = Palmap {
+      width: 5,
+      height: 3,
+      indexes: (0..15).map(|i| (i * 7 % palette_len) as u8).collect(),
+      palette,
+    };
+    let expected: Bitmap<r8g8b8a8_Srgb> = Bitmap::from(&palmap);
+    for top_down in [false, true] {
+      let bytes = bmp_palmap_to_bytes(&palmap, BmpWriteOptions { top_down }).unwrap();
+      assert_eq!(palmap.to_bmp_bytes(BmpWriteOptions { top_down }).unwrap(), bytes);
+      let h = bmp_get_nice_header(&bytes).unwrap();
+      assert_eq!((h.bits_per_pixel, h.palette_len), (bits_per_pixel, palette_len));
+      let decoded: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&bytes, true).unwrap();
+      assert_eq!(decoded, expected, "{palette_len} {top_down}");
+    }
+  }
+
+  let bad: Palmap<u8, r8g8b8a8_Srgb> =
+    Palmap { width: 2, height: 2, indexes: vec![0], palette: vec![px(0, 0, 0)] };
+  assert_eq!(
+    bmp_palmap_to_bytes(&bad, BmpWriteOptions::default()),
+    Err(imagine::ImagineError::DimensionsMismatch)
+  );
+}
+
+#[test]
+fn test_bmp_lenient_header_size() {
+  use imagine::{bmp::nice_header::*, ImagineError};
+
+  // a 2x1, 24bpp image with the info header size given as `declared`, and
+  // that many bytes of header (the extra bytes are junk).
+  let sized_bmp = |declared: u32| {
+    let mut info = info_header(declared, 2, 1, 24);
+    info[(declared as usize).min(40)..].fill(0xAA);
+    make_bmp(&info, &[], &[0, 0, 255, 255, 0, 0, 0, 0])
+  };
+
+  let bmp = sized_bmp(44);
+  assert_eq!(bmp_get_nice_header(&bmp).unwrap_err(), ImagineError::Parse);
+  let h = bmp_get_nice_header_lenient(&bmp).unwrap();
+  assert_eq!((h.width, h.height, h.bits_per_pixel), (2, 1, 24));
+  assert!(matches!(h.data_format, BmpDataFormat::BGR24));
+  assert_eq!(h.data_span, (14 + 44, 14 + 44 + 8));
+
+  // known sizes are the same either way
+  let bmp = sized_bmp(40);
+  let strict = bmp_get_nice_header(&bmp).unwrap();
+  let lenient = bmp_get_nice_header_lenient(&bmp).unwrap();
+  assert_eq!((strict.width, strict.data_span), (lenient.width, lenient.data_span));
+
+  // too small to be any windows header
+  let bmp = sized_bmp(36);
+  assert_eq!(bmp_get_nice_header_lenient(&bmp).unwrap_err(), ImagineError::Parse);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_dpi() {
+  use imagine::{
+    bmp::{bmp_to_bytes, nice_header::*, BmpWriteOptions},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let bitmap: Bitmap<r8g8b8a8_Srgb> =
+    Bitmap { width: 1, height: 1, pixels: vec![r8g8b8a8_Srgb::default()] };
+  let mut bytes = bmp_to_bytes(&bitmap, BmpWriteOptions::default()).unwrap();
+  let h = bmp_get_nice_header(&bytes).unwrap();
+  assert_eq!(h.pixels_per_meter, (2835, 2835));
+  let (x, y) = h.dpi().unwrap();
+  assert!((x - 72.0).abs() < 0.01 && (y - 72.0).abs() < 0.01, "{x} {y}");
+
+  // zero the vertical resolution
+  bytes[42..46].fill(0);
+  let h = bmp_get_nice_header(&bytes).unwrap();
+  assert_eq!(h.pixels_per_meter, (2835, 0));
+  assert_eq!(h.dpi(), None);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_try_packed_palmap_rgba() {
+  use imagine::{
+    bmp::{bmp_palmap_to_bytes, bmp_try_packed_palmap_rgba, BmpWriteOptions},
+    PackedPalmap, Palmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let px = |r, g, b| r8g8b8a8_Srgb { r, g, b, a: 255 };
+  let colors = [px(255, 0, 0), px(0, 255, 0), px(0, 0, 255), px(10, 20, 30)];
+  for (palette_len, bits_per_index) in [(2, 1), (4, 2), (5, 4), (20, 8)] {
+    let palette: Vec<r8g8b8a8_Srgb> = colors.iter().copied().cycle().take(palette_len).collect();
+    // 11 wide so that the last byte of each row is partly unused.
+    let palmap: Palmap<u8, r8g8b8a8_Srgb> = Palmap {
+      width: 11,
+      height: 3,
+      indexes: (0..33).map(|i| (i * 7 % palette_len) as u8).collect(),
+      palette,
+    };
+    for top_down in [false, true] {
+      let bytes = bmp_palmap_to_bytes(&palmap, BmpWriteOptions { top_down }).unwrap();
+      let packed: PackedPalmap<r8g8b8a8_Srgb> = bmp_try_packed_palmap_rgba(&bytes, true).unwrap();
+      assert_eq!(packed.bits_per_index, bits_per_index);
+      assert_eq!(packed.packed.len(), packed.bytes_per_row() * 3);
+      assert_eq!(packed.unpack_index(1, 0), Some(palmap.indexes[1]));
+      assert_eq!(packed.unpack_index(11, 0), None);
+      assert_eq!(Palmap::from(&packed), palmap, "{palette_len} {top_down}");
+
+      let mut flipped = palmap.clone();
+      flipped.vertical_flip();
+      let packed: PackedPalmap<r8g8b8a8_Srgb> = bmp_try_packed_palmap_rgba(&bytes, false).unwrap();
+      assert_eq!(Palmap::from(&packed), flipped);
+    }
+  }
+
+  // direct color images can't be packed.
+  let bitmap = imagine::Bitmap { width: 1, height: 1, pixels: vec![px(1, 2, 3)] };
+  let bytes = imagine::bmp::bmp_to_bytes(&bitmap, BmpWriteOptions::default()).unwrap();
+  let r: Result<PackedPalmap<r8g8b8a8_Srgb>, _> = bmp_try_packed_palmap_rgba(&bytes, true);
+  assert_eq!(r.unwrap_err(), imagine::ImagineError::Parse);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_huge_width_errors() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgb, bmp_try_bitmap_rgba, bmp_try_packed_palmap_rgba},
+    Bitmap, ImagineError, PackedPalmap,
+  };
+  use pixel_formats::{r8g8b8_Srgb, r8g8b8a8_Srgb};
+
+  // just a 40 byte info header claiming a huge width, with no data at all.
+  let huge_bmp = |width: i32, height: i32, bits_per_pixel: u16| {
+    make_bmp(&info_header(40, width, height, bits_per_pixel), &[], &[])
+  };
+
+  for (width, height) in [(2_000_000_000, 1), (1, 2_000_000_000), (i32::MAX, i32::MAX)] {
+    for bits_per_pixel in [8, 24] {
+      let bmp = huge_bmp(width, height, bits_per_pixel);
+      let r: Result<Bitmap<r8g8b8_Srgb>, _> = bmp_try_bitmap_rgb(&bmp, true);
+      assert_eq!(r.unwrap_err(), ImagineError::DimensionsTooLarge);
+      let r: Result<Bitmap<r8g8b8a8_Srgb>, _> = bmp_try_bitmap_rgba(&bmp, true);
+      assert_eq!(r.unwrap_err(), ImagineError::DimensionsTooLarge);
+    }
+    let bmp = huge_bmp(width, height, 8);
+    let r: Result<PackedPalmap<r8g8b8a8_Srgb>, _> = bmp_try_packed_palmap_rgba(&bmp, true);
+    assert_eq!(r.unwrap_err(), ImagineError::DimensionsTooLarge);
+  }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_try_bitmap_rgba8_matches_float_path() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, bmp_try_bitmap_rgba8},
+    Bitmap,
+  };
+  use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Unorm};
+
+  let mut files: Vec<(String, Vec<u8>)> = WalkDir::new("tests/bmp/valid/")
+    .into_iter()
+    .filter_map(|e| e.ok())
+    .filter(|e| !e.file_type().is_dir())
+    .map(|e| (e.path().display().to_string(), std::fs::read(e.path()).unwrap()))
+    .collect();
+  // none of the sample files are translucent, so make one with every alpha.
+  let mut v = std::fs::read("tests/bmp/valid/32bpp-400x400-rgba.bmp").unwrap();
+  let offset = u32::from_le_bytes(v[10..14].try_into().unwrap()) as usize;
+  v[offset..].chunks_exact_mut(4).enumerate().for_each(|(i, px)| px[3] = i as u8);
+  files.push(("translucent".to_string(), v));
+
+  let u8f = |c: f32| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+  let mut compared = 0;
+  let mut translucent = 0;
+  for (name, v) in files.iter() {
+    let Ok(float) = bmp_try_bitmap_rgba::<r32g32b32a32_Sfloat>(v, true) else { continue };
+    let fast: Bitmap<r8g8b8a8_Unorm> = bmp_try_bitmap_rgba8(v, true).unwrap();
+    assert_eq!((fast.width, fast.height), (float.width, float.height));
+    for (f, p) in fast.pixels.iter().zip(float.pixels.iter()) {
+      // the float path is pre-multiplied, so un-multiply it to compare.
+      let [r, g, b] = if p.a > 0.0 { [p.r / p.a, p.g / p.a, p.b / p.a] } else { [0.0; 3] };
+      let expected = [u8f(r), u8f(g), u8f(b), u8f(p.a)];
+      let close = [f.r, f.g, f.b, f.a].iter().zip(expected).all(|(&f, e)| f.abs_diff(e) <= 1);
+      assert!(close, "{name}: {f:?} vs {expected:?}");
+      translucent += usize::from(p.a > 0.0 && p.a < 1.0);
+    }
+    compared += 1;
+  }
+  assert!(compared > 10);
+  assert!(translucent > 0);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_assume_565() {
+  use imagine::{
+    bmp::{bmp_try_bitmap_rgba, bmp_try_bitmap_rgba_assume_565},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // this file declares 565 bitfields, so both decoders agree.
+  let v = std::fs::read("tests/bmp/valid/565-320x240.bmp").unwrap();
+  let expected: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&v, true).unwrap();
+  let assumed: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba_assume_565(&v, true).unwrap();
+  assert_eq!(assumed, expected);
+
+  // switch the compression to BI_RGB, which drops the masks.
+  let mut headerless = v.clone();
+  headerless[30..34].copy_from_slice(&0_u32.to_le_bytes());
+  let as_555: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&headerless, true).unwrap();
+  assert_ne!(as_555, expected);
+  let assumed: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba_assume_565(&headerless, true).unwrap();
+  assert_eq!(assumed, expected);
+
+  // other formats are decoded normally.
+  let v24 = std::fs::read("tests/bmp/valid/24bpp-320x240.bmp").unwrap();
+  let expected24: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&v24, true).unwrap();
+  let assumed24: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba_assume_565(&v24, true).unwrap();
+  assert_eq!(assumed24, expected24);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_get_suggested_palette() {
+  use imagine::bmp::bmp_get_suggested_palette;
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let px = |r, g, b| r8g8b8a8_Srgb { r, g, b, a: 255 };
+  let expected =
+    [px(255, 0, 255), px(0, 0, 0), px(255, 0, 0), px(0, 255, 0), px(0, 0, 255), px(255, 255, 255)];
+  // one table comes right after the header, the other after the bitfield masks.
+  for name in ["32bpp-optimalpalette-320x240", "32bpp-888-optimalpalette-320x240"] {
+    let v = std::fs::read(format!("tests/bmp/valid/{name}.bmp")).unwrap();
+    assert_eq!(bmp_get_suggested_palette(&v).unwrap(), expected, "{name}");
+  }
+
+  let v = std::fs::read("tests/bmp/valid/24bpp-320x240.bmp").unwrap();
+  assert_eq!(bmp_get_suggested_palette(&v), None);
+
+  // indexed images just give their normal palette.
+  let v = std::fs::read("tests/bmp/valid/8bpp-320x240.bmp").unwrap();
+  let palette = bmp_get_suggested_palette(&v).unwrap();
+  assert!(!palette.is_empty() && palette.len() <= 256);
+
+  assert_eq!(bmp_get_suggested_palette(&v[..60]), None);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_bmp_v4_and_v5_headers() {
+  use imagine::{
+    bmp::{
+      bmp_try_bitmap_rgba,
+      nice_header::{bmp_get_nice_header, BmpDataFormat},
+    },
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // Rebuilds a file that has a 40 byte info header with a V4 (108 byte) or V5
+  // (124 byte) header instead, using the sRGB colorspace.
+  let upgrade = |v: &[u8], header_size: usize, masks: Option<[u32; 3]>| {
+    let grow = header_size - 40;
+    let mut out = v[..54].to_vec();
+    out[14..18].copy_from_slice(&(header_size as u32).to_le_bytes());
+    if let Some(masks) = masks {
+      out[30..34].copy_from_slice(&3_u32.to_le_bytes());
+      masks.iter().for_each(|m| out.extend_from_slice(&m.to_le_bytes()));
+    } else {
+      out.extend_from_slice(&[0; 12]);
+    }
+    // alpha mask, then the colorspace type
+    out.extend_from_slice(&0_u32.to_le_bytes());
+    out.extend_from_slice(b"BGRs");
+    out.resize(14 + header_size, 0);
+    out.extend_from_slice(&v[54..]);
+    let offset = u32::from_le_bytes(v[10..14].try_into().unwrap()) as usize + grow;
+    out[10..14].copy_from_slice(&(offset as u32).to_le_bytes());
+    let file_size = out.len() as u32;
+    out[2..6].copy_from_slice(&file_size.to_le_bytes());
+    out
+  };
+
+  let v24 = std::fs::read("tests/bmp/valid/24bpp-320x240.bmp").unwrap();
+  let v32 = std::fs::read("tests/bmp/valid/32bpp-320x240.bmp").unwrap();
+  let expected: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&v24, true).unwrap();
+  // 32bpp data without bitfields is the same as the standard masks.
+  let from_32: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&v32, true).unwrap();
+  assert_eq!(from_32, expected);
+
+  for header_size in [108, 124] {
+    let v = upgrade(&v24, header_size, None);
+    assert!(matches!(bmp_get_nice_header(&v).unwrap().data_format, BmpDataFormat::BGR24));
+    let actual: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&v, true).unwrap();
+    assert_eq!(actual, expected, "24bpp {header_size}");
+
+    for masks in [None, Some([0xFF << 16, 0xFF << 8, 0xFF])] {
+      let v = upgrade(&v32, header_size, masks);
+      assert!(matches!(
+        bmp_get_nice_header(&v).unwrap().data_format,
+        BmpDataFormat::Bitmask32RGB { r_mask: 0xFF0000, g_mask: 0xFF00, b_mask: 0xFF }
+      ));
+      let actual: Bitmap<r8g8b8a8_Srgb> = bmp_try_bitmap_rgba(&v, true).unwrap();
+      assert_eq!(actual, expected, "32bpp {header_size} {masks:?}");
+    }
+  }
+
+  // a V5 file straight from an editor, with an alpha mask.
+  let v = std::fs::read("tests/bmp/valid/32bpp-400x400-rgba.bmp").unwrap();
+  assert!(matches!(
+    bmp_get_nice_header(&v).unwrap().data_format,
+    BmpDataFormat::Bitmask32RGBA { .. }
+  ));
+  assert!(bmp_try_bitmap_rgba::<r8g8b8a8_Srgb>(&v, true).is_ok());
+}
@@ -0,0 +1,209 @@
+use imagine::netpbm::*;
+
+#[test]
+fn test_netpbm_ascii_value_at_eof() {
+  let v = std::fs::read("tests/netpbm/p2_no_trailing_newline.pgm").unwrap();
+  assert_ne!(v.last(), Some(&b'\n'));
+  let (header, rest) = netpbm_pull_header(&v).unwrap();
+  assert_eq!((header.tag, header.width, header.height, header.max), (2, 3, 2, 9));
+  assert_eq!(netpbm_iter_p2(rest).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 9]);
+
+  let (_, rest) = netpbm_pull_header(b"P3 1 2 255 1 2 3 4 5 6").unwrap();
+  assert_eq!(netpbm_iter_p3(rest).collect::<Vec<_>>(), vec![[1, 2, 3], [4, 5, 6]]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_netpbm_p2_last_pixel_at_eof() {
+  use imagine::Bitmap;
+  use pixel_formats::r32g32b32_Sfloat;
+
+  let v = std::fs::read("tests/netpbm/p2_no_trailing_newline.pgm").unwrap();
+  let bitmap: Bitmap<r32g32b32_Sfloat> = netpbm_try_bitmap_rgb(&v).unwrap();
+  assert_eq!(bitmap.pixels.len(), 6);
+  assert_eq!(bitmap.pixels[5], r32g32b32_Sfloat::WHITE);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_netpbm_binary_missing_pixel_data() {
+  use imagine::{Bitmap, ImagineError};
+  use pixel_formats::r32g32b32_Sfloat;
+
+  for bytes in [&b"P6 1 1 255"[..], b"P6 1 1 255\n", b"P5 2 2 255\n", b"P4 8 1\n", b"P4 8 1"] {
+    assert_eq!(netpbm_for_each_rgb(bytes, |_| ()), Err(ImagineError::Parse));
+    let r: Result<Bitmap<r32g32b32_Sfloat>, _> = netpbm_try_bitmap_rgb(bytes);
+    assert_eq!(r, Err(ImagineError::Parse));
+  }
+
+  // partial data is still allowed, the rest of the image is black.
+  let bitmap: Bitmap<r32g32b32_Sfloat> =
+    netpbm_try_bitmap_rgb(b"P6 2 1 255\n\xFF\xFF\xFF").unwrap();
+  assert_eq!(bitmap.pixels, vec![r32g32b32_Sfloat::WHITE, r32g32b32_Sfloat::BLACK]);
+}
+
+#[test]
+fn test_netpbm_binary_data_starts_after_one_whitespace() {
+  // the pixel data contains `\n` (10), which must not be skipped over.
+  let (header, rest) = netpbm_pull_header(b"P5 3 1 255\n\x0A\x0B\x0C").unwrap();
+  assert_eq!((header.tag, header.width, header.height, header.max), (5, 3, 1, 255));
+  assert_eq!(rest, b"\x0A\x0B\x0C");
+  let (_, rest) = netpbm_pull_header(b"P4\n8 1 \x0A").unwrap();
+  assert_eq!(rest, b"\x0A");
+}
+
+#[test]
+fn test_netpbm_header_accessors() {
+  let info = |bytes: &[u8]| {
+    let (h, _) = netpbm_pull_header(bytes).unwrap();
+    (h.channel_count(), h.is_ascii(), h.bytes_per_pixel())
+  };
+  assert_eq!(info(b"P1 1 1 0"), (1, true, None));
+  assert_eq!(info(b"P2 1 1 9 0"), (1, true, None));
+  assert_eq!(info(b"P3 1 1 9 0 0 0"), (3, true, None));
+  assert_eq!(info(b"P4 8 1 \x00"), (1, false, None));
+  assert_eq!(info(b"P5 1 1 255 \x00"), (1, false, Some(1)));
+  assert_eq!(info(b"P6 1 1 255 \x00\x00\x00"), (3, false, Some(3)));
+  assert_eq!(info(b"P6 1 1 65535 \x00\x00\x00\x00\x00\x00"), (3, false, Some(6)));
+  assert_eq!(info(b"P6 1 1 65536 \x00\x00\x00\x00\x00\x00"), (3, false, None));
+  assert!(!netpbm_pull_header(b"P6 1 1 255 \x00\x00\x00").unwrap().0.is_float());
+
+  // the float formats aren't decoded, but the accessors know about them.
+  let float = |tag: u8| NetpbmHeader { tag, width: 1, height: 1, max: 1 };
+  let (rgb, gray) = (float(NetpbmHeader::TAG_FLOAT_RGB), float(NetpbmHeader::TAG_FLOAT_GRAY));
+  assert_eq!(netpbm_pull_tag(b"PF").unwrap().0, NetpbmHeader::TAG_FLOAT_RGB);
+  assert!(rgb.is_float() && gray.is_float() && !rgb.is_ascii());
+  assert_eq!((rgb.channel_count(), rgb.bytes_per_pixel()), (3, Some(12)));
+  assert_eq!((gray.channel_count(), gray.bytes_per_pixel()), (1, Some(4)));
+}
+
+#[test]
+fn test_netpbm_16_bit_samples() {
+  use imagine::ImagineError;
+  use pixel_formats::r32g32b32_Sfloat;
+
+  // the stride from the header matches what the iterators read.
+  let ppm = b"P6 2 1 65535\n\xFF\xFF\x80\x00\x00\x00\x00\x00\xFF\xFF\x00\x00";
+  let (header, rest) = netpbm_pull_header(ppm).unwrap();
+  assert_eq!(rest.len(), header.bytes_per_pixel().unwrap() * 2);
+  let samples: Vec<[u16; 3]> = netpbm_iter_p6_16(rest).collect();
+  assert_eq!(samples, vec![[0xFFFF, 0x8000, 0], [0, 0xFFFF, 0]]);
+
+  let mut pixels = Vec::new();
+  netpbm_for_each_rgb_with(ppm, ColorAssumption::Linear, |p| pixels.push(p)).unwrap();
+  let half = 32768.0 / 65535.0;
+  assert_eq!(pixels[0], r32g32b32_Sfloat { r: 1.0, g: half, b: 0.0 });
+  assert_eq!(pixels[1], r32g32b32_Sfloat { r: 0.0, g: 1.0, b: 0.0 });
+
+  let mut pixels = Vec::new();
+  netpbm_for_each_rgb(b"P5 2 1 1000\n\x01\xF4\x03\xE8", |p| pixels.push(p)).unwrap();
+  assert_eq!(pixels[0], r32g32b32_Sfloat { r: 0.5, g: 0.5, b: 0.5 });
+  assert_eq!(pixels[1], r32g32b32_Sfloat::WHITE);
+
+  let r = netpbm_for_each_rgb(b"P5 1 1 65536\n\x00\x00\x00", |_| ());
+  assert_eq!(r, Err(ImagineError::Parse));
+}
+
+#[test]
+fn test_netpbm_samples_over_max_are_clamped() {
+  use pixel_formats::r32g32b32_Sfloat;
+
+  let half = r32g32b32_Sfloat { r: 0.5, g: 0.5, b: 0.5 };
+  let mut pixels = Vec::new();
+  let ppm = b"P6 2 1 100\n\x32\x32\x32\xC8\x65\xFF";
+  netpbm_for_each_rgb_with(ppm, ColorAssumption::Linear, |p| pixels.push(p)).unwrap();
+  assert_eq!(pixels, vec![half, r32g32b32_Sfloat::WHITE]);
+
+  let mut pixels = Vec::new();
+  netpbm_for_each_rgb(b"P2 2 1 4 2 9", |p| pixels.push(p)).unwrap();
+  assert_eq!(pixels, vec![half, r32g32b32_Sfloat::WHITE]);
+
+  let mut pixels = Vec::new();
+  netpbm_for_each_rgb_with(b"P3 1 1 10 5 11 5", ColorAssumption::Linear, |p| pixels.push(p))
+    .unwrap();
+  assert_eq!(pixels, vec![r32g32b32_Sfloat { r: 0.5, g: 1.0, b: 0.5 }]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_netpbm_write_pam() {
+  use imagine::{Bitmap, ImagineError};
+  use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Srgb};
+
+  let pixels = vec![
+    r8g8b8a8_Srgb { r: 255, g: 0, b: 0, a: 255 },
+    r8g8b8a8_Srgb { r: 0, g: 0, b: 0, a: 0 },
+    r8g8b8a8_Srgb { r: 10, g: 20, b: 30, a: 255 },
+    r8g8b8a8_Srgb { r: 0, g: 255, b: 0, a: 255 },
+  ];
+  // the linear, pre-multiplied form, like the decoders give.
+  let linear: Vec<r32g32b32a32_Sfloat> = pixels.iter().map(|&p| p.into()).collect();
+  let bitmap = Bitmap { width: 2, height: 2, pixels: linear };
+  let bytes = netpbm_write_pam(&bitmap).unwrap();
+  let header = b"P7\nWIDTH 2\nHEIGHT 2\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n";
+  assert_eq!(bytes[..header.len()], header[..]);
+  // there's no P7 reader, so the data is split up by hand.
+  let data: Vec<r8g8b8a8_Srgb> = bytes[header.len()..]
+    .chunks_exact(4)
+    .map(|c| r8g8b8a8_Srgb { r: c[0], g: c[1], b: c[2], a: c[3] })
+    .collect();
+  assert_eq!(data, pixels);
+
+  // partial alpha is stored straight.
+  let half = r32g32b32a32_Sfloat { r: 0.5, g: 0.0, b: 0.0, a: 0.5 };
+  let bytes = netpbm_write_pam(&Bitmap { width: 1, height: 1, pixels: vec![half] }).unwrap();
+  assert_eq!(bytes[bytes.len() - 4..], [255, 0, 0, 128]);
+  assert_eq!(netpbm_pull_header(&bytes).unwrap_err(), ImagineError::Parse);
+
+  let bad = Bitmap { width: 2, height: 2, pixels: vec![half] };
+  assert_eq!(netpbm_write_pam(&bad), Err(ImagineError::DimensionsMismatch));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_netpbm_color_assumption() {
+  use imagine::Bitmap;
+  use pixel_formats::{r32g32b32_Sfloat, r32g32b32a32_Sfloat, r8g8b8_Srgb, r8g8b8a8_Srgb};
+
+  let ppm = b"P6 2 1 255\n\x80\x40\x20\xFF\x00\x10";
+  let (header, _) = netpbm_pull_header(ppm).unwrap();
+  assert_eq!(ColorAssumption::for_header(&header), ColorAssumption::Srgb);
+  let srgb: Bitmap<r8g8b8a8_Srgb> =
+    netpbm_try_bitmap_rgba_with(ppm, true, ColorAssumption::Srgb).unwrap();
+  assert_eq!(srgb.pixels[0], r8g8b8a8_Srgb { r: 0x80, g: 0x40, b: 0x20, a: 255 });
+  assert_eq!(srgb.pixels[1], r8g8b8a8_Srgb { r: 0xFF, g: 0x00, b: 0x10, a: 255 });
+  // the plain decoder uses sRGB for color data.
+  let plain: Bitmap<r8g8b8a8_Srgb> = netpbm_try_bitmap_rgba(ppm, true).unwrap();
+  assert_eq!(srgb, plain);
+
+  // the rgb paths decode the same way.
+  let rgb: Bitmap<r8g8b8_Srgb> = netpbm_try_bitmap_rgb(ppm).unwrap();
+  assert_eq!(rgb.pixels[0], r8g8b8_Srgb { r: 0x80, g: 0x40, b: 0x20 });
+  let mut first = None;
+  netpbm_for_each_rgb(ppm, |p| {
+    first.get_or_insert(p);
+  })
+  .unwrap();
+  assert_eq!(r8g8b8_Srgb::from(first.unwrap()), rgb.pixels[0]);
+  let linear: Bitmap<r32g32b32_Sfloat> =
+    netpbm_try_bitmap_rgb_with(ppm, ColorAssumption::Linear).unwrap();
+  assert_eq!(linear.pixels[0].r, 128.0 / 255.0);
+
+  // and linear for grayscale data.
+  let pgm = b"P5 2 1 255\n\x80\xFF";
+  let (header, _) = netpbm_pull_header(pgm).unwrap();
+  assert_eq!(ColorAssumption::for_header(&header), ColorAssumption::Linear);
+  let linear: Bitmap<r32g32b32a32_Sfloat> =
+    netpbm_try_bitmap_rgba_with(pgm, true, ColorAssumption::Linear).unwrap();
+  let plain: Bitmap<r32g32b32a32_Sfloat> = netpbm_try_bitmap_rgba(pgm, true).unwrap();
+  assert_eq!(linear, plain);
+  assert_eq!(linear.pixels[0].r, 128.0 / 255.0);
+
+  // both curves darken the mid tones, and leave the ends alone.
+  let rec709: Bitmap<r32g32b32a32_Sfloat> =
+    netpbm_try_bitmap_rgba_with(pgm, true, ColorAssumption::Rec709).unwrap();
+  assert!(rec709.pixels[0].r < linear.pixels[0].r);
+  assert!((rec709.pixels[1].r - 1.0).abs() < 1e-6);
+  assert_eq!(ColorAssumption::Rec709.decode(0.0), 0.0);
+  assert_eq!(ColorAssumption::Srgb.decode(0.0), 0.0);
+}
@@ -1,6 +1,20 @@
 use imagine::png::PngRawChunkIter;
 use walkdir::WalkDir;
 
+/// Copies a PNG with an extra chunk put right after the `IHDR`.
+///
+/// The chunk's CRC is left as zero, since the decoders don't check it.
+fn with_chunk_after_ihdr(png: &[u8], chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+  // the signature is 8 bytes, and the IHDR chunk is 25 bytes.
+  let mut out = png[..33].to_vec();
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  out.extend_from_slice(chunk_type);
+  out.extend_from_slice(data);
+  out.extend_from_slice(&[0; 4]);
+  out.extend_from_slice(&png[33..]);
+  out
+}
+
 #[test]
 #[cfg(feature = "png")]
 fn test_RawPngChunkIter_no_panics() {
@@ -39,3 +53,968 @@ fn test_files_do_not_panic_decoder() {
     // However, the library shouldn't panic even with a hostile image.
   }
 }
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_try_bitmap_gray_packed() {
+  use imagine::png::png_try_bitmap_gray_packed;
+
+  for depth in ["01", "02", "04", "08", "16"] {
+    let n = std::fs::read(format!("tests/png/PngSuite-2017jul19/basn0g{depth}.png")).unwrap();
+    let i = std::fs::read(format!("tests/png/PngSuite-2017jul19/basi0g{depth}.png")).unwrap();
+    let (packed, width, height, bit_depth) = png_try_bitmap_gray_packed(&n).unwrap();
+    assert_eq!((width, height, u32::from(bit_depth)), (32, 32, depth.parse().unwrap()));
+    assert_eq!(packed.len(), (32 * usize::from(bit_depth) / 8) * 32);
+    // de-interlacing must give back the same packed lines.
+    assert_eq!(png_try_bitmap_gray_packed(&i).unwrap().0, packed);
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_indexed_16bit_is_parse_error() {
+  use imagine::{
+    png::{png_get_header, png_try_bitmap_rgba},
+    Bitmap, ImagineError,
+  };
+
+  let mut v = std::fs::read("tests/png/PngSuite-2017jul19/basn3p08.png").unwrap();
+  // signature (8), chunk length and type (8), then width and height (8).
+  assert_eq!(&v[12..16], b"IHDR");
+  assert_eq!(v[24], 8);
+  v[24] = 16;
+  assert!(png_get_header(&v).is_none());
+  let r: Result<Bitmap, ImagineError> = png_try_bitmap_rgba(&v, true);
+  assert_eq!(r.unwrap_err(), ImagineError::Parse);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_decode_into_borrowed() {
+  use imagine::{
+    png::{png_decode_into_borrowed, png_get_header, png_try_bitmap_rgba},
+    Bitmap, BorrowedBitmap, ImagineError, UnsupportedFormat,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  for name in ["basn2c08", "basi3p04", "basn4a16", "tbrn2c08"] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+    let expected: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&v, true).unwrap();
+    let ihdr = png_get_header(&v).unwrap();
+    let mut scratch = vec![0_u8; ihdr.get_zlib_decompression_requirement()];
+    let mut pixels = vec![r8g8b8a8_Srgb::default(); expected.pixels.len()];
+    let mut borrowed = BorrowedBitmap { width: 32, height: 32, pixels: &mut pixels };
+    png_decode_into_borrowed(&v, &mut borrowed, &mut scratch).unwrap();
+    assert_eq!(pixels, expected.pixels, "{name}");
+
+    let mut borrowed = BorrowedBitmap { width: 32, height: 31, pixels: &mut pixels };
+    let r = png_decode_into_borrowed(&v, &mut borrowed, &mut scratch);
+    assert_eq!(r, Err(ImagineError::DimensionsMismatch));
+    let mut borrowed = BorrowedBitmap { width: 32, height: 32, pixels: &mut pixels };
+    let r = png_decode_into_borrowed(&v, &mut borrowed, &mut scratch[1..]);
+    assert_eq!(r, Err(ImagineError::BufferTooSmall));
+
+    let mut mng = v.clone();
+    mng[..4].copy_from_slice(b"\x8AMNG");
+    let r = png_decode_into_borrowed(&mng, &mut borrowed, &mut scratch);
+    assert_eq!(r, Err(ImagineError::Unsupported(UnsupportedFormat::MngOrJng)));
+  }
+}
+
+#[test]
+fn test_png_rgba8_byte_size() {
+  use imagine::{png::png_rgba8_byte_size, ImagineError};
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  assert_eq!(png_rgba8_byte_size(&v), Ok(32 * 32 * 4));
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/s39n3p04.png").unwrap();
+  assert_eq!(png_rgba8_byte_size(&v), Ok(39 * 39 * 4));
+  assert_eq!(png_rgba8_byte_size(&[]), Err(ImagineError::Parse));
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_decode_options_ancillary_errors() {
+  use imagine::{
+    png::{png_check_ancillary_chunks, png_try_bitmap_rgba_with_options, PngDecodeOptions},
+    Bitmap, ImagineError,
+  };
+
+  let strict = PngDecodeOptions { ignore_ancillary_errors: false, ..Default::default() };
+  for entry in std::fs::read_dir("tests/png/PngSuite-2017jul19").unwrap() {
+    let path = entry.unwrap().path();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    if name.ends_with(".png") && !name.starts_with('x') {
+      let v = std::fs::read(&path).unwrap();
+      assert_eq!(png_check_ancillary_chunks(&v), Ok(()), "{name}");
+    }
+  }
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn0g08.png").unwrap();
+  // put a 3 byte bKGD (invalid for any color type) right after the IHDR.
+  let broken = with_chunk_after_ihdr(&v, b"bKGD", &[1, 2, 3]);
+  let lenient: Bitmap =
+    png_try_bitmap_rgba_with_options(&broken, true, Default::default()).unwrap();
+  let normal: Bitmap = png_try_bitmap_rgba_with_options(&v, true, strict).unwrap();
+  assert_eq!(lenient, normal);
+  let r: Result<Bitmap, _> = png_try_bitmap_rgba_with_options(&broken, true, strict);
+  assert_eq!(r.unwrap_err(), ImagineError::Parse);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_decode_options_max_scratch_bytes() {
+  use imagine::{
+    png::{png_get_header, png_try_bitmap_rgba_with_options, PngDecodeOptions},
+    Bitmap, ImagineError,
+  };
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  // 32 lines of 32 RGB8 pixels, plus a filter byte per line.
+  let needed = png_get_header(&v).unwrap().get_zlib_decompression_requirement();
+  assert_eq!(needed, 32 * (32 * 3 + 1));
+  let options = PngDecodeOptions { max_scratch_bytes: needed, ..Default::default() };
+  let r: Result<Bitmap, _> = png_try_bitmap_rgba_with_options(&v, true, options);
+  assert!(r.is_ok());
+  let options = PngDecodeOptions { max_scratch_bytes: needed - 1, ..Default::default() };
+  let r: Result<Bitmap, _> = png_try_bitmap_rgba_with_options(&v, true, options);
+  assert_eq!(r.unwrap_err(), ImagineError::DimensionsTooLarge);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_try_bitmap_rgb_trns_gray() {
+  use imagine::{
+    png::{png_try_bitmap_rgb, png_try_bitmap_rgba},
+    Bitmap,
+  };
+  use pixel_formats::{r32g32b32_Sfloat, r32g32b32a32_Sfloat};
+
+  let red = r32g32b32_Sfloat { r: 1.0, g: 0.0, b: 0.0 };
+  // 16-bit and 4-bit grayscale, both with a tRNS transparency key.
+  for name in ["tbwn0g16", "tbbn0g04"] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+    let rgba: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+    let rgb: Bitmap<r32g32b32_Sfloat> = png_try_bitmap_rgb(&v, true, red).unwrap();
+    assert_eq!((rgba.width, rgba.height), (rgb.width, rgb.height));
+    let mut keyed = 0;
+    for (a, b) in rgba.pixels.iter().zip(rgb.pixels.iter()) {
+      if a.a == 0.0 {
+        keyed += 1;
+        assert_eq!(*b, red);
+      } else {
+        assert_eq!(a.a, 1.0);
+        assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+      }
+    }
+    assert!(keyed > 0, "{name}");
+  }
+
+  // 8-bit grayscale, with the tRNS chunk added after the header.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn0g08.png").unwrap();
+  let rgba: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+  // tRNS with gray 0.
+  let with_trns = with_chunk_after_ihdr(&v, b"tRNS", &[0, 0]);
+  let rgb: Bitmap<r32g32b32_Sfloat> = png_try_bitmap_rgb(&with_trns, true, red).unwrap();
+  assert_eq!(rgba.pixels[0].r, 0.0);
+  assert_eq!(rgb.pixels[0], red);
+  assert_ne!(rgb.pixels[1], red);
+}
+
+#[test]
+#[cfg(feature = "miniz_oxide")]
+fn test_png_for_each_pixel() {
+  use imagine::png::{png_for_each_pixel, png_get_header, PngSample};
+
+  // 16-bit RGB, the samples keep their full precision.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c16.png").unwrap();
+  let ihdr = png_get_header(&v).unwrap();
+  let mut scratch = vec![0; ihdr.get_zlib_decompression_requirement()];
+  let mut count = 0;
+  let mut first = None;
+  png_for_each_pixel(&v, &mut scratch, |x, y, s| {
+    count += 1;
+    if (x, y) == (0, 0) {
+      first = Some(s);
+    }
+    assert!(matches!(s, PngSample::Rgb16(_)));
+  })
+  .unwrap();
+  assert_eq!(count, 32 * 32);
+  assert_eq!(first, Some(PngSample::Rgb16([u16::MAX, u16::MAX, 0])));
+
+  // interlaced 1-bit grayscale gives unscaled values, once per pixel.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basi0g01.png").unwrap();
+  let ihdr = png_get_header(&v).unwrap();
+  let mut scratch = vec![0; ihdr.get_zlib_decompression_requirement()];
+  let mut seen = vec![false; 32 * 32];
+  png_for_each_pixel(&v, &mut scratch, |x, y, s| {
+    assert!(matches!(s, PngSample::Y8(0 | 1)));
+    let i = (y * 32 + x) as usize;
+    assert!(!seen[i]);
+    seen[i] = true;
+  })
+  .unwrap();
+  assert!(seen.iter().all(|b| *b));
+
+  // indexed data gives the index, not the color.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn3p08.png").unwrap();
+  let ihdr = png_get_header(&v).unwrap();
+  let mut scratch = vec![0; ihdr.get_zlib_decompression_requirement()];
+  png_for_each_pixel(&v, &mut scratch, |_, _, s| assert!(matches!(s, PngSample::Index(_))))
+    .unwrap();
+
+  let mut too_small = vec![0; 10];
+  assert_eq!(
+    png_for_each_pixel(&v, &mut too_small, |_, _, _| ()),
+    Err(imagine::ImagineError::BufferTooSmall)
+  );
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_icc_profile_and_transform() {
+  use imagine::{
+    png::{
+      png_for_each_pixel, png_get_header, png_get_icc_profile, png_try_bitmap_rgba,
+      png_try_bitmap_rgba_with_transform, PngSample,
+    },
+    Bitmap, ImagineError, UnsupportedFormat,
+  };
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  assert!(png_get_icc_profile(&v).is_none());
+  let plain: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+  let same: Bitmap<r32g32b32a32_Sfloat> =
+    png_try_bitmap_rgba_with_transform(&v, true, &super::AsLinear).unwrap();
+  assert_eq!(plain, same);
+
+  // zlib "stored" blocks just hold the bytes as is.
+  let profile = b"not a real profile";
+  let mut zlib = vec![0x78, 0x01, 0x01];
+  zlib.extend_from_slice(&(profile.len() as u16).to_le_bytes());
+  zlib.extend_from_slice(&(!(profile.len() as u16)).to_le_bytes());
+  zlib.extend_from_slice(profile);
+  zlib.extend_from_slice(&[0; 4]); // the adler32 isn't checked
+  let mut data = b"test\0\0".to_vec();
+  data.extend_from_slice(&zlib);
+  let with_iccp = with_chunk_after_ihdr(&v, b"iCCP", &data);
+
+  let iccp = png_get_icc_profile(&with_iccp).unwrap();
+  assert_eq!(iccp.name(), b"test");
+  assert_eq!(iccp.decompress_profile().unwrap(), profile);
+
+  let stored: Bitmap<r32g32b32a32_Sfloat> =
+    png_try_bitmap_rgba_with_transform(&with_iccp, true, &super::AsLinear).unwrap();
+  assert_eq!(stored.pixels.len(), plain.pixels.len());
+  // the transform got the stored values, without gamma applied.
+  let mut scratch = vec![0; png_get_header(&v).unwrap().get_zlib_decompression_requirement()];
+  png_for_each_pixel(&v, &mut scratch, |x, y, sample| {
+    let PngSample::Rgb8([r, g, b]) = sample else { panic!() };
+    let expected =
+      r32g32b32a32_Sfloat { r: r as f32 / 255.0, g: g as f32 / 255.0, b: b as f32 / 255.0, a: 1.0 };
+    assert_eq!(stored.pixels[(y * 32 + x) as usize], expected);
+  })
+  .unwrap();
+
+  // the profile doesn't make an MNG or JNG file decodable.
+  for tag in [*b"\x8AMNG", *b"\x8BJNG"] {
+    let mut mng = with_iccp.clone();
+    mng[..4].copy_from_slice(&tag);
+    let r: Result<Bitmap<r32g32b32a32_Sfloat>, _> =
+      png_try_bitmap_rgba_with_transform(&mng, true, &super::AsLinear);
+    assert_eq!(r, Err(ImagineError::Unsupported(UnsupportedFormat::MngOrJng)));
+  }
+}
+
+#[test]
+fn test_png_bkgd_resolve() {
+  use imagine::png::{
+    bKGD, png_get_background_color, png_get_background_rgba, png_get_header, png_get_palette,
+  };
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  // bgbn4a08: 8-bit gray+alpha, black background.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/bgbn4a08.png").unwrap();
+  let ihdr = png_get_header(&v).unwrap();
+  let bkgd = png_get_background_color(&v).unwrap();
+  assert_eq!(bkgd.resolve(&ihdr, None), Some(r32g32b32a32_Sfloat::OPAQUE_BLACK));
+  assert_eq!(png_get_background_rgba(&v), Some(r32g32b32a32_Sfloat::OPAQUE_BLACK));
+  // the wrong form for the color type doesn't resolve.
+  assert_eq!(bKGD::Index { i: 0 }.resolve(&ihdr, None), None);
+
+  // bgwn6a08: 8-bit RGBA, white background.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/bgwn6a08.png").unwrap();
+  let ihdr = png_get_header(&v).unwrap();
+  let bkgd = png_get_background_color(&v).unwrap();
+  assert_eq!(bkgd.resolve(&ihdr, None), Some(r32g32b32a32_Sfloat::OPAQUE_WHITE));
+
+  // indexed images look up the palette.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/tbbn3p08.png").unwrap();
+  let ihdr = png_get_header(&v).unwrap();
+  let palette = png_get_palette(&v).unwrap();
+  let bkgd = png_get_background_color(&v).unwrap();
+  let bKGD::Index { i } = bkgd else { panic!("{bkgd:?}") };
+  let [r, g, b] = palette[usize::from(i)];
+  let expected =
+    r32g32b32a32_Sfloat { r: r as f32 / 255.0, g: g as f32 / 255.0, b: b as f32 / 255.0, a: 1.0 };
+  assert_eq!(bkgd.resolve(&ihdr, Some(palette)), Some(expected));
+  assert_eq!(bkgd.resolve(&ihdr, None), None);
+}
+
+#[test]
+fn test_png_ihdr_bit_depth_table() {
+  use imagine::png::{PngColorType, IHDR};
+
+  let ihdr_bytes =
+    |color_type: u8, bit_depth: u8| [0, 0, 0, 1, 0, 0, 0, 1, bit_depth, color_type, 0, 0, 0];
+  let legal: [(u8, &[u8]); 5] =
+    [(0, &[1, 2, 4, 8, 16]), (2, &[8, 16]), (3, &[1, 2, 4, 8]), (4, &[8, 16]), (6, &[8, 16])];
+  for (color_type, depths) in legal {
+    assert_eq!(PngColorType::try_from(color_type).unwrap().allowed_bit_depths(), depths);
+    for bit_depth in 0..=u8::MAX {
+      let r = IHDR::try_from(&ihdr_bytes(color_type, bit_depth)[..]);
+      if depths.contains(&bit_depth) {
+        let ihdr = r.unwrap();
+        assert_eq!((ihdr.color_type as u8, ihdr.bit_depth), (color_type, bit_depth));
+      } else {
+        assert_eq!(r, Err(()), "color type {color_type}, bit depth {bit_depth}");
+      }
+    }
+  }
+  // the illegal pairs that real encoders most often get wrong.
+  for (color_type, bit_depth) in [(2, 1), (2, 4), (3, 16), (4, 4), (6, 2), (0, 3), (0, 0)] {
+    assert_eq!(IHDR::try_from(&ihdr_bytes(color_type, bit_depth)[..]), Err(()));
+  }
+  // color types that don't exist are always rejected.
+  for color_type in [1, 5, 7, 255] {
+    assert_eq!(IHDR::try_from(&ihdr_bytes(color_type, 8)[..]), Err(()));
+  }
+}
+
+#[test]
+fn test_png_collect_metadata() {
+  use imagine::{png::*, ImagineError};
+
+  for name in [
+    "basn3p04.png",
+    "tbbn3p08.png",
+    "g03n2c08.png",
+    "bgai4a16.png",
+    "basn0g08.png",
+    "ccwn2c08.png",
+    "cdun2c08.png",
+    "cm7n0g04.png",
+    "ct1n0g04.png",
+  ] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}")).unwrap();
+    let m = png_collect_metadata(&v).unwrap();
+    assert_eq!(Some(m.header), png_get_header(&v), "{name}");
+    assert_eq!(m.srgb, png_get_srgb(&v), "{name}");
+    assert_eq!(m.gamma, png_get_gamma(&v), "{name}");
+    assert_eq!(m.palette, png_get_palette(&v), "{name}");
+    assert_eq!(m.transparency, png_get_transparency(&v), "{name}");
+    assert_eq!(m.background, png_get_background_color(&v), "{name}");
+    assert_eq!(m.icc_profile, png_get_icc_profile(&v), "{name}");
+    assert_eq!(m.chromaticities, png_get_chromaticities(&v), "{name}");
+    assert_eq!(m.physical_dimensions, png_get_physical_dimensions(&v), "{name}");
+    assert_eq!(m.time, png_get_time(&v), "{name}");
+    assert_eq!(m.text, png_get_text(&v).next(), "{name}");
+  }
+  let dir = "tests/png/PngSuite-2017jul19";
+  let v = std::fs::read(format!("{dir}/ccwn2c08.png")).unwrap();
+  let chrm = png_collect_metadata(&v).unwrap().chromaticities.unwrap();
+  assert_eq!((chrm.white, chrm.blue), ([31270, 32900], [15000, 6000]));
+  let v = std::fs::read(format!("{dir}/cdun2c08.png")).unwrap();
+  let phys = png_collect_metadata(&v).unwrap().physical_dimensions.unwrap();
+  assert_eq!(phys, pHYs { x: 1000, y: 1000, unit_is_meter: true });
+  let (x, _y) = phys.dpi().unwrap();
+  assert!((x - 25.4).abs() < 0.01);
+  let v = std::fs::read(format!("{dir}/cm7n0g04.png")).unwrap();
+  let time = png_collect_metadata(&v).unwrap().time.unwrap();
+  assert_eq!(time, tIME { year: 1970, month: 1, day: 1, hour: 0, minute: 0, second: 0 });
+  let v = std::fs::read(format!("{dir}/ct1n0g04.png")).unwrap();
+  let text = png_collect_metadata(&v).unwrap().text.unwrap();
+  assert_eq!((text.keyword(), text.text()), (&b"Title"[..], &b"PngSuite"[..]));
+  assert_eq!(png_get_text(&v).count(), 6);
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/tbbn3p08.png").unwrap();
+  let m = png_collect_metadata(&v).unwrap();
+  assert!(m.palette.is_some() && m.transparency.is_some() && m.background.is_some());
+
+  assert_eq!(png_collect_metadata(&[]), Err(ImagineError::Parse));
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_zero_gamma() {
+  use imagine::{
+    png::{png_check_ancillary_chunks, png_try_bitmap_rgba, png_try_bitmap_rgba_with_options},
+    Bitmap, ImagineError,
+  };
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  // this file has a gAMA of 1.0, so put a gAMA of 0 in front of that one.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn0g08.png").unwrap();
+  let zero = with_chunk_after_ihdr(&v, b"gAMA", &[0, 0, 0, 0]);
+
+  // a zero gamma is treated like no gamma at all, which is gamma 1.0.
+  let normal: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+  let zeroed: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&zero, true).unwrap();
+  assert!(zeroed.pixels.iter().all(|p| p.r.is_finite() && p.g.is_finite() && p.b.is_finite()));
+  assert_eq!(zeroed, normal);
+
+  // but it's still not a valid file.
+  assert_eq!(png_check_ancillary_chunks(&zero), Err(ImagineError::Parse));
+  let strict =
+    imagine::png::PngDecodeOptions { ignore_ancillary_errors: false, ..Default::default() };
+  let r: Result<Bitmap, _> = png_try_bitmap_rgba_with_options(&zero, true, strict);
+  assert_eq!(r.unwrap_err(), ImagineError::Parse);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_idat_contiguity() {
+  use imagine::{
+    png::{
+      png_collect_metadata, png_idat_is_contiguous, png_try_bitmap_rgba,
+      png_try_bitmap_rgba_with_options, PngDecodeOptions,
+    },
+    Bitmap, ImagineError,
+  };
+
+  // this file has two IDAT chunks, the second one starts at byte 125.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/oi2n0g16.png").unwrap();
+  assert!(png_idat_is_contiguous(&v));
+  assert!(png_collect_metadata(&v).unwrap().idat_is_contiguous);
+  // put an empty tEXt chunk between the two IDAT chunks.
+  let mut split = v[..125].to_vec();
+  split.extend_from_slice(&[0, 0, 0, 0, b't', b'E', b'X', b't', 0, 0, 0, 0]);
+  split.extend_from_slice(&v[125..]);
+  assert!(!png_idat_is_contiguous(&split));
+  assert!(!png_collect_metadata(&split).unwrap().idat_is_contiguous);
+
+  // it's allowed by default, but strict decoding rejects it.
+  let normal: Bitmap = png_try_bitmap_rgba(&v, true).unwrap();
+  let lenient: Bitmap = png_try_bitmap_rgba(&split, true).unwrap();
+  assert_eq!(lenient, normal);
+  let strict = PngDecodeOptions { require_contiguous_idat: true, ..Default::default() };
+  let r: Result<Bitmap, _> = png_try_bitmap_rgba_with_options(&v, true, strict);
+  assert_eq!(r.unwrap(), normal);
+  let r: Result<Bitmap, _> = png_try_bitmap_rgba_with_options(&split, true, strict);
+  assert_eq!(r.unwrap_err(), ImagineError::Parse);
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_decode_rgba8() {
+  use imagine::{decode_rgba8, png::png_try_bitmap_rgba, Bitmap};
+  use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Unorm};
+
+  // RGBA8 with a gamma of 1.0, so the pixels are copied directly.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn6a08.png").unwrap();
+  let fast: Bitmap<r8g8b8a8_Unorm> = decode_rgba8(&v).unwrap();
+  assert_eq!((fast.width, fast.height), (32, 32));
+  // the float decode, un-multiplied by hand, should agree (within rounding).
+  let float: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+  let u8f = |c: f32| (c * 255.0).round() as i32;
+  for (f, p) in fast.pixels.iter().zip(float.pixels.iter()) {
+    if p.a == 0.0 {
+      assert_eq!(*f, r8g8b8a8_Unorm::TRANSPARENT_BLACK);
+      continue;
+    }
+    let expected = [u8f(p.r / p.a), u8f(p.g / p.a), u8f(p.b / p.a), u8f(p.a)];
+    let actual = [f.r, f.g, f.b, f.a].map(i32::from);
+    for (e, a) in expected.iter().zip(actual.iter()) {
+      assert!((e - a).abs() <= 1, "{expected:?} vs {actual:?}");
+    }
+  }
+
+  // with an sRGB chunk it goes through the float path instead.
+  let srgb = with_chunk_after_ihdr(&v, b"sRGB", &[0]);
+  let slow: Bitmap<r8g8b8a8_Unorm> = decode_rgba8(&srgb).unwrap();
+  assert_eq!((slow.width, slow.height), (32, 32));
+  // sRGB encoded values are brighter than linear ones, so decoding them as
+  // sRGB makes the linear output darker.
+  let sum = |b: &Bitmap<r8g8b8a8_Unorm>| b.pixels.iter().map(|p| u32::from(p.r)).sum::<u32>();
+  assert!(sum(&slow) < sum(&fast));
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_decode_scanlines_no_alloc() {
+  use imagine::{
+    decode_rgba8,
+    png::{png_decode_scanlines_no_alloc, png_get_header},
+    Bitmap, ImagineError,
+  };
+  use pixel_formats::r8g8b8a8_Unorm;
+
+  for name in ["basn2c08", "basn3p04", "basn4a16", "tbrn2c08"] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+    let expected: Bitmap<r8g8b8a8_Unorm> = decode_rgba8(&v).unwrap();
+    let ihdr = png_get_header(&v).unwrap();
+    let mut scratch = vec![0_u8; ihdr.get_zlib_decompression_requirement()];
+    let mut row_buf = [r8g8b8a8_Unorm::default(); 40];
+    let mut next_y = 0;
+    png_decode_scanlines_no_alloc(&v, &mut scratch, &mut row_buf, |y, row| {
+      assert_eq!(y, next_y, "{name}");
+      assert_eq!(row, expected.pixels.chunks_exact(32).nth(y as usize).unwrap(), "{name}");
+      next_y += 1;
+    })
+    .unwrap();
+    assert_eq!(next_y, 32, "{name}");
+
+    let r = png_decode_scanlines_no_alloc(&v, &mut scratch, &mut row_buf[..31], |_, _| ());
+    assert_eq!(r, Err(ImagineError::BufferTooSmall));
+    let r = png_decode_scanlines_no_alloc(&v, &mut scratch[1..], &mut row_buf, |_, _| ());
+    assert_eq!(r, Err(ImagineError::BufferTooSmall));
+  }
+
+  // interlaced rows arrive in pieces, so they're rejected.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basi2c08.png").unwrap();
+  let mut scratch = vec![0_u8; png_get_header(&v).unwrap().get_zlib_decompression_requirement()];
+  let mut row_buf = [r8g8b8a8_Unorm::default(); 32];
+  let r = png_decode_scanlines_no_alloc(&v, &mut scratch, &mut row_buf, |_, _| ());
+  assert_eq!(r, Err(ImagineError::Parse));
+}
+
+#[test]
+fn test_png_suggested_palettes() {
+  use imagine::png::{png_get_suggested_palettes, SuggestedPaletteEntry};
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/ps1n0g08.png").unwrap();
+  let palettes: Vec<_> = png_get_suggested_palettes(&v).collect();
+  assert_eq!(palettes.len(), 1);
+  assert_eq!(palettes[0].name(), b"six-cube");
+  assert_eq!(palettes[0].sample_depth(), 8);
+  assert_eq!(palettes[0].entries().count(), 216);
+  let second = SuggestedPaletteEntry { r: 0, g: 0, b: 0x33, a: 0xFF, frequency: 0 };
+  assert_eq!(palettes[0].entries().nth(1), Some(second));
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/ps2n0g08.png").unwrap();
+  let palettes: Vec<_> = png_get_suggested_palettes(&v).collect();
+  assert_eq!(palettes.len(), 1);
+  assert_eq!(palettes[0].sample_depth(), 16);
+  assert_eq!(palettes[0].entries().count(), 216);
+  assert_eq!(palettes[0].entries().nth(1), Some(second));
+
+  // a bad sample depth is skipped, and a partial entry is ignored.
+  let bad = with_chunk_after_ihdr(&v, b"sPLT", b"short\x00\x08\x01\x02\x03\x04\x00\x05\x09");
+  let bad = with_chunk_after_ihdr(&bad, b"sPLT", b"depth\x00\x07\x01\x02\x03\x04\x00\x05");
+  let palettes: Vec<_> = png_get_suggested_palettes(&bad).collect();
+  assert_eq!(palettes.len(), 2);
+  assert_eq!(palettes[0].name(), b"short");
+  let only = SuggestedPaletteEntry { r: 1, g: 2, b: 3, a: 4, frequency: 5 };
+  assert_eq!(palettes[0].entries().collect::<Vec<_>>(), vec![only]);
+  assert_eq!(palettes[1].name(), b"six-cube");
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_png_mng_and_jng_are_unsupported() {
+  use imagine::{
+    png::{png_signature_is_mng_or_jng, png_try_bitmap_rgba},
+    try_bitmap_rgba, Bitmap, ImagineError, UnsupportedFormat,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  assert!(!png_signature_is_mng_or_jng(&v));
+  for tag in [*b"\x8AMNG", *b"\x8BJNG"] {
+    let mut v = v.clone();
+    v[..4].copy_from_slice(&tag);
+    assert!(png_signature_is_mng_or_jng(&v));
+    let expected = Err(ImagineError::Unsupported(UnsupportedFormat::MngOrJng));
+    let r: Result<Bitmap<r8g8b8a8_Srgb>, _> = png_try_bitmap_rgba(&v, true);
+    assert_eq!(r, expected);
+    let r: Result<Bitmap<r8g8b8a8_Srgb>, _> = try_bitmap_rgba(&v, true);
+    assert_eq!(r, expected);
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_write_bitmap() {
+  use imagine::{
+    png::{png_get_header, png_try_bitmap_rgba, png_write_bitmap, PngColorType, PngWriteFormat},
+    Bitmap,
+  };
+  use pixel_formats::{r32g32b32a32_Sfloat, r8g8b8a8_Srgb};
+
+  // a grey "scan" with some noise in it.
+  let mut seed = 12345_u32;
+  let pixels: Vec<r8g8b8a8_Srgb> = (0..(64 * 48))
+    .map(|_| {
+      seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+      let y = (seed >> 24) as u8;
+      r8g8b8a8_Srgb { r: y, g: y, b: y, a: 255 }
+    })
+    .collect();
+  let scan = Bitmap { width: 64, height: 48, pixels };
+
+  let rgba = png_write_bitmap(&scan, PngWriteFormat::Rgba8).unwrap();
+  let y8 = png_write_bitmap(&scan, PngWriteFormat::Y8).unwrap();
+  let ihdr = png_get_header(&y8).unwrap();
+  assert_eq!((ihdr.width, ihdr.height), (64, 48));
+  assert_eq!((ihdr.color_type, ihdr.bit_depth), (PngColorType::Y, 8));
+  assert!(y8.len() < rgba.len(), "{} vs {}", y8.len(), rgba.len());
+  // every file ends with the same IEND chunk, including its CRC.
+  assert!(y8.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+  let back: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&y8, true).unwrap();
+  assert_eq!(back, scan);
+  let back: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&rgba, true).unwrap();
+  assert_eq!(back, scan);
+
+  // 16-bit greyscale keeps linear values to full precision.
+  let ramp = Bitmap {
+    width: 256,
+    height: 1,
+    pixels: (0..256_u16)
+      .map(|i| {
+        let y = f32::from(i * 257) / 65535.0;
+        r32g32b32a32_Sfloat { r: y, g: y, b: y, a: 1.0 }
+      })
+      .collect::<Vec<_>>(),
+  };
+  let y16 = png_write_bitmap(&ramp, PngWriteFormat::Y16).unwrap();
+  let ihdr = png_get_header(&y16).unwrap();
+  assert_eq!((ihdr.color_type, ihdr.bit_depth), (PngColorType::Y, 16));
+  let back: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&y16, true).unwrap();
+  for (b, r) in back.pixels.iter().zip(ramp.pixels.iter()) {
+    assert!((b.r - r.r).abs() < 1e-6 && b.a == 1.0, "{b:?} vs {r:?}");
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_trns_before_plte() {
+  use imagine::{
+    png::{png_check_ancillary_chunks, png_try_bitmap_rgba},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  // split the file into its chunks, each with the length, type, data, and CRC.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/tbbn3p08.png").unwrap();
+  let mut chunks: Vec<&[u8]> = Vec::new();
+  let mut rest = &v[8..];
+  while !rest.is_empty() {
+    let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+    let (chunk, tail) = rest.split_at(12 + len);
+    chunks.push(chunk);
+    rest = tail;
+  }
+  let plte = chunks.iter().position(|c| &c[4..8] == b"PLTE").unwrap();
+  let trns = chunks.iter().position(|c| &c[4..8] == b"tRNS").unwrap();
+  assert!(plte < trns);
+  let trns_chunk = chunks.remove(trns);
+  chunks.insert(plte, trns_chunk);
+  let mut swapped = v[..8].to_vec();
+  chunks.iter().for_each(|c| swapped.extend_from_slice(c));
+  assert_eq!(swapped.len(), v.len());
+  assert_ne!(swapped, v);
+
+  assert_eq!(png_check_ancillary_chunks(&swapped), Ok(()));
+  let expected: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&v, true).unwrap();
+  let actual: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&swapped, true).unwrap();
+  assert_eq!(actual, expected);
+  // the transparency really is used.
+  assert!(actual.pixels.iter().any(|p| p.a == 0));
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_try_bitmap_rgba8_lossless() {
+  use imagine::png::{
+    png_for_each_pixel, png_get_header, png_try_bitmap_rgba8_lossless, PngSample,
+  };
+  use pixel_formats::r8g8b8a8_Unorm;
+
+  // RGBA8 samples are copied exactly, even when partly transparent.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn6a08.png").unwrap();
+  let bitmap = png_try_bitmap_rgba8_lossless(&v).unwrap();
+  assert_eq!((bitmap.width, bitmap.height), (32, 32));
+  let mut scratch = vec![0_u8; png_get_header(&v).unwrap().get_zlib_decompression_requirement()];
+  let mut checked = 0;
+  png_for_each_pixel(&v, &mut scratch, |x, y, sample| {
+    let PngSample::Rgba8([r, g, b, a]) = sample else { panic!() };
+    assert_eq!(bitmap.pixels[(y * 32 + x) as usize], r8g8b8a8_Unorm { r, g, b, a });
+    checked += 1;
+  })
+  .unwrap();
+  assert_eq!(checked, 32 * 32);
+  assert!(bitmap.pixels.iter().any(|p| p.a != 0 && p.a != 255 && p.r != 0));
+
+  // 4-bit greyscale is scaled up to the full range.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn0g04.png").unwrap();
+  let bitmap = png_try_bitmap_rgba8_lossless(&v).unwrap();
+  assert!(bitmap.pixels.iter().all(|p| p.r % 17 == 0 && p.r == p.g && p.a == 255));
+  assert!(bitmap.pixels.iter().any(|p| p.r == 238));
+
+  // palette alpha comes from tRNS.
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/tbbn3p08.png").unwrap();
+  let bitmap = png_try_bitmap_rgba8_lossless(&v).unwrap();
+  assert!(bitmap.pixels.iter().any(|p| p.a == 0));
+  assert!(bitmap.pixels.iter().any(|p| p.a == 255));
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_suite_decodes_are_valid_premultiplied() {
+  use imagine::{png::png_try_bitmap_rgba, Bitmap};
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  for entry in std::fs::read_dir("tests/png/PngSuite-2017jul19").unwrap() {
+    let path = entry.unwrap().path();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    if name.ends_with(".png") && !name.starts_with('x') {
+      let v = std::fs::read(&path).unwrap();
+      let bitmap: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+      assert!(bitmap.is_valid_premultiplied(1e-6), "{name}");
+    }
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_try_bitmap_rgba_partial() {
+  use imagine::{
+    png::{png_try_bitmap_rgba, png_try_bitmap_rgba_partial},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let clear = r8g8b8a8_Srgb { r: 0, g: 0, b: 0, a: 0 };
+  for name in ["basn2c08", "basi2c08", "basn3p04", "basn0g16"] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+    let full: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&v, true).unwrap();
+    let (partial, rows): (Bitmap<r8g8b8a8_Srgb>, u32) =
+      png_try_bitmap_rgba_partial(&v, true, 10).unwrap();
+    assert_eq!(rows, 10, "{name}");
+    assert_eq!((partial.width, partial.height), (32, 32), "{name}");
+    assert_eq!(partial.pixels[..320], full.pixels[..320], "{name}");
+    assert!(partial.pixels[320..].iter().all(|&p| p == clear), "{name}");
+
+    let (all, rows): (Bitmap<r8g8b8a8_Srgb>, u32) =
+      png_try_bitmap_rgba_partial(&v, true, 1000).unwrap();
+    assert_eq!(rows, 32, "{name}");
+    assert_eq!(all, full, "{name}");
+
+    let (none, rows): (Bitmap<r8g8b8a8_Srgb>, u32) =
+      png_try_bitmap_rgba_partial(&v, true, 0).unwrap();
+    assert_eq!(rows, 0, "{name}");
+    assert!(none.pixels.iter().all(|&p| p == clear), "{name}");
+
+    // flipped, the decoded rows are at the bottom.
+    let (flipped, _): (Bitmap<r8g8b8a8_Srgb>, u32) =
+      png_try_bitmap_rgba_partial(&v, false, 10).unwrap();
+    assert_eq!(flipped.pixels[32 * 31..], full.pixels[..32], "{name}");
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_16_bit_precision_is_kept() {
+  use imagine::{
+    png::{png_for_each_pixel, png_get_header, png_try_bitmap_rgba, PngSample},
+    Bitmap,
+  };
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  // There's no 16-bit float pixel type to use, so this is a stand in that just
+  // needs the same `From` impl that any other output type would.
+  #[derive(Debug, Clone, Copy, PartialEq)]
+  struct Rgba16([u16; 4]);
+  impl From<r32g32b32a32_Sfloat> for Rgba16 {
+    fn from(c: r32g32b32a32_Sfloat) -> Self {
+      let u = |f: f32| (f.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16;
+      Rgba16([u(c.r), u(c.g), u(c.b), u(c.a)])
+    }
+  }
+
+  for name in ["basn2c16", "basn0g16", "basi2c16"] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+    let bitmap: Bitmap<Rgba16> = png_try_bitmap_rgba(&v, true).unwrap();
+    let ihdr = png_get_header(&v).unwrap();
+    let mut scratch = vec![0; ihdr.get_zlib_decompression_requirement()];
+    let mut beyond_8_bits = false;
+    png_for_each_pixel(&v, &mut scratch, |x, y, sample| {
+      let expected = match sample {
+        PngSample::Y16(y) => [y, y, y, u16::MAX],
+        PngSample::Rgb16([r, g, b]) => [r, g, b, u16::MAX],
+        other => panic!("{name}: {other:?}"),
+      };
+      beyond_8_bits |= expected.iter().any(|&c| c % 257 != 0);
+      let i = (y * bitmap.width + x) as usize;
+      assert_eq!(bitmap.pixels[i].0, expected, "{name} ({x},{y})");
+    })
+    .unwrap();
+    assert!(beyond_8_bits, "{name}");
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_try_bitmap_rgba_diagnostic() {
+  use imagine::{
+    png::{png_try_bitmap_rgba, png_try_bitmap_rgba_diagnostic, PngDiagnostics},
+    Bitmap,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let read =
+    |name: &str| std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+  let diagnose = |v: &[u8]| {
+    let (bitmap, d): (Bitmap<r8g8b8a8_Srgb>, PngDiagnostics) =
+      png_try_bitmap_rgba_diagnostic(v, true).unwrap();
+    let expected: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(v, true).unwrap();
+    assert_eq!(bitmap, expected);
+    d
+  };
+
+  let v = read("basn2c08");
+  let d = diagnose(&v);
+  assert_eq!(d.filter_counts.iter().sum::<u32>(), 32);
+  assert_eq!(d.bad_filter_count, 0);
+  assert_eq!(d.data_len, d.expected_data_len);
+  assert!(d.idat_len > 0 && d.idat_len < v.len());
+  assert!(!d.zlib_trailing_bytes && !d.zlib_error && !d.crc_mismatch);
+
+  // each filter type has its own test image.
+  for (i, name) in ["f00n2c08", "f01n2c08", "f02n2c08", "f03n2c08", "f04n2c08"].iter().enumerate() {
+    let d = diagnose(&read(name));
+    assert_eq!(d.filter_counts[i], 32, "{name}: {d:?}");
+  }
+
+  // interlaced images count the lines of all 7 reduced images.
+  let d = diagnose(&read("basi2c08"));
+  assert_eq!(d.filter_counts.iter().sum::<u32>(), 4 + 4 + 4 + 8 + 8 + 16 + 16);
+
+  // bad IDAT CRC
+  assert!(diagnose(&read("xcsn0g01")).crc_mismatch);
+
+  // put some junk after the zlib stream, inside the same IDAT.
+  let idat_pos = v.windows(4).position(|w| w == b"IDAT").unwrap() - 4;
+  let idat_len = u32::from_be_bytes(v[idat_pos..idat_pos + 4].try_into().unwrap()) as usize;
+  let data_end = idat_pos + 8 + idat_len;
+  let mut junk = v.clone();
+  junk.splice(data_end..data_end, [1, 2, 3, 4]);
+  junk[idat_pos..idat_pos + 4].copy_from_slice(&(idat_len as u32 + 4).to_be_bytes());
+  let d = diagnose(&junk);
+  assert!(d.zlib_trailing_bytes && !d.zlib_error && d.crc_mismatch, "{d:?}");
+  assert_eq!(d.idat_len, idat_len + 4);
+
+  // cut the zlib stream short.
+  let mut short = v.clone();
+  let cut = idat_len / 2;
+  short[idat_pos..idat_pos + 4].copy_from_slice(&((idat_len - cut) as u32).to_be_bytes());
+  short.drain(data_end - cut..data_end);
+  let (_, d): (Bitmap<r8g8b8a8_Srgb>, PngDiagnostics) =
+    png_try_bitmap_rgba_diagnostic(&short, true).unwrap();
+  assert!(d.zlib_error && d.data_len < d.expected_data_len, "{d:?}");
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_palette_presence() {
+  use imagine::{
+    png::{png_try_bitmap_rgba, png_try_bitmap_rgba_with_options, PngDecodeOptions},
+    Bitmap, ImagineError,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let read = |name| std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+  let without_plte = |v: &[u8]| {
+    let plte = v.windows(4).position(|w| w == b"PLTE").unwrap() - 4;
+    let plte_len = u32::from_be_bytes(v[plte..plte + 4].try_into().unwrap()) as usize;
+    let mut out = v.to_vec();
+    out.drain(plte..plte + 12 + plte_len);
+    out
+  };
+
+  // an indexed image without a PLTE can't be decoded.
+  let v = read("basn3p08");
+  assert!(png_try_bitmap_rgba::<r8g8b8a8_Srgb>(&v, true).is_ok());
+  assert_eq!(
+    png_try_bitmap_rgba::<r8g8b8a8_Srgb>(&without_plte(&v), true),
+    Err(ImagineError::Parse)
+  );
+
+  // a PLTE in a truecolor image is just a suggestion, and is ignored.
+  let strict = PngDecodeOptions { ignore_ancillary_errors: false, ..Default::default() };
+  for name in ["pp0n2c16", "pp0n6a08"] {
+    let v = read(name);
+    let actual: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba_with_options(&v, true, strict).unwrap();
+    let expected: Bitmap<r8g8b8a8_Srgb> = png_try_bitmap_rgba(&without_plte(&v), true).unwrap();
+    assert_eq!(actual, expected, "{name}");
+  }
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_try_indexed_rgba() {
+  use imagine::{
+    png::{png_try_bitmap_rgba, png_try_indexed_rgba},
+    Bitmap, ImagineError,
+  };
+  use pixel_formats::r32g32b32a32_Sfloat;
+
+  // different bit depths, interlacing, and a tRNS palette.
+  for name in ["basn3p01", "basn3p02", "basn3p04", "basn3p08", "basi3p04", "tbbn3p08"] {
+    let v = std::fs::read(format!("tests/png/PngSuite-2017jul19/{name}.png")).unwrap();
+    let (indexes, palette, width, height) = png_try_indexed_rgba(&v).unwrap();
+    let expected: Bitmap<r32g32b32a32_Sfloat> = png_try_bitmap_rgba(&v, true).unwrap();
+    assert_eq!((width, height), (expected.width, expected.height), "{name}");
+    assert_eq!(indexes.len(), expected.pixels.len(), "{name}");
+    for (i, p) in indexes.iter().zip(expected.pixels.iter()) {
+      assert_eq!(palette[usize::from(*i)], *p, "{name}");
+    }
+  }
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn2c08.png").unwrap();
+  assert_eq!(png_try_indexed_rgba(&v), Err(ImagineError::Parse));
+}
+
+#[test]
+#[cfg(all(feature = "alloc", feature = "miniz_oxide"))]
+fn test_png_zero_dimensions_are_rejected() {
+  use imagine::{
+    png::{
+      png_for_each_pixel, png_get_header, png_try_bitmap_rgba, png_try_bitmap_rgba8_lossless,
+      png_try_bitmap_rgba_diagnostic, png_try_bitmap_rgba_partial, png_try_indexed_rgba,
+    },
+    try_bitmap_rgba, Bitmap, ImagineError,
+  };
+  use pixel_formats::r8g8b8a8_Srgb;
+
+  let v = std::fs::read("tests/png/PngSuite-2017jul19/basn3p08.png").unwrap();
+  // the IHDR data starts after the signature, chunk length, and chunk type.
+  let (width, height) = (16..20, 20..24);
+  for zeroed in [vec![width.clone()], vec![height.clone()], vec![width, height]] {
+    let mut bad = v.clone();
+    zeroed.iter().for_each(|r| bad[r.clone()].fill(0));
+    assert_eq!(png_get_header(&bad), None);
+    assert_eq!(png_try_bitmap_rgba::<r8g8b8a8_Srgb>(&bad, true), Err(ImagineError::Parse));
+    assert_eq!(try_bitmap_rgba::<r8g8b8a8_Srgb>(&bad, true), Err(ImagineError::Parse));
+    assert_eq!(
+      png_try_bitmap_rgba_partial::<r8g8b8a8_Srgb>(&bad, true, 5).map(|_| ()),
+      Err(ImagineError::Parse)
+    );
+    assert_eq!(
+      png_try_bitmap_rgba_diagnostic::<r8g8b8a8_Srgb>(&bad, true).map(|_| ()),
+      Err(ImagineError::Parse)
+    );
+    assert_eq!(png_try_bitmap_rgba8_lossless(&bad).map(|_| ()), Err(ImagineError::Parse));
+    assert_eq!(png_try_indexed_rgba(&bad).map(|_| ()), Err(ImagineError::Parse));
+    assert_eq!(png_for_each_pixel(&bad, &mut [0; 64], |_, _, _| ()), Err(ImagineError::Parse));
+
+    // random image data after the bad header doesn't panic either.
+    for _ in 0..10 {
+      let mut fuzzed = bad[..33].to_vec();
+      fuzzed.extend_from_slice(&super::rand_bytes(256));
+      let _: Result<Bitmap<r8g8b8a8_Srgb>, _> = png_try_bitmap_rgba(&fuzzed, true);
+    }
+  }
+}